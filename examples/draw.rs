@@ -1,25 +1,7 @@
 use image::*;
 
-use dlib_face_recognition::*;
-
-fn draw_rectangle(image: &mut RgbImage, rect: &Rectangle, colour: Rgb<u8>) {
-    for x in rect.left..rect.right {
-        image.put_pixel(x as u32, rect.top as u32, colour);
-        image.put_pixel(x as u32, rect.bottom as u32, colour);
-    }
-
-    for y in rect.top..rect.bottom {
-        image.put_pixel(rect.left as u32, y as u32, colour);
-        image.put_pixel(rect.right as u32, y as u32, colour);
-    }
-}
-
-fn draw_point(image: &mut RgbImage, point: &Point, colour: Rgb<u8>) {
-    image.put_pixel(point.x as u32, point.y as u32, colour);
-    image.put_pixel(point.x as u32 + 1, point.y as u32, colour);
-    image.put_pixel(point.x as u32 + 1, point.y as u32 + 1, colour);
-    image.put_pixel(point.x as u32, point.y as u32 + 1, colour);
-}
+use fofscreen::drawing::{DrawStyle, FaceAnnotation};
+use fofscreen::*;
 
 fn tick<R>(name: &str, f: impl Fn() -> R) -> R {
     let now = std::time::Instant::now();
@@ -34,7 +16,7 @@ fn main() {
     let input = args.next().unwrap();
     let output = args.next().unwrap();
 
-    let mut image = image::open(input).unwrap().to_rgb();
+    let mut image = image::open(input).unwrap().to_rgb8();
     let matrix = ImageMatrix::from_image(&image);
 
     let detector = FaceDetector::default();
@@ -43,31 +25,30 @@ fn main() {
 
     let red = Rgb([255, 0, 0]);
     let green = Rgb([0, 255, 0]);
+    let style = DrawStyle::default();
 
     let face_locations = tick("FaceDetector", || detector.face_locations(&matrix));
 
     for r in face_locations.iter() {
-        draw_rectangle(&mut image, &r, red);
+        let landmarks = landmarks.face_landmarks(&matrix, r);
 
-        let landmarks = landmarks.face_landmarks(&matrix, &r);
-
-        for point in landmarks.iter() {
-            draw_point(&mut image, &point, red);
-        }
+        FaceAnnotation::new(*r)
+            .with_landmarks(&landmarks)
+            .with_label("hog")
+            .with_colour(red)
+            .draw(&mut image, &style);
     }
 
     let face_locations = tick("FaceDetectorCnn", || cnn_detector.face_locations(&matrix));
 
     for r in face_locations.iter() {
-        draw_rectangle(&mut image, &r, green);
-
-        let landmarks = tick("LandmarkPredictor", || {
-            landmarks.face_landmarks(&matrix, &r)
-        });
+        let landmarks = tick("LandmarkPredictor", || landmarks.face_landmarks(&matrix, r));
 
-        for point in landmarks.iter() {
-            draw_point(&mut image, &point, green);
-        }
+        FaceAnnotation::new(*r)
+            .with_landmarks(&landmarks)
+            .with_label("cnn")
+            .with_colour(green)
+            .draw(&mut image, &style);
     }
 
     image.save(&output).unwrap();
@@ -0,0 +1,322 @@
+//! Face attributes that make recognition unreliable, like masks and sunglasses.
+//!
+//! A face that doesn't match any reference isn't necessarily a stranger: a mask or sunglasses hide enough
+//! of a known face to push it past the match threshold. Attribute estimates let the alerting tell the two apart.
+//!
+//! Attributes are estimated by [`AttributeClassifier`]s, e.g. a network trained on face chips, or the
+//! [`OcclusionHeuristic`], which compares the regions of the face outlined by the 68 point landmarks.
+
+use std::fmt;
+
+use image::{Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+
+use crate::geometry::{Point, Rectangle};
+use crate::landmark_prediction::FaceLandmarks;
+use crate::liveness::ramp;
+use crate::matrix::ImageMatrix;
+use crate::quality::{laplacian_variance, luma};
+
+/// Something a face is wearing, or that hides part of it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Attribute {
+    /// Glasses dark enough to hide the eyes.
+    Sunglasses,
+    /// A mask, scarf or hand over the mouth and nose.
+    Mask,
+}
+
+impl Attribute {
+    /// Whether the attribute hides part of the face from the encoder.
+    pub fn is_occlusion(self) -> bool {
+        matches!(self, Attribute::Sunglasses | Attribute::Mask)
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Attribute::Sunglasses => write!(f, "sunglasses"),
+            Attribute::Mask => write!(f, "mask"),
+        }
+    }
+}
+
+/// How confident a classifier is that a face has an attribute, from 0 to 1.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Estimate {
+    pub attribute: Attribute,
+    pub confidence: f64,
+}
+
+/// The most confident of `estimates` of an attribute hiding part of the face.
+pub fn occlusion(estimates: &[Estimate]) -> Option<&Estimate> {
+    estimates
+        .iter()
+        .filter(|estimate| estimate.attribute.is_occlusion())
+        .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+}
+
+/// A detected face, as given to an [`AttributeClassifier`].
+pub struct FaceSample<'a> {
+    /// The frame the face was found in.
+    pub image: &'a ImageMatrix,
+    /// The landmarks of the face, in the coordinates of `image`.
+    pub landmarks: &'a FaceLandmarks,
+    /// The aligned chip of the face, as the encoder sees it.
+    pub chip: &'a RgbImage,
+}
+
+/// Estimates attributes of faces.
+///
+/// Classifiers are shared by the workers of the pipeline's landmark stage, so they can't modify themselves
+/// while classifying.
+pub trait AttributeClassifier: Send + Sync {
+    /// The attributes found on the face, leaving out those the classifier doesn't estimate.
+    fn classify(&self, face: &FaceSample) -> Vec<Estimate>;
+}
+
+/// Estimates masks and sunglasses by comparing regions of the face outlined by 68 point landmarks.
+///
+/// A mask replaces the lips and the shading around the mouth with smooth cloth of another colour
+/// than the skin of the nose bridge. Sunglasses make the eyes much darker than the nose.
+/// Faces with another number of landmarks aren't classified.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct OcclusionHeuristic;
+
+impl AttributeClassifier for OcclusionHeuristic {
+    fn classify(&self, face: &FaceSample) -> Vec<Estimate> {
+        occlusion_estimates(face.landmarks, |x, y| {
+            if x < 0 || y < 0 {
+                return None;
+            }
+            face.image.get_pixel(x as usize, y as usize).copied()
+        })
+    }
+}
+
+/// The mean colour and the sharpness of a region of a face.
+struct RegionStats {
+    mean: [f64; 3],
+    brightness: f64,
+    sharpness: f64,
+}
+
+fn region_stats<F>(rect: Rectangle, pixel: &F) -> Option<RegionStats>
+where
+    F: Fn(i64, i64) -> Option<Rgb<u8>>,
+{
+    let (width, height) = (rect.width().max(0) as usize, rect.height().max(0) as usize);
+    let mut sum = [0.0; 3];
+    let mut gray = Vec::with_capacity(width * height);
+
+    for y in rect.top..=rect.bottom {
+        for x in rect.left..=rect.right {
+            let pixel = pixel(x, y)?;
+            for (sum, channel) in sum.iter_mut().zip(pixel.0.iter()) {
+                *sum += *channel as f64;
+            }
+            gray.push(luma(&pixel));
+        }
+    }
+
+    if gray.is_empty() {
+        return None;
+    }
+
+    let len = gray.len() as f64;
+    let mean = [sum[0] / len, sum[1] / len, sum[2] / len];
+
+    Some(RegionStats {
+        mean,
+        brightness: gray.iter().sum::<f64>() / len,
+        sharpness: laplacian_variance(&gray, width, height),
+    })
+}
+
+/// The bounding box of `points`.
+fn bounding_box(points: &[Point]) -> Rectangle {
+    Rectangle {
+        left: points.iter().map(Point::x).min().unwrap_or(0),
+        top: points.iter().map(Point::y).min().unwrap_or(0),
+        right: points.iter().map(Point::x).max().unwrap_or(-1),
+        bottom: points.iter().map(Point::y).max().unwrap_or(-1),
+    }
+}
+
+/// Estimate masks and sunglasses from the 68 landmarks `parts` and the pixels of the frame,
+/// `None` outside of it.
+fn occlusion_estimates<F>(parts: &[Point], pixel: F) -> Vec<Estimate>
+where
+    F: Fn(i64, i64) -> Option<Rgb<u8>>,
+{
+    if parts.len() != 68 {
+        return vec![];
+    }
+
+    // both eyes, from corner to corner
+    let eyes = bounding_box(&parts[36..48]);
+    // the bridge between the eyes, the top of the nose
+    let bridge = bounding_box(&[parts[39], parts[42], parts[27], parts[28]]);
+    // the lower nose, between the nostrils
+    let nose = bounding_box(&[parts[29], parts[31], parts[35], parts[33]]);
+    // from the nostrils to the chin, as wide as the mouth
+    let lower = bounding_box(&[parts[33], parts[48], parts[54], parts[8]]);
+
+    let (eyes, bridge, nose, lower) = match (
+        region_stats(eyes, &pixel),
+        region_stats(bridge, &pixel),
+        region_stats(nose, &pixel),
+        region_stats(lower, &pixel),
+    ) {
+        (Some(eyes), Some(bridge), Some(nose), Some(lower)) => (eyes, bridge, nose, lower),
+        // part of the face is outside the frame
+        _ => return vec![],
+    };
+
+    let smoothness = 1.0 - lower.sharpness / eyes.sharpness.max(1.0);
+    let colour_distance = lower
+        .mean
+        .iter()
+        .zip(bridge.mean.iter())
+        .map(|(a, b)| (a - b).powi(2))
+        .sum::<f64>()
+        .sqrt();
+    let mask = ramp(smoothness, 0.6, 0.9).min(ramp(colour_distance, 20.0, 60.0));
+
+    let darkening = 1.0 - eyes.brightness / nose.brightness.max(1.0);
+    let sunglasses = ramp(darkening, 0.4, 0.7);
+
+    vec![
+        Estimate {
+            attribute: Attribute::Mask,
+            confidence: mask,
+        },
+        Estimate {
+            attribute: Attribute::Sunglasses,
+            confidence: sunglasses,
+        },
+    ]
+}
+
+#[cfg(test)]
+fn synthetic_face() -> (RgbImage, Vec<Point>) {
+    let mut parts = vec![Point::default(); 68];
+    // jaw, chin at the bottom
+    for (i, part) in parts[..17].iter_mut().enumerate() {
+        let angle = std::f64::consts::PI * i as f64 / 16.0;
+        *part = Point::new(
+            (100.0 - 70.0 * angle.cos()) as i64,
+            (100.0 + 90.0 * angle.sin()) as i64,
+        );
+    }
+    for i in 0..10 {
+        parts[17 + i] = Point::new(40 + 13 * i as i64, 60);
+    }
+    for i in 0..4 {
+        parts[27 + i] = Point::new(100, 75 + 13 * i as i64);
+    }
+    for i in 0..5 {
+        parts[31 + i] = Point::new(85 + 7 * i as i64, 125);
+    }
+    let eye = [(-15, 0), (-7, -5), (7, -5), (15, 0), (7, 5), (-7, 5)];
+    for (i, (dx, dy)) in eye.iter().enumerate() {
+        parts[36 + i] = Point::new(65 + dx, 82 + dy);
+        parts[42 + i] = Point::new(135 + dx, 82 + dy);
+    }
+    for i in 0..20 {
+        let angle = 2.0 * std::f64::consts::PI * i as f64 / 20.0;
+        parts[48 + i] = Point::new(
+            (100.0 - 30.0 * angle.cos()) as i64,
+            (155.0 + 10.0 * angle.sin()) as i64,
+        );
+    }
+
+    // skin with some texture, darker eyes and lips
+    let mut seed = 7u32;
+    let image = RgbImage::from_fn(200, 200, |x, y| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        let noise = ((seed >> 16) % 30) as f64 - 15.0;
+        let (x, y) = (x as f64, y as f64);
+        let in_eye =
+            ((x - 65.0).abs() < 15.0 || (x - 135.0).abs() < 15.0) && (y - 82.0).abs() < 5.0;
+        let in_lips = ((x - 100.0) / 30.0).powi(2) + ((y - 155.0) / 10.0).powi(2) < 1.0;
+        let base = if in_eye {
+            [70.0, 50.0, 40.0]
+        } else if in_lips {
+            [170.0, 90.0, 90.0]
+        } else {
+            [200.0, 160.0, 140.0]
+        };
+        Rgb([
+            (base[0] + noise) as u8,
+            (base[1] + noise) as u8,
+            (base[2] + noise) as u8,
+        ])
+    });
+
+    (image, parts)
+}
+
+#[cfg(test)]
+fn estimate(image: &RgbImage, parts: &[Point], attribute: Attribute) -> f64 {
+    let estimates = occlusion_estimates(parts, |x, y| {
+        if x < 0 || y < 0 || x >= image.width() as i64 || y >= image.height() as i64 {
+            return None;
+        }
+        Some(*image.get_pixel(x as u32, y as u32))
+    });
+
+    estimates
+        .iter()
+        .find(|estimate| estimate.attribute == attribute)
+        .map_or(0.0, |estimate| estimate.confidence)
+}
+
+#[test]
+fn test_occlusion_heuristic() {
+    let (face, parts) = synthetic_face();
+    assert_eq!(estimate(&face, &parts, Attribute::Mask), 0.0);
+    assert_eq!(estimate(&face, &parts, Attribute::Sunglasses), 0.0);
+
+    // smooth light blue cloth from the nose to the chin
+    let mut masked = face.clone();
+    for (_, y, pixel) in masked.enumerate_pixels_mut() {
+        if y >= 110 {
+            *pixel = Rgb([150, 190, 220]);
+        }
+    }
+    assert_eq!(estimate(&masked, &parts, Attribute::Mask), 1.0);
+    assert_eq!(estimate(&masked, &parts, Attribute::Sunglasses), 0.0);
+
+    let mut sunglasses = face.clone();
+    for (x, y, pixel) in sunglasses.enumerate_pixels_mut() {
+        if (70..95).contains(&y) && (45..155).contains(&x) {
+            *pixel = Rgb([15, 15, 20]);
+        }
+    }
+    assert_eq!(estimate(&sunglasses, &parts, Attribute::Sunglasses), 1.0);
+
+    // not enough landmarks, or a face partly outside the frame, can't be judged
+    assert_eq!(estimate(&face, &parts[..5], Attribute::Mask), 0.0);
+    let shifted: Vec<Point> = parts.iter().map(|p| *p + Point::new(0, 50)).collect();
+    assert_eq!(estimate(&face, &shifted, Attribute::Mask), 0.0);
+
+    let estimates = [
+        Estimate {
+            attribute: Attribute::Sunglasses,
+            confidence: 0.4,
+        },
+        Estimate {
+            attribute: Attribute::Mask,
+            confidence: 0.6,
+        },
+    ];
+    assert_eq!(occlusion(&estimates), Some(&estimates[1]));
+    assert_eq!(occlusion(&[]), None);
+    assert_eq!(
+        serde_json::to_string(&estimates[1]).unwrap(),
+        r#"{"attribute":"mask","confidence":0.6}"#
+    );
+}
@@ -1,4 +1,5 @@
 use std::ffi::*;
+use std::os::raw::c_char;
 use std::path::*;
 
 pub fn path_as_cstring(path: &Path) -> Result<CString, String> {
@@ -9,3 +10,20 @@ pub fn path_as_cstring(path: &Path) -> Result<CString, String> {
         Ok(CString::new(string).unwrap())
     }
 }
+
+cpp_class!(
+    /// A `std::string`, used to carry messages of C++ exceptions back to Rust.
+    pub(crate) unsafe struct CppString as "std::string"
+);
+
+impl CppString {
+    pub(crate) fn to_string_lossy(&self) -> String {
+        unsafe {
+            let pointer = cpp!([self as "const std::string*"] -> *const c_char as "const char*" {
+                return self->c_str();
+            });
+
+            CStr::from_ptr(pointer).to_string_lossy().into_owned()
+        }
+    }
+}
@@ -4,37 +4,722 @@
 extern crate clap;
 extern crate nokhwa;
 
-use clap::{App, Arg};
-use fofscreen::capture::utils::{capture_loop, display_frames};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use fofscreen::attributes::{Attribute, OcclusionHeuristic};
+use fofscreen::capture::overlay::{AnnotatedFrame, DisplayCommand};
+use fofscreen::capture::utils::{capture_loop, display_annotated};
+use fofscreen::drawing::FaceAnnotation;
+use fofscreen::enrollment::{reference_images, Enroller};
+use fofscreen::evaluation::LabelledEncodings;
+use fofscreen::events::{
+    format_rfc3339, Alert, Event, EventLog, EvidenceStore, Retention, Rotation, Sighting,
+    SnapshotFormat,
+};
 use fofscreen::face_detection::*;
 use fofscreen::face_encoding::*;
-use fofscreen::matrix::*;
+use fofscreen::gallery::{Gallery, Sample};
 use fofscreen::landmark_prediction::*;
+use fofscreen::liveness::SpoofDetector;
+use fofscreen::metrics::{Metrics, MetricsServer};
+use fofscreen::models::{Model, ModelInfo, ModelRegistry, ResolvedModel};
+use fofscreen::pipeline::{
+    nearest_match, DropPolicy, Pipeline, RecognisedFace, RecognisedFrame, RunningPipeline,
+};
+use fofscreen::quality::QualityScorer;
+use fofscreen::service::{RecognitionServer, RecognitionService};
+use fofscreen::{ImageMatrix, Rectangle};
+use flume::{Receiver, Sender};
+use image::Rgb;
 use nokhwa::{query_devices, CaptureAPIBackend, FrameFormat};
 
-use image::RgbImage;
-use std::collections::HashMap;
+use std::fs;
 use std::path::*;
 use std::process::exit;
-use std::time::SystemTime;
-use std::{env, fs};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
 
 // #[macro_use]
 // extern crate lazy_static;
 
-fn load_image(filename: &str, path: &str) -> RgbImage {
-    let filepath = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join(path)
-        .join(filename);
-    // dbg!("Loading file ", &filepath);
-    image::open(&filepath).unwrap().to_rgb()
+/// Faces at most this far from a reference are recognised as that reference.
+const MATCH_THRESHOLD: f64 = 0.6;
+
+/// Unknown faces more likely occluded than this aren't treated as intruders.
+const OCCLUSION_THRESHOLD: f64 = 0.5;
+
+/// What hides an unknown face, if it is likely enough to explain why the face didn't match.
+fn occlusion(face: &RecognisedFace) -> Option<Attribute> {
+    face.occlusion()
+        .filter(|estimate| estimate.confidence >= OCCLUSION_THRESHOLD)
+        .map(|estimate| estimate.attribute)
+}
+
+/// Encodings of the known faces, shared with the pipeline's matcher so faces enrolled from the window are
+/// recognised from the next frame on.
+type References = Arc<RwLock<Vec<(String, FaceEncoding)>>>;
+
+/// Record an event in the event log, if there is one.
+fn log_event(events: &mut Option<EventLog>, time: SystemTime, event: Event) {
+    if let Some(events) = events {
+        if let Err(why) = events.log_at(time, event) {
+            println!("{}", why);
+        }
+    }
+}
+
+/// Record a sighting of every face of a frame in the event log, if there is one.
+fn log_sightings(events: &mut Option<EventLog>, frame: &RecognisedFrame) {
+    for face in &frame.faces {
+        let sighting = Sighting::new(frame.seq, face);
+        log_event(events, frame.captured, Event::Sighting(sighting));
+    }
+}
+
+/// Save the frame and chips of the unknown faces of a frame as evidence, if there is an evidence store
+/// and it isn't cooling down, and raise an alert for every unknown face.
+///
+/// Faces left unmatched because of their quality, or likely occluded, aren't unknown, they may well be someone known.
+fn save_evidence(
+    events: &mut Option<EventLog>,
+    evidence: &mut Option<EvidenceStore>,
+    frame: &RecognisedFrame,
+) {
+    let store = match evidence {
+        Some(store) => store,
+        None => return,
+    };
+
+    let unknown = frame
+        .faces
+        .iter()
+        .filter(|face| {
+            face.identity.is_none() && !face.is_low_quality() && occlusion(face).is_none()
+        })
+        .collect::<Vec<_>>();
+    if unknown.is_empty() || !store.is_ready(frame.captured) {
+        return;
+    }
+
+    // faces whose landmarks can't be aligned have no chip to save
+    let matrix = ImageMatrix::from_image(&frame.image);
+    let (unknown, chips): (Vec<_>, Vec<_>) = unknown
+        .into_iter()
+        .filter_map(|face| {
+            let chip = matrix.face_chip(&face.landmarks, 150, 0.25)?;
+            Some((face, chip.to_rgb_image()))
+        })
+        .unzip();
+    if chips.is_empty() {
+        return;
+    }
+
+    match store.save(frame.captured, &frame.image, &chips) {
+        Ok(Some(saved)) => {
+            println!(
+                "Saved evidence of {} unknown faces to {}",
+                unknown.len(),
+                saved.dir.display()
+            );
+
+            for (face, chip) in unknown.iter().zip(saved.chips) {
+                let alert = Alert {
+                    sighting: Sighting::new(frame.seq, face),
+                    frame_image: saved.frame.clone(),
+                    chip_image: chip,
+                };
+                log_event(events, frame.captured, Event::Alert(alert));
+            }
+        }
+        Ok(None) => {}
+        Err(why) => println!("{}", why),
+    }
+}
+
+/// Add a face enrolled from the display window to the gallery at `path`, so it's still known after a restart.
+fn save_to_gallery(
+    path: &Path,
+    encoder: &ModelInfo,
+    name: &str,
+    encoding: FaceEncoding,
+) -> Result<(), String> {
+    let mut gallery = Gallery::open(path)?;
+    gallery.check_encoder(encoder)?;
+    gallery.add(name, Sample::new(encoding, None, SystemTime::now()));
+    gallery.save(path)
+}
+
+/// Annotate the pipeline results for the display window, and enroll faces when the window asks to.
+///
+/// Enrolled faces are recognised right away, and saved to the gallery at `gallery`.
+fn run_display_recognition(
+    pipeline: RunningPipeline,
+    annotated: Sender<AnnotatedFrame>,
+    commands: Receiver<DisplayCommand>,
+    references: References,
+    gallery: (PathBuf, ModelInfo),
+    mut events: Option<EventLog>,
+    mut evidence: Option<EvidenceStore>,
+) {
+    for frame in pipeline.iter() {
+        log_sightings(&mut events, &frame);
+        save_evidence(&mut events, &mut evidence, &frame);
+
+        let mut message = None;
+        for command in commands.try_iter() {
+            match command {
+                DisplayCommand::Enroll(name) => {
+                    let largest = frame.faces.iter().max_by_key(|face| face.rect.area());
+
+                    message = Some(match largest {
+                        Some(face) if face.is_spoof() => {
+                            format!("not enrolling {}, the face looks like a photo", name)
+                        }
+                        Some(face) if face.is_low_quality() => {
+                            format!("not enrolling {}, the face is of low quality", name)
+                        }
+                        Some(face) => {
+                            references
+                                .write()
+                                .unwrap()
+                                .push((name.clone(), face.encoding.clone()));
+                            let event = Event::Enrolled {
+                                identity: name.clone(),
+                            };
+                            log_event(&mut events, SystemTime::now(), event);
+
+                            let (path, encoder) = &gallery;
+                            match save_to_gallery(path, encoder, &name, face.encoding.clone()) {
+                                Ok(()) => format!("enrolled {}", name),
+                                Err(why) => format!("enrolled {} until exit: {}", name, why),
+                            }
+                        }
+                        None => "no face to enroll".to_string(),
+                    });
+                }
+            }
+        }
+
+        let annotations = frame
+            .faces
+            .iter()
+            .map(|face| {
+                let annotation = FaceAnnotation::new(face.rect).with_landmarks(&face.landmarks);
+
+                match &face.identity {
+                    Some(identity) => {
+                        annotation.with_identity(&identity.identity, identity.distance)
+                    }
+                    None if face.is_spoof() => annotation
+                        .with_label("spoof")
+                        .with_colour(Rgb([255, 0, 255])),
+                    None if occlusion(face).is_some() => annotation
+                        .with_label(format!("unknown ({})", occlusion(face).unwrap()))
+                        .with_colour(Rgb([255, 165, 0])),
+                    None if face.is_low_quality() => annotation
+                        .with_label("low quality")
+                        .with_colour(Rgb([128, 128, 128])),
+                    None => annotation
+                        .with_label("unknown")
+                        .with_colour(Rgb([255, 0, 0])),
+                }
+            })
+            .collect();
+
+        let frame = AnnotatedFrame {
+            frame: frame.image,
+            annotations,
+            message,
+        };
+
+        if annotated.send(frame).is_err() {
+            break;
+        }
+    }
+}
+
+/// Enroll the reference images in `reference`, printing the progress.
+///
+/// All photos in `reference/<person>/` are enrolled as that person.
+fn load_references(
+    reference: &str,
+    detector: &FaceDetector,
+    predictor: &LandmarkPredictor,
+    model: &FaceEncoderNetwork,
+) -> Vec<(String, FaceEncoding)> {
+    let reference_path = PathBuf::from(reference);
+
+    println!("Loading reference images from {}", reference_path.display());
+
+    let references = reference_images(&reference_path).unwrap_or_else(|why| {
+        println!("{}", why);
+        exit(1);
+    });
+    for (path, why) in &references.unreadable {
+        println!("Can't read {}: {}", path.display(), why);
+    }
+    for path in &references.ignored {
+        println!("Ignoring {}, it isn't an image", path.display());
+    }
+    println!(
+        "Found {} reference images of {} people",
+        references.images.len(),
+        references.num_identities()
+    );
+
+    let report = Enroller::new(detector, predictor, model).enroll(references.images, |progress| {
+        match progress.error {
+            Some(why) => println!(
+                "[{}/{}] Skipping {}: {}",
+                progress.done,
+                progress.total,
+                progress.path.display(),
+                why
+            ),
+            None => println!(
+                "[{}/{}] Loaded {}",
+                progress.done,
+                progress.total,
+                progress.path.display()
+            ),
+        }
+    });
+
+    let mut reference_encodings = vec![];
+    for enrolled in report.enrolled {
+        if !enrolled.quality.acceptable {
+            println!(
+                "Skipping {}, the face is of low quality: {}",
+                enrolled.path.display(),
+                enrolled.quality.reasons()
+            );
+            continue;
+        }
+
+        println!(
+            "Adding reference encoding of {} from {}",
+            enrolled.identity,
+            enrolled.path.display()
+        );
+        reference_encodings.push((enrolled.identity, enrolled.encoding));
+    }
+
+    reference_encodings
 }
 
-fn approx_equal(a: f64, b: f64, decimal_places: u8) -> bool {
-    let factor = 10.0f64.powi(decimal_places as i32);
-    let a = (a * factor).trunc();
-    let b = (b * factor).trunc();
-    a == b
+/// The known faces: the images in `reference` if given, otherwise the gallery if there is one.
+fn known_faces(
+    reference: Option<&str>,
+    gallery: &Path,
+    detector: &FaceDetector,
+    predictor: &LandmarkPredictor,
+    model: &FaceEncoderNetwork,
+) -> Option<Vec<(String, FaceEncoding)>> {
+    match reference {
+        Some(reference) => Some(load_references(reference, detector, predictor, model)),
+        None if gallery.exists() => {
+            println!("Loading references from gallery {}", gallery.display());
+            Some(open_gallery_for(gallery, model).references())
+        }
+        None => None,
+    }
+}
+
+fn open_gallery(path: &Path) -> Gallery {
+    Gallery::open(path).unwrap_or_else(|why| {
+        println!("{}", why);
+        exit(1);
+    })
+}
+
+/// Open a gallery to compare with or add encodings of `model`, which must be the encoder of its samples.
+fn open_gallery_for(path: &Path, model: &FaceEncoderNetwork) -> Gallery {
+    let mut gallery = open_gallery(path);
+    if let Err(why) = gallery.check_encoder(model.model_info()) {
+        println!("{}: {}", path.display(), why);
+        exit(1);
+    }
+    gallery
+}
+
+fn save_gallery(gallery: &Gallery, path: &Path) {
+    if let Err(why) = gallery.save(path) {
+        println!("{}", why);
+        exit(1);
+    }
+}
+
+fn model_registry(matches: &ArgMatches) -> ModelRegistry {
+    let mut registry = ModelRegistry::new();
+    if let Some(directory) = matches.value_of("model-dir") {
+        registry = registry.with_directory(directory);
+    }
+    if let Some(checksums) = matches.value_of("model-checksums") {
+        registry = registry
+            .with_checksums_file(checksums)
+            .unwrap_or_else(|why| {
+                println!("{}", why);
+                exit(1);
+            });
+    }
+    registry
+}
+
+fn resolve_model(registry: &ModelRegistry, model: Model) -> ResolvedModel {
+    registry.resolve(model).unwrap_or_else(|why| {
+        println!("{}", why);
+        exit(1);
+    })
+}
+
+/// The gallery subcommands that don't need the models.
+const GALLERY_COMMANDS: [&str; 5] = ["list", "remove", "rename", "export", "import"];
+
+fn run_gallery_command(command: &str, matches: &ArgMatches, path: &Path) {
+    let mut gallery = open_gallery(path);
+
+    match command {
+        "list" => {
+            for identity in gallery.identities() {
+                println!("{} ({} samples)", identity.name, identity.samples.len());
+
+                if matches.is_present("verbose") {
+                    for sample in &identity.samples {
+                        let source = sample.source.as_deref().unwrap_or("-");
+                        println!("    {} enrolled {}", source, sample.enrolled);
+                    }
+                }
+            }
+            println!(
+                "{} identities, {} samples in {}",
+                gallery.identities().len(),
+                gallery.num_samples(),
+                path.display()
+            );
+            if let Some(encoder) = gallery.encoder() {
+                println!("Encoded with {}", encoder);
+            }
+        }
+        "remove" => {
+            let name = matches.value_of("NAME").unwrap();
+            match gallery.remove(name) {
+                Ok(identity) => {
+                    save_gallery(&gallery, path);
+                    println!("Removed {} ({} samples)", name, identity.samples.len());
+                }
+                Err(why) => {
+                    println!("{}", why);
+                    exit(1);
+                }
+            }
+        }
+        "rename" => {
+            let from = matches.value_of("FROM").unwrap();
+            let to = matches.value_of("TO").unwrap();
+            if let Err(why) = gallery.rename(from, to) {
+                println!("{}", why);
+                exit(1);
+            }
+            save_gallery(&gallery, path);
+            println!("Renamed {} to {}", from, to);
+        }
+        "export" => {
+            let file = Path::new(matches.value_of("FILE").unwrap());
+            let exported = match matches.values_of("NAMES") {
+                Some(names) => gallery.subset(&names.collect::<Vec<_>>()),
+                None => Ok(gallery),
+            };
+            let exported = exported.unwrap_or_else(|why| {
+                println!("{}", why);
+                exit(1);
+            });
+
+            save_gallery(&exported, file);
+            println!(
+                "Exported {} identities, {} samples to {}",
+                exported.identities().len(),
+                exported.num_samples(),
+                file.display()
+            );
+        }
+        "import" => {
+            let file = Path::new(matches.value_of("FILE").unwrap());
+            let imported = Gallery::load(file).unwrap_or_else(|why| {
+                println!("{}", why);
+                exit(1);
+            });
+
+            let added = gallery.merge(imported).unwrap_or_else(|why| {
+                println!("Can't import {}: {}", file.display(), why);
+                exit(1);
+            });
+            save_gallery(&gallery, path);
+            println!("Imported {} new samples from {}", added, file.display());
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn run_enroll(
+    matches: &ArgMatches,
+    path: &Path,
+    detector: &FaceDetector,
+    predictor: &LandmarkPredictor,
+    model: &FaceEncoderNetwork,
+) {
+    let name = matches.value_of("NAME").unwrap();
+    let min_face_size = matches
+        .value_of("min-face-size")
+        .unwrap()
+        .trim()
+        .parse::<f64>()
+        .expect("Minimum face size must be a number of pixels!");
+    let min_quality = matches
+        .value_of("min-quality")
+        .unwrap()
+        .trim()
+        .parse::<f64>()
+        .expect("Minimum quality must be a score from 0 to 1!");
+    let num_jitters = matches
+        .value_of("jitters")
+        .unwrap()
+        .trim()
+        .parse::<u32>()
+        .expect("Jitters must be a u32!");
+
+    let images = matches
+        .values_of("IMAGES")
+        .unwrap()
+        .map(|image| (name.to_string(), PathBuf::from(image)))
+        .collect();
+
+    let mut gallery = open_gallery_for(path, model);
+    let scorer = QualityScorer::default()
+        .with_min_face_size(min_face_size)
+        .with_min_score(min_quality);
+    let report = Enroller::new(detector, predictor, model)
+        .with_jitters(num_jitters)
+        .with_quality_scorer(scorer)
+        .enroll(images, |_| {});
+
+    let diagnoses = gallery.enroll(name, report);
+    for (image, diagnosis) in &diagnoses {
+        println!("{}: {}", image.display(), diagnosis);
+    }
+
+    let enrolled = diagnoses.iter().filter(|(_, d)| d.is_enrolled()).count();
+    if enrolled == 0 {
+        println!("Nothing enrolled for {}", name);
+        exit(1);
+    }
+
+    save_gallery(&gallery, path);
+    println!(
+        "Enrolled {} of {} images as {}",
+        enrolled,
+        diagnoses.len(),
+        name
+    );
+}
+
+fn run_verify(
+    matches: &ArgMatches,
+    path: &Path,
+    detector: &FaceDetector,
+    predictor: &LandmarkPredictor,
+    model: &FaceEncoderNetwork,
+) {
+    let image_path = matches.value_of("IMAGE").unwrap();
+    let threshold = matches
+        .value_of("threshold")
+        .unwrap()
+        .trim()
+        .parse::<f64>()
+        .expect("Threshold must be a number!");
+
+    let gallery = open_gallery_for(path, model);
+    if gallery.is_empty() {
+        println!(
+            "Gallery {} is empty, enroll some faces first!",
+            path.display()
+        );
+        exit(1);
+    }
+
+    let image = match image::open(image_path) {
+        Ok(image) => image.to_rgb8(),
+        Err(why) => {
+            println!("Can't read {}: {}", image_path, why);
+            exit(1);
+        }
+    };
+    let matrix = ImageMatrix::from_image(&image);
+    let landmarks: Vec<_> = detector
+        .face_locations(&matrix)
+        .iter()
+        .map(|rect| predictor.face_landmarks(&matrix, rect))
+        .collect();
+    if landmarks.is_empty() {
+        println!("No face found in {}", image_path);
+        exit(1);
+    }
+
+    let encodings = model.get_face_encodings(&matrix, &landmarks, 0);
+    let mut verified = false;
+    for (landmarks, encoding) in landmarks.iter().zip(encodings.iter()) {
+        match gallery.identify(encoding, f64::INFINITY) {
+            Some(nearest) if nearest.distance <= threshold => {
+                verified = true;
+                println!(
+                    "{:?}: {} (distance {:.4})",
+                    landmarks.rect(),
+                    nearest.identity,
+                    nearest.distance
+                );
+            }
+            Some(nearest) => println!(
+                "{:?}: unknown (nearest {} at distance {:.4})",
+                landmarks.rect(),
+                nearest.identity,
+                nearest.distance
+            ),
+            None => println!("{:?}: unknown", landmarks.rect()),
+        }
+    }
+
+    if !verified {
+        exit(1);
+    }
+}
+
+fn run_serve(
+    matches: &ArgMatches,
+    gallery: &Path,
+    detector: &FaceDetector,
+    predictor: &LandmarkPredictor,
+    model: &FaceEncoderNetwork,
+) {
+    let references = known_faces(
+        matches.value_of("reference"),
+        gallery,
+        detector,
+        predictor,
+        model,
+    )
+    .unwrap_or_default();
+    let threshold = matches
+        .value_of("threshold")
+        .unwrap()
+        .trim()
+        .parse::<f64>()
+        .expect("Threshold must be a number!");
+    let num_jitters = matches
+        .value_of("jitters")
+        .unwrap()
+        .trim()
+        .parse::<u32>()
+        .expect("Jitters must be a u32!");
+
+    let service = RecognitionService::new(detector.clone(), predictor.clone(), model.clone())
+        .with_references(references)
+        .with_threshold(threshold)
+        .with_num_jitters(num_jitters);
+    let references = service.references().len();
+
+    let addr = matches.value_of("addr").unwrap();
+    let server = RecognitionServer::start(addr, service).unwrap_or_else(|why| {
+        println!("{}", why);
+        exit(1);
+    });
+    println!(
+        "Serving recognition with {} references on http://{}",
+        references,
+        server.addr()
+    );
+
+    server.join();
+}
+
+fn run_eval(
+    matches: &ArgMatches,
+    detector: &FaceDetector,
+    predictor: &LandmarkPredictor,
+    model: &FaceEncoderNetwork,
+) {
+    let dataset = Path::new(matches.value_of("DIR").unwrap());
+    let num_jitters = matches
+        .value_of("jitters")
+        .unwrap()
+        .trim()
+        .parse::<u32>()
+        .expect("Jitters must be a u32!");
+
+    println!("Encoding labelled faces from {}", dataset.display());
+    let encodings =
+        match LabelledEncodings::from_directory(dataset, detector, predictor, model, num_jitters) {
+            Ok(encodings) => encodings,
+            Err(why) => {
+                println!("Failed to load dataset: {}", why);
+                exit(1);
+            }
+        };
+
+    for (path, reason) in &encodings.skipped {
+        println!("Skipping {}: {}", path.display(), reason);
+    }
+    println!(
+        "Encoded {} images of {} identities ({} skipped)",
+        encodings.samples.len(),
+        encodings.num_identities(),
+        encodings.skipped.len()
+    );
+
+    let distances = encodings.pair_distances();
+    let evaluation = match distances.evaluate() {
+        Some(evaluation) => evaluation,
+        None => {
+            println!("Need at least two images of one identity and two identities to evaluate!");
+            exit(1);
+        }
+    };
+
+    println!(
+        "Genuine pairs: {}, impostor pairs: {}",
+        evaluation.genuine_pairs, evaluation.impostor_pairs
+    );
+    println!(
+        "EER: {:.2}% at threshold {:.4}",
+        evaluation.eer() * 100.0,
+        evaluation.eer_point.threshold
+    );
+    println!(
+        "Best threshold: {:.4} (TAR {:.2}%, FAR {:.2}%)",
+        evaluation.best.threshold,
+        evaluation.best.tar * 100.0,
+        evaluation.best.far * 100.0
+    );
+
+    for far in matches.values_of("far").unwrap() {
+        let far = far.trim().parse::<f64>().expect("FAR must be a f64!");
+
+        match evaluation.tar_at_far(far) {
+            Some(point) => println!(
+                "TAR@FAR={}: {:.2}% at threshold {:.4}",
+                far,
+                point.tar * 100.0,
+                point.threshold
+            ),
+            None => println!("TAR@FAR={}: not reachable", far),
+        }
+    }
+
+    if let Some(roc) = matches.value_of("roc") {
+        let file = fs::File::create(roc).expect("Failed to create ROC file");
+        evaluation
+            .write_csv(std::io::BufWriter::new(file))
+            .expect("Failed to write ROC file");
+        println!("ROC points written to {}", roc);
+    }
 }
 
 fn main() {
@@ -100,24 +785,326 @@ fn main() {
         .arg(Arg::with_name("reference")
             // .short("f")
             .long("reference")
-            .help("Pass a directory of reference face images")
+            .help("Pass a directory of reference face images. Defaults to the gallery if there is one, otherwise assets.")
             .takes_value(true))
         .arg(Arg::with_name("display")
             .short("d")
             .long("display")
             .help("Pass to open a window and display.")
-            .takes_value(false)).get_matches();
+            .takes_value(false))
+        .arg(Arg::with_name("snapshot-dir")
+            .long("snapshot-dir")
+            .value_name("DIR")
+            .help("Directory the display window saves snapshots to.")
+            .default_value(".")
+            .takes_value(true))
+        .arg(Arg::with_name("detect-scale")
+            .long("detect-scale")
+            .value_name("SCALE")
+            .help("Resize frames by SCALE before detecting faces, e.g. 0.5 on 1080p frames. Faces are still encoded at full resolution.")
+            .default_value("1.0")
+            .takes_value(true))
+        .arg(Arg::with_name("roi")
+            .long("roi")
+            .value_name("LEFT,TOP,RIGHT,BOTTOM")
+            .help("Only detect faces inside this region of the frame.")
+            .use_delimiter(true)
+            .number_of_values(4)
+            .takes_value(true))
+        .arg(Arg::with_name("detect-workers")
+            .long("detect-workers")
+            .value_name("N")
+            .help("Number of threads detecting faces.")
+            .default_value("1")
+            .takes_value(true))
+        .arg(Arg::with_name("landmark-workers")
+            .long("landmark-workers")
+            .value_name("N")
+            .help("Number of threads predicting landmarks.")
+            .default_value("1")
+            .takes_value(true))
+        .arg(Arg::with_name("encode-workers")
+            .long("encode-workers")
+            .value_name("N")
+            .help("Number of threads encoding faces. Each thread loads its own copy of the network.")
+            .default_value("1")
+            .takes_value(true))
+        .arg(Arg::with_name("queue-capacity")
+            .long("queue-capacity")
+            .value_name("FRAMES")
+            .help("Number of frames waiting in front of each stage of the recognition pipeline.")
+            .default_value("2")
+            .takes_value(true))
+        .arg(Arg::with_name("drop-policy")
+            .long("drop-policy")
+            .value_name("POLICY")
+            .help("What to do when a stage falls behind: latest drops the oldest frames, block recognises every frame and slows down the capture.")
+            .possible_values(&["latest", "block"])
+            .default_value("latest")
+            .takes_value(true))
+        .arg(Arg::with_name("metrics-addr")
+            .long("metrics-addr")
+            .value_name("ADDR")
+            .help("Serve metrics in the Prometheus text format at http://ADDR/metrics, e.g. 127.0.0.1:9898.")
+            .takes_value(true))
+        .arg(Arg::with_name("metrics-interval")
+            .long("metrics-interval")
+            .value_name("SECONDS")
+            .help("Log a summary of the metrics every SECONDS, 0 to disable.")
+            .default_value("10")
+            .takes_value(true))
+        .arg(Arg::with_name("event-log")
+            .long("event-log")
+            .value_name("FILE")
+            .help("Append every sighting to FILE as JSON Lines, e.g. to audit who sat at the computer.")
+            .takes_value(true))
+        .arg(Arg::with_name("event-log-max-mb")
+            .long("event-log-max-mb")
+            .value_name("MEGABYTES")
+            .help("Rotate the event log once it reaches this size.")
+            .default_value("10")
+            .takes_value(true))
+        .arg(Arg::with_name("event-log-keep")
+            .long("event-log-keep")
+            .value_name("FILES")
+            .help("Number of rotated event logs to keep.")
+            .default_value("5")
+            .takes_value(true))
+        .arg(Arg::with_name("evidence-dir")
+            .long("evidence-dir")
+            .value_name("DIR")
+            .help("Save frames with unknown faces, and the chips of those faces, to a directory per incident in DIR, and log an alert referencing them.")
+            .takes_value(true))
+        .arg(Arg::with_name("evidence-format")
+            .long("evidence-format")
+            .value_name("FORMAT")
+            .help("Image format of the evidence.")
+            .possible_values(&["jpeg", "png"])
+            .default_value("jpeg")
+            .takes_value(true))
+        .arg(Arg::with_name("evidence-interval")
+            .long("evidence-interval")
+            .value_name("SECONDS")
+            .help("Save at most one incident every SECONDS.")
+            .default_value("5")
+            .takes_value(true))
+        .arg(Arg::with_name("evidence-max-count")
+            .long("evidence-max-count")
+            .value_name("INCIDENTS")
+            .help("Number of incidents to keep, 0 for no limit.")
+            .default_value("500")
+            .takes_value(true))
+        .arg(Arg::with_name("evidence-max-days")
+            .long("evidence-max-days")
+            .value_name("DAYS")
+            .help("Remove incidents older than DAYS, 0 for no limit.")
+            .default_value("30")
+            .takes_value(true))
+        .arg(Arg::with_name("evidence-max-mb")
+            .long("evidence-max-mb")
+            .value_name("MEGABYTES")
+            .help("Total size of the kept incidents, 0 for no limit.")
+            .default_value("512")
+            .takes_value(true))
+        .arg(Arg::with_name("min-quality")
+            .long("min-quality")
+            .value_name("SCORE")
+            .help("Don't match faces with a quality score below SCORE, from 0 to 1, and don't treat them as unknown.")
+            .takes_value(true))
+        .arg(Arg::with_name("spoof-threshold")
+            .long("spoof-threshold")
+            .value_name("SCORE")
+            .help("Check faces for photos and screens held up to the camera, and don't match faces with a spoof score of SCORE or more, from 0 to 1.")
+            .takes_value(true))
+        .arg(Arg::with_name("detect-occlusion")
+            .long("detect-occlusion")
+            .help("Estimate whether faces wear a mask or sunglasses, and don't treat unknown faces that do as intruders."))
+        .arg(Arg::with_name("gallery")
+            .long("gallery")
+            .value_name("FILE")
+            .help("Gallery of enrolled faces. Used to recognise faces when --reference isn't given.")
+            .default_value("gallery.json")
+            .global(true)
+            .takes_value(true))
+        .arg(Arg::with_name("model-dir")
+            .long("model-dir")
+            .value_name("DIR")
+            .help("Directory with the dlib model files, searched before $FOFSCREEN_MODEL_DIR and ~/.local/share/fofscreen/models.")
+            .global(true)
+            .takes_value(true))
+        .arg(Arg::with_name("model-checksums")
+            .long("model-checksums")
+            .value_name("FILE")
+            .help("sha256sum output with the SHA-256 of the model files, replacing the published ones. Models that don't match aren't loaded.")
+            .global(true)
+            .takes_value(true))
+        .subcommand(SubCommand::with_name("enroll")
+            .about("Enroll photos of a person in the gallery")
+            .arg(Arg::with_name("NAME")
+                .required(true)
+                .index(1))
+            .arg(Arg::with_name("IMAGES")
+                .help("Photos with exactly one face")
+                .required(true)
+                .multiple(true)
+                .index(2))
+            .arg(Arg::with_name("min-face-size")
+                .long("min-face-size")
+                .value_name("PIXELS")
+                .help("Faces smaller than PIXELS lower the quality score, they give unreliable encodings.")
+                .default_value("80")
+                .takes_value(true))
+            .arg(Arg::with_name("min-quality")
+                .long("min-quality")
+                .value_name("SCORE")
+                .help("Refuse faces with a quality score below SCORE, from 0 to 1, e.g. blurry, dark or turned away faces.")
+                .default_value("0.8")
+                .takes_value(true))
+            .arg(Arg::with_name("jitters")
+                .long("jitters")
+                .value_name("NUM_JITTERS")
+                .help("Number of jitters used when encoding each face.")
+                .default_value("0")
+                .takes_value(true)))
+        .subcommand(SubCommand::with_name("list")
+            .about("List the identities in the gallery")
+            .arg(Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .help("Also list the samples of every identity.")))
+        .subcommand(SubCommand::with_name("remove")
+            .about("Remove an identity and its samples from the gallery")
+            .arg(Arg::with_name("NAME")
+                .required(true)
+                .index(1)))
+        .subcommand(SubCommand::with_name("rename")
+            .about("Rename an identity in the gallery")
+            .arg(Arg::with_name("FROM")
+                .required(true)
+                .index(1))
+            .arg(Arg::with_name("TO")
+                .required(true)
+                .index(2)))
+        .subcommand(SubCommand::with_name("export")
+            .about("Write the gallery, or some of its identities, to another file")
+            .arg(Arg::with_name("FILE")
+                .required(true)
+                .index(1))
+            .arg(Arg::with_name("NAMES")
+                .help("Identities to export, all by default")
+                .multiple(true)
+                .index(2)))
+        .subcommand(SubCommand::with_name("import")
+            .about("Add the samples of an exported gallery to the gallery")
+            .arg(Arg::with_name("FILE")
+                .required(true)
+                .index(1)))
+        .subcommand(SubCommand::with_name("verify")
+            .about("Identify the faces of an image against the gallery, failing if none is recognised")
+            .arg(Arg::with_name("IMAGE")
+                .required(true)
+                .index(1))
+            .arg(Arg::with_name("threshold")
+                .long("threshold")
+                .value_name("DISTANCE")
+                .help("Faces at most DISTANCE from a sample are identified as its identity.")
+                .default_value("0.6")
+                .takes_value(true)))
+        .subcommand(SubCommand::with_name("serve")
+            .about("Serve face detection, landmarks, encodings and identification as a local HTTP/JSON service")
+            .arg(Arg::with_name("addr")
+                .long("addr")
+                .value_name("ADDR")
+                .help("Address to listen on. Keep it on localhost, requests aren't authenticated.")
+                .default_value("127.0.0.1:9899")
+                .takes_value(true))
+            .arg(Arg::with_name("reference")
+                .long("reference")
+                .value_name("DIR")
+                .help("Directory of reference face images to identify faces against, instead of the gallery.")
+                .takes_value(true))
+            .arg(Arg::with_name("threshold")
+                .long("threshold")
+                .value_name("DISTANCE")
+                .help("Faces at most DISTANCE from a reference are identified as that reference.")
+                .default_value("0.6")
+                .takes_value(true))
+            .arg(Arg::with_name("jitters")
+                .long("jitters")
+                .value_name("NUM_JITTERS")
+                .help("Number of jitters used when encoding each face.")
+                .default_value("0")
+                .takes_value(true)))
+        .subcommand(SubCommand::with_name("eval")
+            .about("Evaluate recognition accuracy on a labelled directory with one folder of images per identity")
+            .arg(Arg::with_name("DIR")
+                .help("Labelled directory, e.g. DIR/alice/1.jpg, DIR/bob/1.jpg")
+                .required(true)
+                .index(1))
+            .arg(Arg::with_name("far")
+                .long("far")
+                .value_name("RATES")
+                .help("Comma separated false accept rates to report the true accept rate at.")
+                .default_value("0.001,0.01,0.1")
+                .use_delimiter(true)
+                .takes_value(true))
+            .arg(Arg::with_name("roc")
+                .long("roc")
+                .value_name("FILE")
+                .help("Write the ROC/DET points (threshold, far, tar, frr) to a CSV file.")
+                .takes_value(true))
+            .arg(Arg::with_name("jitters")
+                .long("jitters")
+                .value_name("NUM_JITTERS")
+                .help("Number of jitters used when encoding each face.")
+                .default_value("0")
+                .takes_value(true))).get_matches();
+
+    let gallery_path = PathBuf::from(matches.value_of("gallery").unwrap());
+
+    if let (command, Some(command_matches)) = matches.subcommand() {
+        if GALLERY_COMMANDS.contains(&command) {
+            run_gallery_command(command, command_matches, &gallery_path);
+            return;
+        }
+    }
 
     println!("Initializing recognition engine...");
     let detector: FaceDetector = FaceDetector::default();
-    let predictor: LandmarkPredictor = LandmarkPredictor::default();
-    let model: FaceEncoderNetwork = FaceEncoderNetwork::default();
+    let registry = model_registry(&matches);
+    let predictor = LandmarkPredictor::new(resolve_model(&registry, Model::LandmarkPredictor).path)
+        .unwrap_or_else(|why| {
+            println!("{}", why);
+            exit(1);
+        });
+    let encoder = resolve_model(&registry, Model::FaceEncoder);
+    let mut model = FaceEncoderNetwork::new(&encoder.path).unwrap_or_else(|why| {
+        println!("{}", why);
+        exit(1);
+    });
+    model.set_model_info(encoder.info);
     println!("done.");
 
-    let mut reference_matrix: Vec<ImageMatrix> = vec![];
-    let mut reference_encodings: Vec<FaceEncoding> = vec![];
+    if let Some(enroll_matches) = matches.subcommand_matches("enroll") {
+        run_enroll(enroll_matches, &gallery_path, &detector, &predictor, &model);
+        return;
+    }
+
+    if let Some(verify_matches) = matches.subcommand_matches("verify") {
+        run_verify(verify_matches, &gallery_path, &detector, &predictor, &model);
+        return;
+    }
+
+    if let Some(serve_matches) = matches.subcommand_matches("serve") {
+        run_serve(serve_matches, &gallery_path, &detector, &predictor, &model);
+        return;
+    }
+
+    if let Some(eval_matches) = matches.subcommand_matches("eval") {
+        run_eval(eval_matches, &detector, &predictor, &model);
+        return;
+    }
 
-    let mut frame_no = 0;
     let print_every = 10;
 
     // Query example
@@ -182,44 +1169,43 @@ fn main() {
             }
         };
 
-        let reference = matches.value_of("reference").unwrap_or("assets");
-        let reference_path = Path::new(reference);
-
-        println!(
-            "Loading reference images from {}",
-            &reference_path.to_str().unwrap()
-        );
-
-        let mut index = 0;
-        let mut reference_meta = HashMap::new();
-
-        for entry in fs::read_dir(reference_path).unwrap() {
-            let path = entry.unwrap().path();
-            let filename = path.file_name().clone();
-            // dbg!(&filename);
-
-            if let Some(imagename) = filename {
-                let reference_rgb_image: RgbImage = load_image(
-                    &imagename.to_str().unwrap(),
-                    reference_path.to_str().unwrap(),
-                );
-                let ref_image_matrix = ImageMatrix::from_image(&reference_rgb_image);
-                let ref_locations = detector.face_locations(&ref_image_matrix);
-                let ref_rect = ref_locations[0];
-                let ref_landmarks = predictor.face_landmarks(&ref_image_matrix, &ref_rect);
-                let ref_encoding =
-                    &model.get_face_encodings(&ref_image_matrix, &[ref_landmarks], 0)[0];
-
-                reference_matrix.push(ref_image_matrix);
-
-                println!("Adding reference encoding {}", index);
-                reference_meta.insert(index, imagename.to_owned());
-
-                reference_encodings.push(ref_encoding.clone());
-                index += 1;
-            }
+        let detect_scale = matches
+            .value_of("detect-scale")
+            .unwrap()
+            .trim()
+            .parse::<f64>()
+            .expect("Detection scale must be a number!");
+        let mut frame_detector = ScaledDetector::new(detector.clone(), detect_scale)
+            .unwrap_or_else(|why| {
+                println!("{}", why);
+                exit(1);
+            });
+        if let Some(roi) = matches.values_of("roi") {
+            let roi = roi
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse::<i64>()
+                        .expect("ROI must be 4 integers!")
+                })
+                .collect::<Vec<i64>>();
+            frame_detector = frame_detector.with_roi(Rectangle {
+                left: roi[0],
+                top: roi[1],
+                right: roi[2],
+                bottom: roi[3],
+            });
         }
 
+        let reference_encodings = known_faces(
+            matches.value_of("reference"),
+            &gallery_path,
+            &detector,
+            &predictor,
+            &model,
+        )
+        .unwrap_or_else(|| load_references("assets", &detector, &predictor, &model));
+
         if reference_encodings.len() == 0 {
             println!("No reference images found. Add some faces to recognize!");
             exit(1);
@@ -231,71 +1217,226 @@ fn main() {
         // TODO pass directory of reference faces and create a vector of encodings
         // TODO later check current frame encoding with vector of encodings and return the first found
 
-        // Start capturing frames
+        let workers = |name: &str| {
+            matches
+                .value_of(name)
+                .unwrap()
+                .trim()
+                .parse::<usize>()
+                .expect("Worker counts must be positive integers!")
+        };
+        let queue_capacity = workers("queue-capacity");
+        let drop_policy = match matches.value_of("drop-policy").unwrap() {
+            "block" => DropPolicy::Block,
+            _ => DropPolicy::LatestWins,
+        };
+
+        let mut events = matches.value_of("event-log").map(|path| {
+            let rotation = Rotation {
+                max_bytes: matches
+                    .value_of("event-log-max-mb")
+                    .unwrap()
+                    .trim()
+                    .parse::<u64>()
+                    .expect("Event log size must be a number of megabytes!")
+                    * 1024
+                    * 1024,
+                keep: matches
+                    .value_of("event-log-keep")
+                    .unwrap()
+                    .trim()
+                    .parse::<usize>()
+                    .expect("Number of event logs to keep must be a number!"),
+            };
+
+            EventLog::open(path)
+                .map(|log| log.with_rotation(rotation))
+                .unwrap_or_else(|why| {
+                    println!("{}", why);
+                    exit(1);
+                })
+        });
+        let event = Event::Started {
+            references: reference_encodings.len(),
+        };
+        log_event(&mut events, SystemTime::now(), event);
+
+        let mut evidence = matches.value_of("evidence-dir").map(|dir| {
+            let limit = |name: &str| {
+                let limit = matches
+                    .value_of(name)
+                    .unwrap()
+                    .trim()
+                    .parse::<u64>()
+                    .expect("Evidence limits must be positive integers!");
+                Some(limit).filter(|limit| *limit > 0)
+            };
+            let retention = Retention {
+                max_count: limit("evidence-max-count").map(|count| count as usize),
+                max_age: limit("evidence-max-days")
+                    .map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+                max_bytes: limit("evidence-max-mb").map(|mb| mb * 1024 * 1024),
+            };
+            let format = match matches.value_of("evidence-format").unwrap() {
+                "png" => SnapshotFormat::Png,
+                _ => SnapshotFormat::Jpeg,
+            };
+            let interval = matches
+                .value_of("evidence-interval")
+                .unwrap()
+                .trim()
+                .parse::<u64>()
+                .expect("Evidence interval must be a number of seconds!");
+
+            EvidenceStore::open(dir)
+                .map(|store| {
+                    store
+                        .with_format(format)
+                        .with_retention(retention)
+                        .with_cooldown(Duration::from_secs(interval))
+                })
+                .unwrap_or_else(|why| {
+                    println!("{}", why);
+                    exit(1);
+                })
+        });
+
+        let references: References = Arc::new(RwLock::new(reference_encodings));
+        let matcher = {
+            let references = references.clone();
+            move |encoding: &FaceEncoding| {
+                nearest_match(&references.read().unwrap(), encoding, MATCH_THRESHOLD)
+            }
+        };
+
+        let metrics = Arc::new(Metrics::new());
+        let _metrics_server = matches.value_of("metrics-addr").map(|addr| {
+            let server = MetricsServer::start(addr, metrics.clone()).unwrap_or_else(|why| {
+                println!("{}", why);
+                exit(1);
+            });
+            println!("Serving metrics on http://{}/metrics", server.addr());
+            server
+        });
+
+        let metrics_interval = matches
+            .value_of("metrics-interval")
+            .unwrap()
+            .trim()
+            .parse::<u64>()
+            .expect("Metrics interval must be a number of seconds!");
+        if metrics_interval > 0 {
+            let metrics = metrics.clone();
+            thread::spawn(move || loop {
+                thread::sleep(Duration::from_secs(metrics_interval));
+                println!("{}", metrics.summary());
+            });
+        }
+
+        // Start capturing frames, and recognise them in the pipeline's worker threads
+        let encoder_info = model.model_info().clone();
         let recv = capture_loop(4, width, height, fps, format, backend_value, true);
+        let mut pipeline = Pipeline::new(frame_detector, predictor, model, matcher)
+            .with_metrics(metrics)
+            .with_detect_workers(workers("detect-workers"))
+            .with_landmark_workers(workers("landmark-workers"))
+            .with_encode_workers(workers("encode-workers"))
+            .with_queue_capacity(queue_capacity)
+            .with_drop_policy(drop_policy);
+        if let Some(min_quality) = matches.value_of("min-quality") {
+            let min_quality = min_quality
+                .trim()
+                .parse::<f64>()
+                .expect("Minimum quality must be a score from 0 to 1!");
+            pipeline =
+                pipeline.with_quality_scorer(QualityScorer::default().with_min_score(min_quality));
+        }
+        if let Some(threshold) = matches.value_of("spoof-threshold") {
+            let threshold = threshold
+                .trim()
+                .parse::<f64>()
+                .expect("Spoof threshold must be a score from 0 to 1!");
+            pipeline = pipeline.with_spoof_detector(SpoofDetector::new().with_threshold(threshold));
+        }
+        if matches.is_present("detect-occlusion") {
+            pipeline = pipeline.with_attribute_classifier(OcclusionHeuristic);
+        }
+        let pipeline = pipeline.start(recv);
 
-        // run glium
+        // run glium, annotating the results on another thread
         if matches.is_present("display") {
-            let _ = display_frames(recv);
+            let (annotated_send, annotated_recv) = flume::bounded(1);
+            let (command_send, command_recv) = flume::unbounded();
+            let snapshot_dir = PathBuf::from(matches.value_of("snapshot-dir").unwrap());
+
+            thread::spawn(move || {
+                run_display_recognition(
+                    pipeline,
+                    annotated_send,
+                    command_recv,
+                    references,
+                    (gallery_path, encoder_info),
+                    events,
+                    evidence,
+                )
+            });
+
+            display_annotated(annotated_recv, command_send, snapshot_dir);
         }
         // dont
         else {
-            // dbg!(&reference_meta);
-
-            loop {
-                if let Ok(frame) = recv.recv() {
-                    if frame_no % print_every == 0 {
-                        println!(
-                            "Frame width {} height {} size {}",
-                            frame.width(),
-                            frame.height(),
-                            frame.len()
-                        );
-                    }
-                    frame_no += 1;
-
-                    let frame_matrix: ImageMatrix = ImageMatrix::from_image(&frame);
-                    let face_locations = detector.face_locations(&frame_matrix);
-
-                    if face_locations.len() > 0 {
-                        let now = SystemTime::now();
-                        println!(
-                            "{:?} Frame number {} uh oh found a face...",
-                            &now, &frame_no
-                        );
-                        let rect = face_locations[0];
-                        let frame_landmarks = predictor.face_landmarks(&frame_matrix, &rect);
-                        let a_encoding =
-                            &model.get_face_encodings(&frame_matrix, &[frame_landmarks], 0)[0];
-
-                        // Calculate distance of precomputed encodings of reference image
-                        println!("Calculating similarities with references...");
-                        let distances = reference_encodings
-                            .iter()
-                            .map(|re| {
-                                let distance = a_encoding.distance(re);
-                                distance
-                            })
-                            .collect::<Vec<f64>>();
-                        println!("Distances from reference images {:?}", &distances);
-
-                        // let mut min_value = distances.iter().fold(0.0f64, |min, &val| if val < min{ val } else{ min });
-                        let min_value: Option<&f64> = distances.iter().min_by(|a, b| a.total_cmp(b));
-                        let min_value = *min_value.unwrap();
-                        let index = distances.iter().position(|&r | approx_equal(r, min_value, 4) ).unwrap();
-                        let whoisthis = reference_meta.get(&index);
-
-                        println!("--------------------------------------------------------");
-                        println!("Hello {}", &whoisthis.unwrap().to_string_lossy());
-                        println!("I see you :P ");
-                        println!("--------------------------------------------------------");
+            for frame in pipeline.iter() {
+                log_sightings(&mut events, &frame);
+                save_evidence(&mut events, &mut evidence, &frame);
+
+                if frame.seq % print_every == 0 {
+                    println!(
+                        "Frame width {} height {} latency {:?}",
+                        frame.image.width(),
+                        frame.image.height(),
+                        frame.latency
+                    );
+                }
+
+                for face in &frame.faces {
+                    println!(
+                        "{} Frame number {} uh oh found a face...",
+                        format_rfc3339(frame.captured),
+                        &frame.seq
+                    );
 
+                    match &face.identity {
+                        Some(identity) => {
+                            println!("--------------------------------------------------------");
+                            println!(
+                                "Hello {} (distance {:.2})",
+                                identity.identity, identity.distance
+                            );
+                            println!("I see you :P ");
+                            println!("--------------------------------------------------------");
+                        }
+                        None if face.is_spoof() => {
+                            println!("Possible photo or screen at {:?}", face.rect)
+                        }
+                        None if occlusion(face).is_some() => println!(
+                            "Unknown face with {} at {:?}",
+                            occlusion(face).unwrap(),
+                            face.rect
+                        ),
+                        None => match &face.quality {
+                            Some(quality) if !quality.acceptable => println!(
+                                "Low quality face at {:?}: {}",
+                                face.rect,
+                                quality.reasons()
+                            ),
+                            _ => println!("Unknown face at {:?}", face.rect),
+                        },
                     }
-                } else {
-                    println!("Thread terminated, closing!");
-                    break;
                 }
             }
+
+            log_event(&mut events, SystemTime::now(), Event::Stopped);
+            println!("Thread terminated, closing!");
         }
     }
 
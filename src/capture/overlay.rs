@@ -0,0 +1,346 @@
+//! What the live display window draws over the video, and how keys change it.
+//!
+//! This is kept apart from the OpenGL code in [`super::utils`], which only uploads the rendered frames
+//! and translates window events into [`Key`]s and typed characters.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use image::{Rgb, RgbImage};
+
+use crate::drawing::{
+    draw_annotations, draw_text, fill_rectangle, DrawStyle, FaceAnnotation, GLYPH_HEIGHT,
+};
+use crate::geometry::Rectangle;
+
+/// How long messages stay on screen.
+const MESSAGE_DURATION: Duration = Duration::from_secs(3);
+
+const HELP: &str = "space pause  s snapshot  o overlays  e enroll  q quit";
+
+/// A captured frame with the faces recognised in it.
+#[derive(Clone, Debug)]
+pub struct AnnotatedFrame {
+    pub frame: RgbImage,
+    pub annotations: Vec<FaceAnnotation>,
+    /// A message from the recognition thread to show, such as the outcome of an enrollment.
+    pub message: Option<String>,
+}
+
+impl AnnotatedFrame {
+    /// A frame without annotations.
+    pub fn new(frame: RgbImage) -> Self {
+        Self {
+            frame,
+            annotations: Vec::new(),
+            message: None,
+        }
+    }
+}
+
+/// Requests from the display window to the recognition thread.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DisplayCommand {
+    /// Enroll the largest face of the latest frame under a name.
+    Enroll(String),
+}
+
+/// The keys the display window reacts to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    Space,
+    S,
+    O,
+    E,
+    Q,
+    Enter,
+    Escape,
+    Backspace,
+}
+
+/// What the window has to do after a key press.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OverlayAction {
+    Snapshot,
+    Enroll(String),
+    Quit,
+}
+
+/// The state of the display window.
+///
+/// - `space` pauses and resumes the video
+/// - `s` saves a snapshot of the window contents
+/// - `o` shows or hides the overlays
+/// - `e` asks for a name, typed and confirmed with `enter`, to enroll the visible face under
+/// - `q` or `escape` quits, or cancels typing a name
+pub struct OverlayState {
+    paused: bool,
+    show_overlays: bool,
+    name: Option<String>,
+    /// Set when `e` opens the name prompt, as the key also arrives as a typed character.
+    skip_char: bool,
+    message: Option<(String, Instant)>,
+    arrivals: VecDeque<Instant>,
+    style: DrawStyle,
+}
+
+impl Default for OverlayState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OverlayState {
+    pub fn new() -> Self {
+        Self {
+            paused: false,
+            show_overlays: true,
+            name: None,
+            skip_char: false,
+            message: None,
+            arrivals: VecDeque::new(),
+            style: DrawStyle::default(),
+        }
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn show_overlays(&self) -> bool {
+        self.show_overlays
+    }
+
+    /// The name being typed, if the window is asking for one.
+    pub fn typing(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Show a message for a few seconds.
+    pub fn show_message<S: Into<String>>(&mut self, message: S, now: Instant) {
+        self.message = Some((message.into(), now));
+    }
+
+    /// Record the arrival of a frame, to measure the frame rate.
+    pub fn frame_received(&mut self, frame: &AnnotatedFrame, now: Instant) {
+        self.arrivals.push_back(now);
+        while let Some(&first) = self.arrivals.front() {
+            if now.duration_since(first) > Duration::from_secs(1) {
+                self.arrivals.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        if let Some(message) = &frame.message {
+            self.show_message(message.clone(), now);
+        }
+    }
+
+    /// Frames received per second, over the last second.
+    pub fn fps(&self) -> f64 {
+        match (self.arrivals.front(), self.arrivals.back()) {
+            (Some(first), Some(last)) if self.arrivals.len() > 1 => {
+                let elapsed = last.duration_since(*first).as_secs_f64();
+                (self.arrivals.len() - 1) as f64 / elapsed.max(f64::EPSILON)
+            }
+            _ => 0.0,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: Key) -> Option<OverlayAction> {
+        if let Some(name) = &mut self.name {
+            match key {
+                Key::Enter => {
+                    let name = name.trim().to_string();
+                    self.name = None;
+
+                    if !name.is_empty() {
+                        return Some(OverlayAction::Enroll(name));
+                    }
+                }
+                Key::Escape => self.name = None,
+                Key::Backspace => {
+                    name.pop();
+                }
+                // letters arrive as typed characters
+                _ => {}
+            }
+
+            return None;
+        }
+
+        match key {
+            Key::Space => self.paused = !self.paused,
+            Key::O => self.show_overlays = !self.show_overlays,
+            Key::E => {
+                self.name = Some(String::new());
+                self.skip_char = true;
+            }
+            Key::S => return Some(OverlayAction::Snapshot),
+            Key::Q | Key::Escape => return Some(OverlayAction::Quit),
+            Key::Enter | Key::Backspace => {}
+        }
+
+        None
+    }
+
+    /// Handle a typed character, which only matters while typing a name.
+    pub fn handle_char(&mut self, c: char) {
+        if let Some(name) = &mut self.name {
+            if std::mem::take(&mut self.skip_char) && c.eq_ignore_ascii_case(&'e') {
+                return;
+            }
+
+            if !c.is_control() {
+                name.push(c);
+            }
+        }
+    }
+
+    /// Draw the frame with its overlays, the frame rate and any prompt or message.
+    pub fn render(&self, frame: &AnnotatedFrame, now: Instant) -> RgbImage {
+        let mut image = frame.frame.clone();
+        let scale = self.style.text_scale;
+        let line = banner_height(scale);
+
+        if self.show_overlays {
+            draw_annotations(&mut image, &frame.annotations, &self.style);
+        }
+
+        let mut status = format!("{:.1} FPS", self.fps());
+        if self.paused {
+            status.push_str("  PAUSED");
+        }
+        draw_banner(&mut image, &status, 0, scale);
+
+        let mut lines = Vec::new();
+        if let Some((message, shown)) = &self.message {
+            if now.duration_since(*shown) < MESSAGE_DURATION {
+                lines.push(message.clone());
+            }
+        }
+        match &self.name {
+            Some(name) => lines.push(format!("name: {}_", name)),
+            None if self.show_overlays => lines.push(HELP.to_string()),
+            None => {}
+        }
+
+        let bottom = image.height() as i64;
+        for (index, text) in lines.iter().rev().enumerate() {
+            draw_banner(&mut image, text, bottom - (index as i64 + 1) * line, scale);
+        }
+
+        image
+    }
+}
+
+fn banner_height(scale: u32) -> i64 {
+    ((GLYPH_HEIGHT + 2) * scale) as i64
+}
+
+/// Draw a line of white text on a black strip across the image, readable over any frame.
+fn draw_banner(image: &mut RgbImage, text: &str, top: i64, scale: u32) {
+    let strip = Rectangle::from_xywh(0, top, image.width() as i64, banner_height(scale));
+
+    fill_rectangle(image, &strip, Rgb([0, 0, 0]));
+    draw_text(
+        image,
+        text,
+        scale as i64,
+        top + scale as i64,
+        scale,
+        Rgb([255, 255, 255]),
+    );
+}
+
+/// Save a snapshot as a timestamped png in `dir`.
+pub fn save_snapshot(image: &RgbImage, dir: &Path) -> Result<PathBuf, String> {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_millis();
+    let path = dir.join(format!("fofscreen-{}.png", millis));
+
+    image.save(&path).map_err(|e| e.to_string())?;
+
+    Ok(path)
+}
+
+#[test]
+fn test_overlay_keys() {
+    let mut state = OverlayState::new();
+    assert!(!state.paused());
+    assert!(state.show_overlays());
+
+    assert_eq!(state.handle_key(Key::Space), None);
+    assert!(state.paused());
+    assert_eq!(state.handle_key(Key::O), None);
+    assert!(!state.show_overlays());
+    assert_eq!(state.handle_key(Key::S), Some(OverlayAction::Snapshot));
+
+    // typing a name swallows every other shortcut
+    assert_eq!(state.handle_key(Key::E), None);
+    state.handle_char('e');
+    assert_eq!(state.typing(), Some(""));
+    for c in "evx".chars() {
+        state.handle_char(c);
+    }
+    assert_eq!(state.handle_key(Key::Backspace), None);
+    state.handle_char('e');
+    assert_eq!(state.handle_key(Key::Q), None);
+    assert_eq!(state.handle_key(Key::Space), None);
+    assert!(state.paused());
+    assert_eq!(state.typing(), Some("eve"));
+    assert_eq!(
+        state.handle_key(Key::Enter),
+        Some(OverlayAction::Enroll("eve".into()))
+    );
+    assert_eq!(state.typing(), None);
+
+    // an empty name or escape cancels
+    state.handle_key(Key::E);
+    assert_eq!(state.handle_key(Key::Enter), None);
+    state.handle_key(Key::E);
+    state.handle_char('b');
+    assert_eq!(state.handle_key(Key::Escape), None);
+    assert_eq!(state.typing(), None);
+
+    assert_eq!(state.handle_key(Key::Escape), Some(OverlayAction::Quit));
+    assert_eq!(state.handle_key(Key::Q), Some(OverlayAction::Quit));
+}
+
+#[test]
+fn test_overlay_render() {
+    let start = Instant::now();
+    let mut state = OverlayState::new();
+    assert_eq!(state.fps(), 0.0);
+
+    let mut frame = AnnotatedFrame::new(RgbImage::from_pixel(200, 100, Rgb([0, 0, 255])));
+    frame
+        .annotations
+        .push(FaceAnnotation::new(Rectangle::from_xywh(50, 40, 40, 40)));
+
+    for i in 0..11 {
+        state.frame_received(&frame, start + Duration::from_millis(i * 100));
+    }
+    assert!((state.fps() - 10.0).abs() < 1e-6);
+
+    let rendered = state.render(&frame, start);
+    assert_eq!(rendered.dimensions(), frame.frame.dimensions());
+    assert_eq!(rendered.get_pixel(50, 60), &Rgb([0, 255, 0]));
+
+    state.handle_key(Key::O);
+    let rendered = state.render(&frame, start);
+    assert_eq!(rendered.get_pixel(50, 60), &Rgb([0, 0, 255]));
+
+    // messages sent with frames disappear after a while
+    frame.message = Some("enrolled alice".into());
+    state.frame_received(&frame, start);
+    assert!(state.message.is_some());
+    let later = start + MESSAGE_DURATION * 2;
+    let expired = state.render(&frame, later);
+    let shown = state.render(&frame, start);
+    assert_ne!(expired, shown);
+}
@@ -1,4 +1,4 @@
-use flume::Receiver;
+use flume::{Receiver, Sender};
 use glium::{
     implement_vertex,
     index::{self, PrimitiveType},
@@ -6,10 +6,20 @@ use glium::{
     texture::RawImage2d,
     uniform, Display, IndexBuffer, Surface, Texture2d, VertexBuffer,
 };
-use glutin::{event_loop::EventLoop, window::WindowBuilder, ContextBuilder};
+use glutin::{
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+    ContextBuilder,
+};
 use image::{ImageBuffer, Rgb};
 use nokhwa::{Camera, CaptureAPIBackend, FrameFormat};
-use std::time::Instant;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use super::overlay::{
+    save_snapshot, AnnotatedFrame, DisplayCommand, Key, OverlayAction, OverlayState,
+};
 
 #[derive(Copy, Clone)]
 pub struct Vertex {
@@ -108,8 +118,33 @@ pub fn capture_loop(
 /// Display frame to openGL window
 ///
 pub fn display_frames(recv: Receiver<ImageBuffer<Rgb<u8>, Vec<u8>>>) {
+    let (send, frames) = flume::bounded(1);
+
+    std::thread::spawn(move || {
+        for frame in recv.iter() {
+            if send.send(AnnotatedFrame::new(frame)).is_err() {
+                break;
+            }
+        }
+    });
+
+    // nothing handles enrollment requests
+    let (commands, _) = flume::unbounded();
+
+    display_annotated(frames, commands, PathBuf::from("."))
+}
+
+/// Display recognised frames to an openGL window, with the overlays and keyboard shortcuts of [`OverlayState`].
+///
+/// Snapshots are saved to `snapshot_dir`, and enrollment requests are sent to `commands`.
+/// The process exits when the window is closed or `q` is pressed.
+pub fn display_annotated(
+    recv: Receiver<AnnotatedFrame>,
+    commands: Sender<DisplayCommand>,
+    snapshot_dir: PathBuf,
+) {
     let gl_event_loop = EventLoop::new();
-    let window_builder = WindowBuilder::new();
+    let window_builder = WindowBuilder::new().with_title("fofscreen");
     let context_builder = ContextBuilder::new().with_vsync(true);
     let gl_display = Display::new(window_builder, context_builder, &gl_event_loop).unwrap();
 
@@ -172,53 +207,118 @@ pub fn display_frames(recv: Receiver<ImageBuffer<Rgb<u8>, Vec<u8>>>) {
     )
     .unwrap();
 
+    let mut state = OverlayState::new();
+    let mut current: Option<AnnotatedFrame> = None;
+
     // run the event loop
     gl_event_loop.run(move |event, _window, ctrl| {
-        let before_capture = Instant::now();
-        let frame = recv.recv().unwrap();
-        let after_capture = Instant::now();
-
-        let width = &frame.width();
-        let height = &frame.height();
-
-        let raw_data = RawImage2d::from_raw_rgb(frame.into_raw(), (*width, *height));
-        let gl_texture = Texture2d::new(&gl_display, raw_data).unwrap();
-
-        let uniforms = uniform! {
-            matrix: [
-                [1.0, 0.0, 0.0, 0.0],
-                [0.0, 1.0, 0.0, 0.0],
-                [0.0, 0.0, 1.0, 0.0],
-                [0.0, 0.0, 0.0, 1.0f32]
-            ],
-            tex: &gl_texture
-        };
+        let now = Instant::now();
+        // poll for frames without spinning
+        *ctrl = ControlFlow::WaitUntil(now + Duration::from_millis(5));
 
-        let mut target = gl_display.draw();
-        target.clear_color(0.0, 0.0, 0.0, 0.0);
-        target
-            .draw(
-                &vert_buffer,
-                &idx_buf,
-                &program,
-                &uniforms,
-                &Default::default(),
-            )
-            .unwrap();
-        target.finish().unwrap();
-
-        match event {
-            glutin::event::Event::WindowEvent { event, .. } => match event {
-                glutin::event::WindowEvent::CloseRequested => {
-                    *ctrl = glutin::event_loop::ControlFlow::Exit;
+        let action = match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => Some(OverlayAction::Quit),
+                WindowEvent::ReceivedCharacter(c) => {
+                    state.handle_char(c);
+                    None
                 }
-                _ => {}
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(code),
+                            ..
+                        },
+                    ..
+                } => key_from_code(code).and_then(|key| state.handle_key(key)),
+                _ => None,
             },
-            _ => {}
+            Event::MainEventsCleared => {
+                if !state.paused() {
+                    // skip to the latest frame if the display fell behind
+                    match recv.drain().last() {
+                        Some(frame) => {
+                            state.frame_received(&frame, now);
+                            current = Some(frame);
+                        }
+                        None if recv.is_disconnected() => {
+                            println!("Recognition thread terminated, closing!");
+                            *ctrl = ControlFlow::Exit;
+                            return;
+                        }
+                        None => {}
+                    }
+                }
+
+                if let Some(frame) = &current {
+                    let rendered = state.render(frame, now);
+                    let dimensions = rendered.dimensions();
+
+                    // images are stored top row first, textures bottom row first
+                    let data = rendered.into_raw();
+                    let raw_data = RawImage2d::from_raw_rgb_reversed(&data, dimensions);
+                    let gl_texture = Texture2d::new(&gl_display, raw_data).unwrap();
+
+                    let uniforms = uniform! {
+                        matrix: [
+                            [1.0, 0.0, 0.0, 0.0],
+                            [0.0, 1.0, 0.0, 0.0],
+                            [0.0, 0.0, 1.0, 0.0],
+                            [0.0, 0.0, 0.0, 1.0f32]
+                        ],
+                        tex: &gl_texture
+                    };
+
+                    let mut target = gl_display.draw();
+                    target.clear_color(0.0, 0.0, 0.0, 0.0);
+                    target
+                        .draw(
+                            &vert_buffer,
+                            &idx_buf,
+                            &program,
+                            &uniforms,
+                            &Default::default(),
+                        )
+                        .unwrap();
+                    target.finish().unwrap();
+                }
+
+                None
+            }
+            _ => None,
+        };
+
+        match action {
+            Some(OverlayAction::Quit) => *ctrl = ControlFlow::Exit,
+            Some(OverlayAction::Snapshot) => {
+                if let Some(frame) = &current {
+                    match save_snapshot(&state.render(frame, now), &snapshot_dir) {
+                        Ok(path) => state.show_message(format!("saved {}", path.display()), now),
+                        Err(why) => state.show_message(format!("snapshot failed: {}", why), now),
+                    }
+                }
+            }
+            Some(OverlayAction::Enroll(name)) => {
+                if commands.send(DisplayCommand::Enroll(name)).is_err() {
+                    state.show_message("enrollment is not available", now);
+                }
+            }
+            None => {}
         }
-        println!(
-            "Took {}ms to capture",
-            after_capture.duration_since(before_capture).as_millis()
-        )
     })
 }
+
+fn key_from_code(code: VirtualKeyCode) -> Option<Key> {
+    match code {
+        VirtualKeyCode::Space => Some(Key::Space),
+        VirtualKeyCode::S => Some(Key::S),
+        VirtualKeyCode::O => Some(Key::O),
+        VirtualKeyCode::E => Some(Key::E),
+        VirtualKeyCode::Q => Some(Key::Q),
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => Some(Key::Enter),
+        VirtualKeyCode::Escape => Some(Key::Escape),
+        VirtualKeyCode::Back => Some(Key::Backspace),
+        _ => None,
+    }
+}
@@ -0,0 +1,260 @@
+use std::ops::Range;
+
+use image::Rgb;
+
+use super::base::Canvas;
+use super::font::{draw_text, text_size};
+use super::shapes::{draw_dot, draw_polyline, draw_rectangle, fill_rectangle};
+use crate::geometry::{Point, Rectangle};
+use crate::quality::luma;
+
+/// A group of landmarks outlining one part of the face.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LandmarkRegion {
+    pub name: &'static str,
+    /// Indices of the region's landmarks.
+    pub parts: Range<usize>,
+    /// Whether the last landmark connects back to the first.
+    pub closed: bool,
+}
+
+const fn region(name: &'static str, parts: Range<usize>, closed: bool) -> LandmarkRegion {
+    LandmarkRegion {
+        name,
+        parts,
+        closed,
+    }
+}
+
+/// The regions of dlib's 68 point landmark model, as used by `shape_predictor_68_face_landmarks.dat`.
+pub const LANDMARK_REGIONS_68: [LandmarkRegion; 9] = [
+    region("jaw", 0..17, false),
+    region("right eyebrow", 17..22, false),
+    region("left eyebrow", 22..27, false),
+    region("nose bridge", 27..31, false),
+    region("lower nose", 31..36, false),
+    region("right eye", 36..42, true),
+    region("left eye", 42..48, true),
+    region("outer lips", 48..60, true),
+    region("inner lips", 60..68, true),
+];
+
+/// The regions of a set of landmarks, which are only known for the 68 point model.
+pub fn landmark_regions(num_parts: usize) -> &'static [LandmarkRegion] {
+    if num_parts == 68 {
+        &LANDMARK_REGIONS_68
+    } else {
+        &[]
+    }
+}
+
+/// A distinct, saturated colour for a track ID, so that each tracked face keeps its colour across frames.
+pub fn track_colour(id: u64) -> Rgb<u8> {
+    // consecutive IDs are spread around the colour wheel by the golden angle
+    let hue = (id as f64 * 0.618_033_988_749_895).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+
+    // keep a little light in every channel so dark frames still show the annotation
+    let channel = |value: f64| (48.0 + value * 207.0).round() as u8;
+
+    Rgb([channel(r), channel(g), channel(b)])
+}
+
+/// Black or white, whichever reads better on `background`.
+fn text_colour(background: Rgb<u8>) -> Rgb<u8> {
+    if luma(&background) > 140.0 {
+        Rgb([0, 0, 0])
+    } else {
+        Rgb([255, 255, 255])
+    }
+}
+
+/// Draw `text` on a filled box in `colour`, just above `rect`, or inside its top edge if there is no room above.
+pub fn draw_label<C: Canvas + ?Sized>(
+    canvas: &mut C,
+    rect: &Rectangle,
+    text: &str,
+    scale: u32,
+    colour: Rgb<u8>,
+) {
+    let scale = scale.max(1);
+    let padding = scale as i64;
+    let (width, height) = text_size(text, scale);
+    let (width, height) = (width as i64 + 2 * padding, height as i64 + 2 * padding);
+
+    let top = if rect.top >= height {
+        rect.top - height
+    } else {
+        rect.top
+    };
+    let background = Rectangle::from_xywh(rect.left, top, width, height);
+
+    fill_rectangle(canvas, &background, colour);
+    draw_text(
+        canvas,
+        text,
+        rect.left + padding,
+        top + padding,
+        scale,
+        text_colour(colour),
+    );
+}
+
+/// How annotations are drawn.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DrawStyle {
+    /// Width of the face rectangle outline.
+    pub thickness: u32,
+    /// Radius of the landmark dots, 0 for single pixels.
+    pub dot_radius: u32,
+    /// Whether to connect the landmarks of each face region.
+    pub contours: bool,
+    /// Size of the label font pixels.
+    pub text_scale: u32,
+}
+
+impl Default for DrawStyle {
+    fn default() -> Self {
+        Self {
+            thickness: 2,
+            dot_radius: 1,
+            contours: true,
+            text_scale: 2,
+        }
+    }
+}
+
+/// Everything drawn for a single face.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FaceAnnotation {
+    pub rect: Rectangle,
+    pub landmarks: Vec<Point>,
+    pub label: Option<String>,
+    pub colour: Rgb<u8>,
+}
+
+impl FaceAnnotation {
+    /// Annotate a face rectangle, in green.
+    pub fn new(rect: Rectangle) -> Self {
+        Self {
+            rect,
+            landmarks: Vec::new(),
+            label: None,
+            colour: Rgb([0, 255, 0]),
+        }
+    }
+
+    pub fn with_landmarks(mut self, landmarks: &[Point]) -> Self {
+        self.landmarks = landmarks.to_vec();
+        self
+    }
+
+    pub fn with_label<S: Into<String>>(mut self, label: S) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Label the face with the identity it was recognised as, and the distance to that identity.
+    pub fn with_identity(self, identity: &str, distance: f64) -> Self {
+        self.with_label(format!("{} {:.2}", identity, distance))
+    }
+
+    pub fn with_colour(mut self, colour: Rgb<u8>) -> Self {
+        self.colour = colour;
+        self
+    }
+
+    /// Colour the face by its track ID, see [`track_colour`].
+    pub fn with_track(self, id: u64) -> Self {
+        self.with_colour(track_colour(id))
+    }
+
+    pub fn draw<C: Canvas + ?Sized>(&self, canvas: &mut C, style: &DrawStyle) {
+        draw_rectangle(canvas, &self.rect, style.thickness, self.colour);
+
+        if style.contours {
+            for region in landmark_regions(self.landmarks.len()) {
+                draw_polyline(
+                    canvas,
+                    &self.landmarks[region.parts.clone()],
+                    region.closed,
+                    1,
+                    self.colour,
+                );
+            }
+        }
+
+        for point in &self.landmarks {
+            draw_dot(canvas, *point, style.dot_radius, self.colour);
+        }
+
+        if let Some(label) = &self.label {
+            draw_label(canvas, &self.rect, label, style.text_scale, self.colour);
+        }
+    }
+}
+
+/// Draw several annotations with the same style.
+pub fn draw_annotations<C: Canvas + ?Sized>(
+    canvas: &mut C,
+    annotations: &[FaceAnnotation],
+    style: &DrawStyle,
+) {
+    for annotation in annotations {
+        annotation.draw(canvas, style);
+    }
+}
+
+#[test]
+fn test_face_annotation() {
+    use image::RgbImage;
+
+    let black = Rgb([0, 0, 0]);
+    let mut image = RgbImage::new(64, 48);
+
+    // a face in the top left corner, with 68 landmarks spilling out of the image
+    let landmarks: Vec<Point> = (0..68).map(|i| Point::new(i - 10, i / 2)).collect();
+    let annotation = FaceAnnotation::new(Rectangle::from_xywh(-4, 2, 40, 40))
+        .with_landmarks(&landmarks)
+        .with_identity("obama", 0.4321)
+        .with_track(3);
+
+    assert_eq!(annotation.label.as_deref(), Some("obama 0.43"));
+    assert_eq!(annotation.colour, track_colour(3));
+
+    let annotations = vec![annotation];
+    draw_annotations(&mut image, &annotations, &DrawStyle::default());
+    let annotation = &annotations[0];
+
+    // no room above the face, so the label is drawn inside it
+    assert_eq!(image.get_pixel(0, 2), &annotation.colour);
+    assert_ne!(image.get_pixel(63, 47), &annotation.colour);
+    assert!(image
+        .pixels()
+        .any(|pixel| pixel == &text_colour(annotation.colour)));
+    assert!(image.pixels().any(|pixel| pixel == &black));
+}
+
+#[test]
+fn test_track_colours() {
+    let colours: Vec<Rgb<u8>> = (0..8).map(track_colour).collect();
+
+    for (i, a) in colours.iter().enumerate() {
+        for b in &colours[i + 1..] {
+            assert_ne!(a, b);
+        }
+    }
+
+    assert_eq!(track_colour(5), track_colour(5));
+    assert_eq!(landmark_regions(68).len(), 9);
+    assert!(landmark_regions(5).is_empty());
+}
@@ -0,0 +1,48 @@
+use std::ops::{Deref, DerefMut};
+
+use image::{ImageBuffer, Rgb};
+
+use crate::matrix::ImageMatrix;
+
+/// An RGB image that annotations can be drawn on.
+///
+/// Implemented for [`image::RgbImage`], which is also the type of the live capture frames, and for [`ImageMatrix`].
+pub trait Canvas {
+    /// The width and height in pixels.
+    fn dimensions(&self) -> (u32, u32);
+
+    /// Set a pixel that is known to be inside the image.
+    fn set_pixel(&mut self, x: u32, y: u32, colour: Rgb<u8>);
+
+    /// Set a pixel, doing nothing if it is outside of the image.
+    fn put_pixel_checked(&mut self, x: i64, y: i64, colour: Rgb<u8>) {
+        let (width, height) = self.dimensions();
+
+        if x >= 0 && y >= 0 && x < width as i64 && y < height as i64 {
+            self.set_pixel(x as u32, y as u32, colour);
+        }
+    }
+}
+
+impl<C> Canvas for ImageBuffer<Rgb<u8>, C>
+where
+    C: Deref<Target = [u8]> + DerefMut,
+{
+    fn dimensions(&self) -> (u32, u32) {
+        ImageBuffer::dimensions(self)
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, colour: Rgb<u8>) {
+        self.put_pixel(x, y, colour);
+    }
+}
+
+impl Canvas for ImageMatrix {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width() as u32, self.height() as u32)
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, colour: Rgb<u8>) {
+        self[(x as usize, y as usize)] = colour;
+    }
+}
@@ -0,0 +1,16 @@
+//! Drawing face annotations on images, for debugging and live display.
+//!
+//! Everything is clipped to the image, so faces at the edges of a frame can be annotated without bounds checks.
+
+mod annotation;
+mod base;
+mod font;
+mod shapes;
+
+pub use self::annotation::{
+    draw_annotations, draw_label, landmark_regions, track_colour, DrawStyle, FaceAnnotation,
+    LandmarkRegion, LANDMARK_REGIONS_68,
+};
+pub use self::base::Canvas;
+pub use self::font::{draw_text, text_size, GLYPH_HEIGHT, GLYPH_WIDTH};
+pub use self::shapes::{draw_dot, draw_line, draw_polyline, draw_rectangle, fill_rectangle};
@@ -0,0 +1,215 @@
+use image::Rgb;
+
+use super::base::Canvas;
+use crate::geometry::{Point, Rectangle};
+
+/// Fill the pixels inside `rect`, including its right column and bottom row, clipped to the canvas.
+pub fn fill_rectangle<C: Canvas + ?Sized>(canvas: &mut C, rect: &Rectangle, colour: Rgb<u8>) {
+    let (width, height) = canvas.dimensions();
+    let rect = rect.clamp_to_image(width as usize, height as usize);
+
+    for y in rect.top..=rect.bottom {
+        for x in rect.left..=rect.right {
+            canvas.set_pixel(x as u32, y as u32, colour);
+        }
+    }
+}
+
+/// Draw the outline of `rect`, `thickness` pixels wide towards the inside of the rectangle.
+pub fn draw_rectangle<C: Canvas + ?Sized>(
+    canvas: &mut C,
+    rect: &Rectangle,
+    thickness: u32,
+    colour: Rgb<u8>,
+) {
+    let t = (thickness.max(1) as i64).min(rect.width().min(rect.height()) / 2 + 1);
+
+    let edges = [
+        Rectangle {
+            bottom: rect.top + t - 1,
+            ..*rect
+        },
+        Rectangle {
+            top: rect.bottom - t + 1,
+            ..*rect
+        },
+        Rectangle {
+            right: rect.left + t - 1,
+            ..*rect
+        },
+        Rectangle {
+            left: rect.right - t + 1,
+            ..*rect
+        },
+    ];
+
+    for edge in edges.iter() {
+        fill_rectangle(canvas, edge, colour);
+    }
+}
+
+/// Draw a line between two points, `thickness` pixels wide.
+pub fn draw_line<C: Canvas + ?Sized>(
+    canvas: &mut C,
+    from: Point,
+    to: Point,
+    thickness: u32,
+    colour: Rgb<u8>,
+) {
+    let (width, height) = canvas.dimensions();
+    let margin = thickness as i64;
+
+    // don't walk lines that can't touch the canvas
+    let extent = Rectangle {
+        left: from.x().min(to.x()) - margin,
+        top: from.y().min(to.y()) - margin,
+        right: from.x().max(to.x()) + margin,
+        bottom: from.y().max(to.y()) + margin,
+    };
+    if extent
+        .intersect(&Rectangle::from_xywh(0, 0, width as i64, height as i64))
+        .is_empty()
+    {
+        return;
+    }
+
+    let offset = (thickness.max(1) as i64 - 1) / 2;
+    let brush = thickness.max(1) as i64;
+
+    // Bresenham's line algorithm
+    let (mut x, mut y) = (from.x(), from.y());
+    let dx = (to.x() - x).abs();
+    let dy = -(to.y() - y).abs();
+    let step_x = if x < to.x() { 1 } else { -1 };
+    let step_y = if y < to.y() { 1 } else { -1 };
+    let mut error = dx + dy;
+
+    loop {
+        for by in 0..brush {
+            for bx in 0..brush {
+                canvas.put_pixel_checked(x - offset + bx, y - offset + by, colour);
+            }
+        }
+
+        if x == to.x() && y == to.y() {
+            break;
+        }
+
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+/// Draw a filled circle.
+pub fn draw_dot<C: Canvas + ?Sized>(canvas: &mut C, center: Point, radius: u32, colour: Rgb<u8>) {
+    let radius = radius as i64;
+
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            if dx * dx + dy * dy <= radius * radius {
+                canvas.put_pixel_checked(center.x() + dx, center.y() + dy, colour);
+            }
+        }
+    }
+}
+
+/// Connect consecutive points with lines, and the last point to the first if `closed`.
+pub fn draw_polyline<C: Canvas + ?Sized>(
+    canvas: &mut C,
+    points: &[Point],
+    closed: bool,
+    thickness: u32,
+    colour: Rgb<u8>,
+) {
+    for pair in points.windows(2) {
+        draw_line(canvas, pair[0], pair[1], thickness, colour);
+    }
+
+    if closed && points.len() > 2 {
+        draw_line(
+            canvas,
+            points[points.len() - 1],
+            points[0],
+            thickness,
+            colour,
+        );
+    }
+}
+
+#[test]
+fn test_shapes_are_clipped() {
+    use image::RgbImage;
+
+    let red = Rgb([255, 0, 0]);
+    let black = Rgb([0, 0, 0]);
+    let mut image = RgbImage::new(20, 10);
+
+    // none of these panic, even though they reach past the edges
+    draw_rectangle(&mut image, &Rectangle::from_xywh(-5, -5, 100, 100), 3, red);
+    draw_line(&mut image, Point::new(-50, 5), Point::new(50, 5), 1, red);
+    draw_line(
+        &mut image,
+        Point::new(-500, -500),
+        Point::new(-400, -400),
+        5,
+        red,
+    );
+    draw_dot(&mut image, Point::new(19, 9), 4, red);
+    draw_polyline(
+        &mut image,
+        &[Point::new(0, 0), Point::new(30, 30)],
+        true,
+        2,
+        red,
+    );
+    fill_rectangle(&mut image, &Rectangle::from_xywh(15, 8, 10, 10), red);
+
+    // the visible part of the rectangle is the inner edge of the outline
+    let mut image = RgbImage::new(20, 10);
+    draw_rectangle(&mut image, &Rectangle::from_xywh(-1, -1, 100, 100), 3, red);
+    assert_eq!(image.get_pixel(5, 0), &red);
+    assert_eq!(image.get_pixel(5, 1), &red);
+    assert_eq!(image.get_pixel(5, 2), &black);
+    assert_eq!(image.get_pixel(19, 9), &black);
+
+    let mut image = RgbImage::new(20, 10);
+    draw_rectangle(&mut image, &Rectangle::from_xywh(2, 2, 10, 6), 1, red);
+    assert_eq!(image.get_pixel(2, 2), &red);
+    assert_eq!(image.get_pixel(11, 7), &red);
+    assert_eq!(image.get_pixel(12, 8), &black);
+    assert_eq!(image.get_pixel(5, 5), &black);
+
+    // dlib rectangles include their right column and bottom row
+    let mut image = RgbImage::new(20, 10);
+    let rect = Rectangle {
+        left: 2,
+        top: 2,
+        right: 12,
+        bottom: 8,
+    };
+    draw_rectangle(&mut image, &rect, 1, red);
+    assert_eq!(image.get_pixel(12, 5), &red);
+    assert_eq!(image.get_pixel(5, 8), &red);
+    assert_eq!(image.get_pixel(12, 8), &red);
+    assert_eq!(image.get_pixel(13, 5), &black);
+    assert_eq!(image.get_pixel(5, 9), &black);
+    assert_eq!(image.get_pixel(11, 5), &black);
+
+    let mut image = RgbImage::new(20, 10);
+    fill_rectangle(&mut image, &rect, red);
+    assert_eq!(image.get_pixel(12, 8), &red);
+    assert_eq!(image.get_pixel(13, 8), &black);
+    assert_eq!(image.get_pixel(12, 9), &black);
+
+    let mut image = RgbImage::new(20, 10);
+    draw_line(&mut image, Point::new(0, 0), Point::new(9, 9), 1, red);
+    assert!((0..10).all(|i| image.get_pixel(i, i) == &red));
+    assert_eq!(image.get_pixel(1, 0), &black);
+}
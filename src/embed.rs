@@ -2,8 +2,11 @@
 
 use std::path::PathBuf;
 
-pub fn path_for_file(filename: &str) -> PathBuf {
-    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join("files")
-        .join(filename)
+use crate::models::{Model, ModelRegistry};
+
+pub fn path_for_model(model: Model) -> PathBuf {
+    ModelRegistry::new()
+        .resolve(model)
+        .unwrap_or_else(|why| panic!("{}", why))
+        .path
 }
@@ -0,0 +1,421 @@
+//! Enrolling reference faces from image files.
+//!
+//! Images are decoded and run through the face detector and landmark predictor on a pool of worker threads,
+//! then the faces of every image are encoded together in a single batched pass through the encoder network.
+//!
+//! [`reference_images`] lists the photos of a reference directory with one folder per person.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crate::face_detection::FaceDetectorTrait;
+use crate::face_encoding::{FaceEncoderTrait, FaceEncoding};
+use crate::geometry::Rectangle;
+use crate::landmark_prediction::{FaceLandmarks, LandmarkPredictorTrait};
+use crate::matrix::ImageMatrix;
+use crate::quality::{Quality, QualityScorer};
+
+/// Extensions of image files. Files with these extensions that don't decode are reported rather than ignored.
+const IMAGE_EXTENSIONS: [&str; 10] = [
+    "jpg", "jpeg", "png", "bmp", "gif", "tif", "tiff", "webp", "ppm", "pgm",
+];
+
+/// Whether `path` is an image, judged by its first bytes rather than its name.
+///
+/// Fails if the file can't be read, or has an image extension but doesn't start like an image.
+pub fn is_image_file(path: &Path) -> Result<bool, String> {
+    let mut header = Vec::with_capacity(32);
+    File::open(path)
+        .and_then(|file| file.take(32).read_to_end(&mut header))
+        .map_err(|e| e.to_string())?;
+
+    if image::guess_format(&header).is_ok() {
+        return Ok(true);
+    }
+
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension {
+        Some(extension) if IMAGE_EXTENSIONS.contains(&extension.as_str()) => {
+            Err(format!("not a valid .{} image", extension))
+        }
+        _ => Ok(false),
+    }
+}
+
+/// The photos found by [`reference_images`].
+#[derive(Clone, Debug, Default)]
+pub struct ReferenceImages {
+    /// `(identity, path)` pairs, sorted by identity and path.
+    pub images: Vec<(String, PathBuf)>,
+    /// Files that should be images but can't be read, and why.
+    pub unreadable: Vec<(PathBuf, String)>,
+    /// Files that aren't images, e.g. notes or thumbnail caches.
+    pub ignored: Vec<PathBuf>,
+}
+
+impl ReferenceImages {
+    /// The number of distinct identities.
+    pub fn num_identities(&self) -> usize {
+        let mut identities: Vec<&str> = self.images.iter().map(|(i, _)| i.as_str()).collect();
+        identities.dedup();
+        identities.len()
+    }
+
+    /// Add the images in `dir` and the folders below it.
+    ///
+    /// Symbolic links are followed, but every folder is only entered once, so a link back up the tree doesn't loop.
+    fn add_dir(
+        &mut self,
+        identity: &str,
+        dir: &Path,
+        visited: &mut HashSet<PathBuf>,
+    ) -> Result<(), String> {
+        let canonical = fs::canonicalize(dir)
+            .map_err(|e| format!("Failed to read '{}': {}", dir.display(), e))?;
+        if !visited.insert(canonical) {
+            return Ok(());
+        }
+
+        for path in read_dir_sorted(dir)? {
+            if path.is_dir() {
+                self.add_dir(identity, &path, visited)?;
+            } else {
+                self.add_file(identity.to_string(), path);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_file(&mut self, identity: String, path: PathBuf) {
+        match is_image_file(&path) {
+            Ok(true) => self.images.push((identity, path)),
+            Ok(false) => self.ignored.push(path),
+            Err(why) => self.unreadable.push((path, why)),
+        }
+    }
+}
+
+/// The entries of `dir` by name, without hidden files.
+fn read_dir_sorted(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut paths = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read '{}': {}", dir.display(), e))?
+        .map(|entry| entry.map(|entry| entry.path()).map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    paths.retain(|path| {
+        path.file_name()
+            .is_some_and(|name| !name.to_string_lossy().starts_with('.'))
+    });
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// List the reference photos under `root`.
+///
+/// Every folder is a person, and all the photos in it, or in folders below it, are enrolled as that person:
+/// `root/alice/1.jpg` and `root/alice/holiday/2.png` are both `alice`.
+/// Photos directly under `root` are named after the file, without its extension.
+/// Files are told apart from images by their content, hidden files are skipped.
+pub fn reference_images(root: &Path) -> Result<ReferenceImages, String> {
+    let mut references = ReferenceImages::default();
+
+    for path in read_dir_sorted(root)? {
+        if path.is_dir() {
+            let identity = path.file_name().unwrap().to_string_lossy().into_owned();
+            references.add_dir(&identity, &path, &mut HashSet::new())?;
+        } else {
+            let identity = path.file_stem().unwrap().to_string_lossy().into_owned();
+            references.add_file(identity, path);
+        }
+    }
+
+    references.images.sort();
+    Ok(references)
+}
+
+/// The error of an image without any face.
+pub const NO_FACE_FOUND: &str = "no face found";
+
+/// A face enrolled from a reference image.
+#[derive(Clone, Debug)]
+pub struct Enrolled {
+    pub identity: String,
+    pub path: PathBuf,
+    /// The face that was encoded. If the image has several faces, this is the largest one.
+    pub rect: Rectangle,
+    /// The number of faces found in the image.
+    pub faces_found: usize,
+    pub encoding: FaceEncoding,
+    /// The quality of the face, see [`Enroller::with_quality_scorer`].
+    pub quality: Quality,
+}
+
+/// The outcome of enrolling a set of images, in the order the images were given.
+#[derive(Clone, Debug, Default)]
+pub struct EnrollmentReport {
+    pub enrolled: Vec<Enrolled>,
+    /// Images that could not be enrolled, and why.
+    pub errors: Vec<(PathBuf, String)>,
+}
+
+/// Passed to the progress callback every time an image has been processed by a worker.
+#[derive(Copy, Clone, Debug)]
+pub struct EnrollmentProgress<'a> {
+    /// The number of images processed so far, including this one.
+    pub done: usize,
+    pub total: usize,
+    pub path: &'a Path,
+    /// Why the image can't be enrolled, if it can't.
+    pub error: Option<&'a str>,
+}
+
+/// Enrolls reference images in parallel.
+///
+/// Each worker uses its own clone of the detector, as dlib detectors can't be shared between threads,
+/// while the landmark predictor is shared.
+pub struct Enroller<'a, D, P> {
+    detector: &'a D,
+    predictor: &'a P,
+    encoder: &'a dyn FaceEncoderTrait,
+    workers: usize,
+    num_jitters: u32,
+    quality_scorer: QualityScorer,
+}
+
+struct DetectedFace {
+    matrix: ImageMatrix,
+    rect: Rectangle,
+    faces_found: usize,
+    landmarks: FaceLandmarks,
+    quality: Quality,
+}
+
+impl<'a, D, P> Enroller<'a, D, P>
+where
+    D: FaceDetectorTrait + Clone + Send,
+    P: LandmarkPredictorTrait + Sync,
+{
+    /// Create an enroller with one worker per available cpu, no jitter and the default [`QualityScorer`].
+    pub fn new(detector: &'a D, predictor: &'a P, encoder: &'a dyn FaceEncoderTrait) -> Self {
+        let workers = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+
+        Self {
+            detector,
+            predictor,
+            encoder,
+            workers,
+            num_jitters: 0,
+            quality_scorer: QualityScorer::default(),
+        }
+    }
+
+    /// Set the number of worker threads used to decode images and detect faces.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Set the number of jitters used when encoding faces.
+    pub fn with_jitters(mut self, num_jitters: u32) -> Self {
+        self.num_jitters = num_jitters;
+        self
+    }
+
+    /// Rate the enrolled faces with `scorer`. Faces are enrolled whatever their quality,
+    /// it's up to the caller to leave out the ones that aren't [`Quality::acceptable`].
+    pub fn with_quality_scorer(mut self, scorer: QualityScorer) -> Self {
+        self.quality_scorer = scorer;
+        self
+    }
+
+    /// Enroll `(identity, path)` pairs, calling `progress` on the calling thread as each image is processed.
+    pub fn enroll<F>(&self, images: Vec<(String, PathBuf)>, mut progress: F) -> EnrollmentReport
+    where
+        F: FnMut(EnrollmentProgress),
+    {
+        let total = images.len();
+
+        let (job_send, job_recv) = flume::unbounded();
+        for job in images.into_iter().enumerate() {
+            job_send.send(job).unwrap();
+        }
+        drop(job_send);
+
+        let (result_send, result_recv) = flume::unbounded();
+
+        let mut detected = Vec::with_capacity(total);
+        let mut errors = Vec::new();
+
+        thread::scope(|scope| {
+            for _ in 0..self.workers.min(total) {
+                let job_recv = job_recv.clone();
+                let result_send = result_send.clone();
+                let detector = self.detector.clone();
+                let predictor = self.predictor;
+                let scorer = &self.quality_scorer;
+
+                scope.spawn(move || {
+                    for (index, (identity, path)) in job_recv.iter() {
+                        let result = detect_reference(&detector, predictor, scorer, &path);
+
+                        if result_send.send((index, identity, path, result)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(result_send);
+
+            for (done, (index, identity, path, result)) in result_recv.iter().enumerate() {
+                progress(EnrollmentProgress {
+                    done: done + 1,
+                    total,
+                    path: &path,
+                    error: result.as_ref().err().map(String::as_str),
+                });
+
+                match result {
+                    Ok(face) => detected.push((index, identity, path, face)),
+                    Err(error) => errors.push((index, path, error)),
+                }
+            }
+        });
+
+        detected.sort_by_key(|(index, ..)| *index);
+        errors.sort_by_key(|(index, ..)| *index);
+
+        let faces: Vec<(&ImageMatrix, &FaceLandmarks)> = detected
+            .iter()
+            .map(|(_, _, _, face)| (&face.matrix, &face.landmarks))
+            .collect();
+
+        let encodings = self
+            .encoder
+            .get_face_encodings_batch(&faces, self.num_jitters);
+
+        let enrolled = detected
+            .into_iter()
+            .zip(encodings.iter())
+            .map(|((_, identity, path, face), encoding)| Enrolled {
+                identity,
+                path,
+                rect: face.rect,
+                faces_found: face.faces_found,
+                encoding: encoding.clone(),
+                quality: face.quality,
+            })
+            .collect();
+
+        EnrollmentReport {
+            enrolled,
+            errors: errors
+                .into_iter()
+                .map(|(_, path, error)| (path, error))
+                .collect(),
+        }
+    }
+}
+
+fn detect_reference<D, P>(
+    detector: &D,
+    predictor: &P,
+    scorer: &QualityScorer,
+    path: &Path,
+) -> Result<DetectedFace, String>
+where
+    D: FaceDetectorTrait,
+    P: LandmarkPredictorTrait,
+{
+    let image = image::open(path).map_err(|e| e.to_string())?.to_rgb8();
+    let matrix = ImageMatrix::from_image(&image);
+    let locations = detector.face_locations(&matrix);
+
+    let rect = *locations
+        .iter()
+        .max_by_key(|rect| rect.area())
+        .ok_or(NO_FACE_FOUND)?;
+
+    let landmarks = predictor.face_landmarks(&matrix, &rect);
+    let quality = scorer.assess(&matrix, &rect, &landmarks);
+
+    Ok(DetectedFace {
+        matrix,
+        rect,
+        faces_found: locations.len(),
+        landmarks,
+        quality,
+    })
+}
+
+#[test]
+fn test_reference_images() {
+    let root = std::env::temp_dir().join(format!("fofscreen-references-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("alice").join("holiday")).unwrap();
+    fs::create_dir_all(root.join("bob")).unwrap();
+
+    let photo = image::RgbImage::new(4, 4);
+    photo.save(root.join("alice").join("1.jpg")).unwrap();
+    photo
+        .save(root.join("alice").join("holiday").join("2.png"))
+        .unwrap();
+    // images are recognised by their content, not their name
+    photo.save(root.join("bob").join("photo.png")).unwrap();
+    fs::rename(
+        root.join("bob").join("photo.png"),
+        root.join("bob").join("photo"),
+    )
+    .unwrap();
+    photo.save(root.join("carol.png")).unwrap();
+
+    fs::write(root.join("alice").join("notes.txt"), "not a photo").unwrap();
+    fs::write(root.join("alice").join(".DS_Store"), "hidden").unwrap();
+    fs::write(root.join("bob").join("broken.jpg"), "not a jpeg").unwrap();
+    // a link back up the tree is only followed once
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(
+        root.join("alice"),
+        root.join("alice").join("holiday").join("again"),
+    )
+    .unwrap();
+
+    let references = reference_images(&root).unwrap();
+    let images: Vec<(&str, PathBuf)> = references
+        .images
+        .iter()
+        .map(|(identity, path)| (identity.as_str(), path.strip_prefix(&root).unwrap().into()))
+        .collect();
+    assert_eq!(
+        images,
+        vec![
+            ("alice", PathBuf::from("alice/1.jpg")),
+            ("alice", PathBuf::from("alice/holiday/2.png")),
+            ("bob", PathBuf::from("bob/photo")),
+            ("carol", PathBuf::from("carol.png")),
+        ]
+    );
+    assert_eq!(references.num_identities(), 3);
+    assert_eq!(
+        references.ignored,
+        vec![root.join("alice").join("notes.txt")]
+    );
+    assert_eq!(references.unreadable.len(), 1);
+    assert_eq!(
+        references.unreadable[0].0,
+        root.join("bob").join("broken.jpg")
+    );
+    assert_eq!(references.unreadable[0].1, "not a valid .jpg image");
+
+    assert!(reference_images(&root.join("missing")).is_err());
+
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,112 @@
+use std::path::{Path, PathBuf};
+
+use super::roc::PairDistances;
+use crate::enrollment::{reference_images, ReferenceImages};
+use crate::face_detection::FaceDetectorTrait;
+use crate::face_encoding::{FaceEncoderTrait, FaceEncoding};
+use crate::landmark_prediction::LandmarkPredictorTrait;
+use crate::matrix::ImageMatrix;
+
+/// List the images of a labelled directory tree as `(identity, path)` pairs.
+///
+/// Every sub-directory of `root` is an identity, named after the directory, and every image inside it
+/// is a photo of that identity, see [`reference_images`]. Files that aren't images, and files directly
+/// under `root`, are left out.
+pub fn identity_images(root: &Path) -> Result<Vec<(String, PathBuf)>, String> {
+    Ok(labelled_images(root)?.images)
+}
+
+fn labelled_images(root: &Path) -> Result<ReferenceImages, String> {
+    let mut references = reference_images(root)?;
+    references
+        .images
+        .retain(|(_, path)| path.parent() != Some(root));
+    references
+        .unreadable
+        .retain(|(path, _)| path.parent() != Some(root));
+
+    Ok(references)
+}
+
+/// Face encodings of a labelled directory tree, one per usable image.
+#[derive(Default)]
+pub struct LabelledEncodings {
+    /// The identity and encoding of every image with exactly one face.
+    pub samples: Vec<(String, FaceEncoding)>,
+    /// Images that could not be used, and why.
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+impl LabelledEncodings {
+    /// Detect, landmark and encode every image listed by [`identity_images`].
+    ///
+    /// Images that can't be decoded, or that don't contain exactly one face, are recorded in `skipped`
+    /// rather than aborting the whole run.
+    pub fn from_directory(
+        root: &Path,
+        detector: &dyn FaceDetectorTrait,
+        predictor: &dyn LandmarkPredictorTrait,
+        encoder: &dyn FaceEncoderTrait,
+        num_jitters: u32,
+    ) -> Result<Self, String> {
+        let references = labelled_images(root)?;
+        let mut encodings = Self {
+            skipped: references.unreadable,
+            ..Self::default()
+        };
+
+        for (identity, path) in references.images {
+            let image = match image::open(&path) {
+                Ok(image) => image.to_rgb8(),
+                Err(e) => {
+                    encodings.skipped.push((path, e.to_string()));
+                    continue;
+                }
+            };
+
+            let matrix = ImageMatrix::from_image(&image);
+            let locations = detector.face_locations(&matrix);
+
+            if locations.len() != 1 {
+                let reason = format!("expected one face, found {}", locations.len());
+                encodings.skipped.push((path, reason));
+                continue;
+            }
+
+            let landmarks = predictor.face_landmarks(&matrix, &locations[0]);
+            let encoding = &encoder.get_face_encodings(&matrix, &[landmarks], num_jitters)[0];
+
+            encodings.samples.push((identity, encoding.clone()));
+        }
+
+        Ok(encodings)
+    }
+
+    /// The number of distinct identities with at least one sample.
+    pub fn num_identities(&self) -> usize {
+        let mut identities: Vec<&str> = self.samples.iter().map(|(i, _)| i.as_str()).collect();
+        identities.sort_unstable();
+        identities.dedup();
+        identities.len()
+    }
+
+    /// Compute the genuine and impostor distances of every pair of samples.
+    pub fn pair_distances(&self) -> PairDistances {
+        let mut genuine = Vec::new();
+        let mut impostor = Vec::new();
+
+        for (i, (identity_a, a)) in self.samples.iter().enumerate() {
+            for (identity_b, b) in &self.samples[i + 1..] {
+                let distance = a.distance(b);
+
+                if identity_a == identity_b {
+                    genuine.push(distance);
+                } else {
+                    impostor.push(distance);
+                }
+            }
+        }
+
+        PairDistances::new(genuine, impostor)
+    }
+}
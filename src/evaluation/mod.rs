@@ -0,0 +1,12 @@
+//! Tools for measuring recognition accuracy on a labelled set of faces.
+//!
+//! A labelled set is a directory with one folder per identity, each containing photos of that person.
+//! Every pair of encodings is compared: pairs of the same identity are *genuine* pairs, pairs of
+//! different identities are *impostor* pairs. Sweeping a distance threshold over both sets gives the
+//! ROC/DET curves, the equal error rate and the threshold that best separates the two.
+
+mod dataset;
+mod roc;
+
+pub use self::dataset::{identity_images, LabelledEncodings};
+pub use self::roc::{Evaluation, PairDistances, RocPoint};
@@ -0,0 +1,212 @@
+use std::io::{self, Write};
+
+/// Distances of genuine (same identity) and impostor (different identity) pairs, sorted ascending.
+#[derive(Clone, Debug, Default)]
+pub struct PairDistances {
+    genuine: Vec<f64>,
+    impostor: Vec<f64>,
+}
+
+/// A single point of the ROC curve.
+///
+/// A pair is accepted as a match when its distance is less than or equal to `threshold`,
+/// the same rule used by [`crate::FaceComparer`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RocPoint {
+    pub threshold: f64,
+    /// False accept rate: the fraction of impostor pairs accepted.
+    pub far: f64,
+    /// True accept rate: the fraction of genuine pairs accepted.
+    pub tar: f64,
+}
+
+impl RocPoint {
+    /// False reject rate: the fraction of genuine pairs rejected.
+    pub fn frr(&self) -> f64 {
+        1.0 - self.tar
+    }
+}
+
+/// The result of sweeping the distance threshold over a set of pair distances.
+#[derive(Clone, Debug)]
+pub struct Evaluation {
+    pub genuine_pairs: usize,
+    pub impostor_pairs: usize,
+    /// One point per distinct distance, in order of increasing threshold, after the point at a threshold of
+    /// negative infinity that accepts nothing.
+    pub points: Vec<RocPoint>,
+    /// The point where the false accept and false reject rates are closest.
+    pub eer_point: RocPoint,
+    /// The point that maximises `tar - far`.
+    pub best: RocPoint,
+}
+
+impl PairDistances {
+    /// Sort the distances of both sets. NaN distances, e.g. of a broken encoding, count as pairs that never match.
+    pub fn new(genuine: Vec<f64>, impostor: Vec<f64>) -> Self {
+        let sorted = |distances: Vec<f64>| {
+            // a NaN of either sign sorts last, after every threshold
+            let mut distances: Vec<f64> = distances
+                .into_iter()
+                .map(|d| if d.is_nan() { f64::NAN } else { d })
+                .collect();
+            distances.sort_by(|a, b| a.total_cmp(b));
+            distances
+        };
+
+        Self {
+            genuine: sorted(genuine),
+            impostor: sorted(impostor),
+        }
+    }
+
+    pub fn genuine(&self) -> &[f64] {
+        &self.genuine
+    }
+
+    pub fn impostor(&self) -> &[f64] {
+        &self.impostor
+    }
+
+    /// Sweep the threshold over every observed distance.
+    ///
+    /// Returns `None` if there are no genuine or no impostor pairs, as the rates would be undefined.
+    pub fn evaluate(&self) -> Option<Evaluation> {
+        if self.genuine.is_empty() || self.impostor.is_empty() {
+            return None;
+        }
+
+        let mut thresholds: Vec<f64> = self
+            .genuine
+            .iter()
+            .chain(&self.impostor)
+            .cloned()
+            .filter(|d| !d.is_nan())
+            .collect();
+        thresholds.sort_by(|a, b| a.total_cmp(b));
+        thresholds.dedup();
+        thresholds.insert(0, f64::NEG_INFINITY);
+
+        let num_genuine = self.genuine.len() as f64;
+        let num_impostor = self.impostor.len() as f64;
+
+        let points: Vec<RocPoint> = thresholds
+            .into_iter()
+            .map(|threshold| RocPoint {
+                threshold,
+                far: self.impostor.partition_point(|&d| d <= threshold) as f64 / num_impostor,
+                tar: self.genuine.partition_point(|&d| d <= threshold) as f64 / num_genuine,
+            })
+            .collect();
+
+        let min_by = |key: &dyn Fn(&RocPoint) -> f64| {
+            *points
+                .iter()
+                .min_by(|a, b| key(a).total_cmp(&key(b)))
+                .unwrap()
+        };
+
+        let eer_point = min_by(&|p| (p.far - p.frr()).abs());
+        let best = min_by(&|p| p.far - p.tar);
+
+        Some(Evaluation {
+            genuine_pairs: self.genuine.len(),
+            impostor_pairs: self.impostor.len(),
+            points,
+            eer_point,
+            best,
+        })
+    }
+}
+
+impl Evaluation {
+    /// The equal error rate, the average of the false accept and false reject rates at `eer_point`.
+    pub fn eer(&self) -> f64 {
+        (self.eer_point.far + self.eer_point.frr()) / 2.0
+    }
+
+    /// The point with the highest true accept rate whose false accept rate does not exceed `far`.
+    pub fn tar_at_far(&self, far: f64) -> Option<RocPoint> {
+        self.points.iter().rev().find(|p| p.far <= far).cloned()
+    }
+
+    /// Write the curve as CSV with a `threshold,far,tar,frr` header.
+    ///
+    /// `far` against `tar` is the ROC curve, `far` against `frr` is the DET curve.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "threshold,far,tar,frr")?;
+
+        for point in &self.points {
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                point.threshold,
+                point.far,
+                point.tar,
+                point.frr()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_separable_distances() {
+    let distances = PairDistances::new(vec![0.3, 0.2, 0.4], vec![0.7, 0.9, 0.8, 0.6]);
+    let evaluation = distances.evaluate().unwrap();
+
+    assert_eq!(evaluation.genuine_pairs, 3);
+    assert_eq!(evaluation.impostor_pairs, 4);
+    assert_eq!(evaluation.points.len(), 8);
+    assert_eq!(
+        evaluation.points[0],
+        RocPoint {
+            threshold: f64::NEG_INFINITY,
+            far: 0.0,
+            tar: 0.0
+        }
+    );
+    assert_eq!(evaluation.eer(), 0.0);
+    assert_eq!(evaluation.best.threshold, 0.4);
+    assert_eq!(evaluation.best.tar, 1.0);
+    assert_eq!(evaluation.best.far, 0.0);
+    assert_eq!(evaluation.tar_at_far(0.0).unwrap().threshold, 0.4);
+}
+
+#[test]
+fn test_overlapping_distances() {
+    let distances = PairDistances::new(vec![0.1, 0.2, 0.5, 0.7], vec![0.4, 0.6, 0.8, 0.9]);
+    let evaluation = distances.evaluate().unwrap();
+
+    assert_eq!(evaluation.eer(), 0.25);
+    assert_eq!(evaluation.tar_at_far(0.0).unwrap().tar, 0.5);
+    assert_eq!(evaluation.tar_at_far(0.25).unwrap().tar, 0.75);
+    assert_eq!(evaluation.tar_at_far(1.0).unwrap().threshold, 0.9);
+
+    let mut csv = Vec::new();
+    evaluation.write_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+
+    assert_eq!(csv.lines().count(), 10);
+    assert_eq!(csv.lines().nth(1), Some("-inf,0,0,1"));
+    assert_eq!(csv.lines().nth(2), Some("0.1,0,0.25,0.75"));
+}
+
+#[test]
+fn test_nan_distances() {
+    let distances = PairDistances::new(vec![0.2, f64::NAN, -f64::NAN], vec![0.8, f64::NAN]);
+    let evaluation = distances.evaluate().unwrap();
+
+    assert_eq!(evaluation.genuine_pairs, 3);
+    assert_eq!(evaluation.points.len(), 3);
+    let last = evaluation.points.last().unwrap();
+    assert_eq!((last.threshold, last.far, last.tar), (0.8, 0.5, 1.0 / 3.0));
+    assert_eq!(evaluation.best.threshold, 0.2);
+}
+
+#[test]
+fn test_missing_pairs() {
+    assert!(PairDistances::new(vec![0.1], vec![]).evaluate().is_none());
+    assert!(PairDistances::default().evaluate().is_none());
+}
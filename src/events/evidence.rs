@@ -0,0 +1,395 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use image::RgbImage;
+
+use super::{format_rfc3339, parse_rfc3339};
+
+/// The file format of saved frames and chips.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    Jpeg,
+    Png,
+}
+
+impl SnapshotFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SnapshotFormat::Jpeg => "jpg",
+            SnapshotFormat::Png => "png",
+        }
+    }
+}
+
+/// How much evidence the [`EvidenceStore`] keeps. `None` doesn't limit.
+///
+/// Incidents are removed oldest first, by the time in their name, and the newest one is always kept.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Retention {
+    /// The number of incidents to keep.
+    pub max_count: Option<usize>,
+    /// Remove incidents older than this.
+    pub max_age: Option<Duration>,
+    /// The total size of the kept incidents.
+    pub max_bytes: Option<u64>,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            max_count: Some(500),
+            max_age: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+            max_bytes: Some(512 * 1024 * 1024),
+        }
+    }
+}
+
+/// The files saved for one incident.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Evidence {
+    /// The directory of the incident, named after the time of the frame.
+    pub dir: PathBuf,
+    pub frame: PathBuf,
+    /// The chip of every face, in the order they were passed to [`EvidenceStore::save`].
+    pub chips: Vec<PathBuf>,
+}
+
+/// Saves frames with unknown faces, and the chips of those faces, to a directory per incident:
+///
+/// ```text
+/// evidence/2021-07-04T09-30-00.250Z/frame.jpg
+/// evidence/2021-07-04T09-30-00.250Z/chip-0.jpg
+/// ```
+///
+/// At most one incident is saved per cooldown, and old incidents are removed according to the [`Retention`].
+pub struct EvidenceStore {
+    dir: PathBuf,
+    format: SnapshotFormat,
+    retention: Retention,
+    cooldown: Duration,
+    last_saved: Option<SystemTime>,
+}
+
+/// An incident directory found when pruning.
+struct Incident {
+    path: PathBuf,
+    time: SystemTime,
+    /// Tells apart incidents of the same time, 0 for the first one.
+    suffix: u32,
+    bytes: u64,
+}
+
+/// The file name of the directory of an incident at `time`, before adding a suffix for incidents of the same time.
+fn incident_name(time: SystemTime) -> String {
+    // colons aren't allowed in file names everywhere
+    format_rfc3339(time).replace(':', "-")
+}
+
+/// The time and suffix of an incident directory named by [`EvidenceStore::save`], `None` for any other name.
+fn parse_incident_name(name: &str) -> Option<(SystemTime, u32)> {
+    let (time, suffix) = match name.get(24..)? {
+        "" => (name, 0),
+        rest => {
+            let digits = rest.strip_prefix('-')?;
+            if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+                return None;
+            }
+            (
+                name.get(..24)?,
+                digits.parse().ok().filter(|&suffix| suffix > 0)?,
+            )
+        }
+    };
+
+    if time.as_bytes()[13] != b'-' || time.as_bytes()[16] != b'-' {
+        return None;
+    }
+    let time = format!("{}:{}:{}", &time[..13], &time[14..16], &time[17..]);
+
+    Some((parse_rfc3339(&time)?, suffix))
+}
+
+fn dir_size(path: &Path) -> u64 {
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok()?.metadata().ok())
+                .filter(|metadata| metadata.is_file())
+                .map(|metadata| metadata.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+impl EvidenceStore {
+    /// Save evidence under `dir`, creating it if needed, as JPEG with the default [`Retention`] and a
+    /// cooldown of 5 seconds.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, String> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)
+            .map_err(|why| format!("Can't create evidence directory {}: {}", dir.display(), why))?;
+
+        Ok(Self {
+            dir,
+            format: SnapshotFormat::Jpeg,
+            retention: Retention::default(),
+            cooldown: Duration::from_secs(5),
+            last_saved: None,
+        })
+    }
+
+    pub fn with_format(mut self, format: SnapshotFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_retention(mut self, retention: Retention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// The least time between two incidents, so someone sitting at the computer doesn't fill the disk.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Whether an incident at `time` would be saved, or is still within the cooldown of the last one.
+    pub fn is_ready(&self, time: SystemTime) -> bool {
+        match self.last_saved {
+            Some(last) => time
+                .duration_since(last)
+                .is_ok_and(|elapsed| elapsed >= self.cooldown),
+            None => true,
+        }
+    }
+
+    /// Save a frame and the chips of its unknown faces as an incident at `time`, then prune old incidents.
+    ///
+    /// Returns `None` without saving anything within the cooldown.
+    pub fn save(
+        &mut self,
+        time: SystemTime,
+        frame: &RgbImage,
+        chips: &[RgbImage],
+    ) -> Result<Option<Evidence>, String> {
+        if !self.is_ready(time) {
+            return Ok(None);
+        }
+
+        let name = incident_name(time);
+        let mut dir = self.dir.join(&name);
+        let mut suffix = 1;
+        while dir.exists() {
+            dir = self.dir.join(format!("{}-{}", name, suffix));
+            suffix += 1;
+        }
+        fs::create_dir(&dir)
+            .map_err(|why| format!("Can't create evidence directory {}: {}", dir.display(), why))?;
+
+        let save = |image: &RgbImage, name: String| -> Result<PathBuf, String> {
+            let path = dir.join(format!("{}.{}", name, self.format.extension()));
+            image
+                .save(&path)
+                .map_err(|why| format!("Can't save evidence {}: {}", path.display(), why))?;
+            Ok(path)
+        };
+
+        let frame = save(frame, "frame".to_string())?;
+        let chips = chips
+            .iter()
+            .enumerate()
+            .map(|(index, chip)| save(chip, format!("chip-{}", index)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.last_saved = Some(time);
+        self.prune(time)?;
+
+        Ok(Some(Evidence { dir, frame, chips }))
+    }
+
+    /// Remove the incidents the [`Retention`] no longer allows at `now`, returning how many were removed.
+    ///
+    /// Only directories named like the incidents [`EvidenceStore::save`] creates are incidents, anything else
+    /// in the evidence directory is left alone.
+    pub fn prune(&self, now: SystemTime) -> Result<usize, String> {
+        let entries = fs::read_dir(&self.dir).map_err(|why| {
+            format!(
+                "Can't read evidence directory {}: {}",
+                self.dir.display(),
+                why
+            )
+        })?;
+
+        let mut incidents = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let (time, suffix) = parse_incident_name(entry.file_name().to_str()?)?;
+                if !entry.metadata().ok()?.is_dir() {
+                    return None;
+                }
+
+                Some(Incident {
+                    bytes: dir_size(&entry.path()),
+                    path: entry.path(),
+                    time,
+                    suffix,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // newest first, incidents of the same time by the order they were saved in
+        incidents.sort_by(|a, b| b.time.cmp(&a.time).then(b.suffix.cmp(&a.suffix)));
+
+        let mut removed = 0;
+        let mut bytes = 0;
+        for (index, incident) in incidents.iter().enumerate() {
+            bytes += incident.bytes;
+
+            let too_many = self.retention.max_count.is_some_and(|max| index >= max);
+            let too_old = self
+                .retention
+                .max_age
+                .is_some_and(|max| now.duration_since(incident.time).is_ok_and(|age| age > max));
+            let too_large = self.retention.max_bytes.is_some_and(|max| bytes > max);
+
+            if index > 0 && (too_many || too_old || too_large) {
+                fs::remove_dir_all(&incident.path).map_err(|why| {
+                    format!("Can't remove evidence {}: {}", incident.path.display(), why)
+                })?;
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+#[test]
+fn test_evidence_retention() {
+    use std::time::UNIX_EPOCH;
+
+    let dir = std::env::temp_dir().join(format!("fofscreen-evidence-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let frame = RgbImage::from_pixel(32, 24, image::Rgb([200, 10, 10]));
+    let chip = RgbImage::from_pixel(8, 8, image::Rgb([10, 200, 10]));
+    let time = UNIX_EPOCH + Duration::from_millis(1_625_391_000_250);
+
+    let mut store = EvidenceStore::open(&dir)
+        .unwrap()
+        .with_format(SnapshotFormat::Png)
+        .with_cooldown(Duration::from_secs(5))
+        .with_retention(Retention {
+            max_count: Some(3),
+            max_age: None,
+            max_bytes: None,
+        });
+
+    let evidence = store
+        .save(time, &frame, &[chip.clone(), chip.clone()])
+        .unwrap()
+        .unwrap();
+    assert_eq!(evidence.dir, dir.join("2021-07-04T09-30-00.250Z"));
+    assert_eq!(evidence.frame, evidence.dir.join("frame.png"));
+    assert_eq!(
+        evidence.chips,
+        vec![
+            evidence.dir.join("chip-0.png"),
+            evidence.dir.join("chip-1.png")
+        ]
+    );
+    assert_eq!(image::open(&evidence.frame).unwrap().to_rgb8(), frame);
+    assert_eq!(image::open(&evidence.chips[1]).unwrap().to_rgb8(), chip);
+
+    // within the cooldown nothing is saved
+    assert!(!store.is_ready(time + Duration::from_secs(1)));
+    assert_eq!(
+        store
+            .save(time + Duration::from_secs(1), &frame, &[])
+            .unwrap(),
+        None
+    );
+
+    // only the newest incidents are kept
+    for i in 1..5 {
+        let time = time + Duration::from_secs(5 * i);
+        assert!(store.save(time, &frame, &[]).unwrap().is_some());
+    }
+    let count = || fs::read_dir(&dir).unwrap().count();
+    assert_eq!(count(), 3);
+
+    // incidents of the same time get their own directory
+    let store = EvidenceStore::open(&dir).unwrap();
+    let mut store = store.with_cooldown(Duration::from_secs(0));
+    let first = store.save(time, &frame, &[]).unwrap().unwrap();
+    let second = store.save(time, &frame, &[]).unwrap().unwrap();
+    assert_ne!(first.dir, second.dir);
+
+    // other files and directories aren't incidents, whatever their age and size
+    let foreign = ["backup", "2021-07-04T09-30-00.250Z-old"];
+    for name in foreign.iter() {
+        fs::create_dir(dir.join(name)).unwrap();
+        fs::write(dir.join(name).join("keep.txt"), b"not evidence").unwrap();
+    }
+    fs::write(dir.join("notes.txt"), b"not evidence").unwrap();
+
+    // the newest incident is kept even when it's too large or too old
+    let store = store.with_retention(Retention {
+        max_count: None,
+        max_age: Some(Duration::from_secs(60)),
+        max_bytes: Some(1),
+    });
+    assert_eq!(store.prune(SystemTime::now()).unwrap(), 4);
+    assert_eq!(count(), 1 + foreign.len() + 1);
+    assert!(dir.join("2021-07-04T09-30-20.250Z").is_dir());
+    for name in foreign.iter() {
+        assert!(dir.join(name).join("keep.txt").is_file());
+    }
+    assert_eq!(
+        store
+            .prune(SystemTime::now() + Duration::from_secs(3600))
+            .unwrap(),
+        0
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_incident_names() {
+    use std::time::UNIX_EPOCH;
+
+    let time = UNIX_EPOCH + Duration::from_millis(1_625_391_000_250);
+    assert_eq!(incident_name(time), "2021-07-04T09-30-00.250Z");
+    assert_eq!(
+        parse_incident_name("2021-07-04T09-30-00.250Z"),
+        Some((time, 0))
+    );
+    assert_eq!(
+        parse_incident_name("2021-07-04T09-30-00.250Z-12"),
+        Some((time, 12))
+    );
+
+    for name in [
+        "",
+        "evidence",
+        "2021-07-04T09:30:00.250Z",
+        "2021-07-04T09-30-00.250Z-",
+        "2021-07-04T09-30-00.250Z-0",
+        "2021-07-04T09-30-00.250Z-+1",
+        "2021-07-04T09-30-00.250Z-old",
+        "2021-07-04T09-30-00.250Z1",
+        "2021-07-04T09-30-00.250",
+        "2021-07-04T09-30-00.250Z-99999999999",
+    ]
+    .iter()
+    {
+        assert_eq!(parse_incident_name(name), None, "{}", name);
+    }
+}
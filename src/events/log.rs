@@ -0,0 +1,185 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::{Event, Record};
+
+/// When the [`EventLog`] starts a new file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rotation {
+    /// Start a new file once the current one reaches this size.
+    pub max_bytes: u64,
+    /// The number of rotated files to keep, as `events.jsonl.1` (the newest) to `events.jsonl.N`.
+    pub keep: usize,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self {
+            max_bytes: 10 * 1024 * 1024,
+            keep: 5,
+        }
+    }
+}
+
+/// Appends events to a JSON Lines file, rotating it when it grows too large.
+///
+/// Every event is flushed as soon as it's written, so the log survives the process being killed.
+pub struct EventLog {
+    path: PathBuf,
+    file: BufWriter<File>,
+    size: u64,
+    rotation: Rotation,
+}
+
+fn open_append(path: &Path) -> Result<(BufWriter<File>, u64), String> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|why| format!("Can't open event log {}: {}", path.display(), why))?;
+    let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+
+    Ok((BufWriter::new(file), size))
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+impl EventLog {
+    /// Open `path` for appending, creating it if needed, with the default [`Rotation`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        let (file, size) = open_append(&path)?;
+
+        Ok(Self {
+            path,
+            file,
+            size,
+            rotation: Rotation::default(),
+        })
+    }
+
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record an event that happened just now.
+    pub fn log(&mut self, event: Event) -> Result<(), String> {
+        self.log_at(SystemTime::now(), event)
+    }
+
+    /// Record an event that happened at `time`, e.g. when the frame it was seen in was captured.
+    pub fn log_at(&mut self, time: SystemTime, event: Event) -> Result<(), String> {
+        let mut line =
+            serde_json::to_string(&Record::new(time, event)).map_err(|e| e.to_string())?;
+        line.push('\n');
+
+        if self.size > 0 && self.size + line.len() as u64 > self.rotation.max_bytes {
+            self.rotate()?;
+        }
+
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|_| self.file.flush())
+            .map_err(|why| format!("Can't write to event log {}: {}", self.path.display(), why))?;
+        self.size += line.len() as u64;
+
+        Ok(())
+    }
+
+    /// Move the current file to `.1`, shifting older files up and deleting the oldest, and start a new one.
+    fn rotate(&mut self) -> Result<(), String> {
+        self.file.flush().map_err(|e| e.to_string())?;
+
+        if self.rotation.keep == 0 {
+            fs::remove_file(&self.path).map_err(|e| e.to_string())?;
+        } else {
+            let oldest = rotated_path(&self.path, self.rotation.keep);
+            if oldest.exists() {
+                fs::remove_file(&oldest).map_err(|e| e.to_string())?;
+            }
+
+            for index in (1..self.rotation.keep).rev() {
+                let from = rotated_path(&self.path, index);
+                if from.exists() {
+                    fs::rename(&from, rotated_path(&self.path, index + 1))
+                        .map_err(|e| e.to_string())?;
+                }
+            }
+
+            fs::rename(&self.path, rotated_path(&self.path, 1)).map_err(|e| e.to_string())?;
+        }
+
+        let (file, size) = open_append(&self.path)?;
+        self.file = file;
+        self.size = size;
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_event_log_rotation() {
+    let dir = std::env::temp_dir().join(format!("fofscreen-events-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("events.jsonl");
+
+    let read_lines = |path: &Path| -> Vec<Record> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    };
+
+    // two lines fit in a file, but not three
+    let line = serde_json::to_string(&Record::new(
+        SystemTime::now(),
+        Event::Started { references: 0 },
+    ));
+    let line_len = line.unwrap().len() as u64 + 1;
+    let mut log = EventLog::open(&path).unwrap().with_rotation(Rotation {
+        max_bytes: line_len * 5 / 2,
+        keep: 2,
+    });
+    for references in 0..7 {
+        log.log(Event::Started { references }).unwrap();
+    }
+
+    let references = |path: &Path| -> Vec<usize> {
+        read_lines(path)
+            .into_iter()
+            .map(|record| match record.event {
+                Event::Started { references } => references,
+                _ => panic!("unexpected event"),
+            })
+            .collect()
+    };
+
+    assert_eq!(references(&path), vec![6]);
+    assert_eq!(references(&rotated_path(&path, 1)), vec![4, 5]);
+    assert_eq!(references(&rotated_path(&path, 2)), vec![2, 3]);
+    assert!(!rotated_path(&path, 3).exists());
+
+    // reopening appends to the current file
+    drop(log);
+    let mut log = EventLog::open(&path).unwrap();
+    log.log(Event::Stopped).unwrap();
+    assert_eq!(read_lines(&path).len(), 2);
+    assert_eq!(read_lines(&path)[1].event, Event::Stopped);
+
+    assert!(EventLog::open(dir.join("missing").join("events.jsonl")).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,204 @@
+//! A structured log of sightings.
+//!
+//! Every [`Event`] is written to an [`EventLog`] as one JSON object per line (JSON Lines),
+//! with an RFC 3339 timestamp and an `event` field naming its kind:
+//!
+//! ```text
+//! {"timestamp":"2021-07-04T09:30:00.250Z","event":"sighting","frame":42,"rect":{"left":10,"top":20,"right":110,"bottom":120},"identity":"alice","distance":0.41}
+//! ```
+//!
+//! Fields that aren't known, like the liveness score of a face the pipeline doesn't check for replays, are left out.
+//!
+//! Unknown faces can also be raised as an [`Alert`], referencing the frame and face chip an [`EvidenceStore`] saved.
+
+mod evidence;
+mod log;
+mod time;
+
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+pub use self::evidence::{Evidence, EvidenceStore, Retention, SnapshotFormat};
+pub use self::log::{EventLog, Rotation};
+pub use self::time::{format_rfc3339, parse_rfc3339};
+
+use crate::attributes::Estimate;
+use crate::geometry::Rectangle;
+use crate::pipeline::RecognisedFace;
+use crate::quality::frontalness;
+
+/// A face seen in a frame.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sighting {
+    /// The position of the frame in the capture.
+    pub frame: u64,
+    /// The [track](crate::pipeline::Tracker) of the face, the same for a face seen in consecutive frames.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_id: Option<u64>,
+    pub rect: Rectangle,
+    /// The reference the face matched, `None` for unknown faces.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+    /// The distance to the matched reference.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f64>,
+    /// How likely the face is live rather than a photo or a screen, from 0 to 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub liveness: Option<f64>,
+    /// The [quality score](crate::quality::Quality::score) of the face, from 0 to 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<f64>,
+    /// The pose of the face, as its [frontalness](crate::quality::frontalness) from 0, in profile,
+    /// to 1, facing the camera.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pose: Option<f64>,
+    /// Attributes estimated for the face, such as a mask that makes the match less reliable.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attributes: Vec<Estimate>,
+}
+
+impl Sighting {
+    /// A sighting of a face found by the [`Pipeline`](crate::pipeline::Pipeline) in frame `frame`.
+    pub fn new(frame: u64, face: &RecognisedFace) -> Self {
+        Self {
+            frame,
+            track_id: Some(face.track_id),
+            rect: face.rect,
+            identity: face.identity.as_ref().map(|m| m.identity.clone()),
+            distance: face.identity.as_ref().map(|m| m.distance),
+            liveness: face.spoof.as_ref().map(|spoof| spoof.liveness()),
+            quality: face.quality.as_ref().map(|quality| quality.score),
+            pose: frontalness(&face.landmarks),
+            attributes: face.attributes.clone(),
+        }
+    }
+}
+
+/// An unknown face, with the evidence saved of it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    #[serde(flatten)]
+    pub sighting: Sighting,
+    /// The saved frame the face was seen in.
+    pub frame_image: PathBuf,
+    /// The saved chip of the face.
+    pub chip_image: PathBuf,
+}
+
+/// Something worth recording in the [`EventLog`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Recognition started, with the number of known references.
+    Started {
+        references: usize,
+    },
+    Sighting(Sighting),
+    Alert(Alert),
+    /// A face was enrolled while running.
+    Enrolled {
+        identity: String,
+    },
+    Stopped,
+}
+
+/// An event with the time it happened, as written to a line of the log.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// An RFC 3339 UTC timestamp.
+    pub timestamp: String,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+impl Record {
+    pub fn new(time: SystemTime, event: Event) -> Self {
+        Self {
+            timestamp: format_rfc3339(time),
+            event,
+        }
+    }
+}
+
+#[test]
+fn test_event_format() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::attributes::Attribute;
+
+    let time = UNIX_EPOCH + Duration::from_millis(1_625_391_000_250);
+    let sighting = Sighting {
+        frame: 42,
+        track_id: None,
+        rect: Rectangle {
+            left: 10,
+            top: 20,
+            right: 110,
+            bottom: 120,
+        },
+        identity: Some("alice".to_string()),
+        distance: Some(0.41),
+        liveness: None,
+        quality: None,
+        pose: None,
+        attributes: vec![],
+    };
+
+    let record = Record::new(time, Event::Sighting(sighting.clone()));
+    let line = serde_json::to_string(&record).unwrap();
+    assert_eq!(
+        line,
+        r#"{"timestamp":"2021-07-04T09:30:00.250Z","event":"sighting","frame":42,"rect":{"left":10,"top":20,"right":110,"bottom":120},"identity":"alice","distance":0.41}"#
+    );
+    assert_eq!(serde_json::from_str::<Record>(&line).unwrap(), record);
+
+    let sighting = Sighting {
+        track_id: Some(3),
+        liveness: Some(0.9),
+        pose: Some(0.8),
+        ..sighting
+    };
+    let line =
+        serde_json::to_string(&Record::new(time, Event::Sighting(sighting.clone()))).unwrap();
+    assert!(line.contains(r#""frame":42,"track_id":3,"rect""#));
+    assert!(line.ends_with(r#""distance":0.41,"liveness":0.9,"pose":0.8}"#));
+
+    let masked = Sighting {
+        attributes: vec![Estimate {
+            attribute: Attribute::Mask,
+            confidence: 0.75,
+        }],
+        ..sighting.clone()
+    };
+    let line = serde_json::to_string(&Record::new(time, Event::Sighting(masked.clone()))).unwrap();
+    assert!(line.ends_with(r#""attributes":[{"attribute":"mask","confidence":0.75}]}"#));
+    assert_eq!(
+        serde_json::from_str::<Record>(&line).unwrap().event,
+        Event::Sighting(masked)
+    );
+
+    let alert = Alert {
+        sighting: Sighting {
+            identity: None,
+            distance: None,
+            ..sighting.clone()
+        },
+        frame_image: PathBuf::from("evidence/2021-07-04T09-30-00.250Z/frame.jpg"),
+        chip_image: PathBuf::from("evidence/2021-07-04T09-30-00.250Z/chip-0.jpg"),
+    };
+    let record = Record::new(time, Event::Alert(alert));
+    let line = serde_json::to_string(&record).unwrap();
+    assert!(line.contains(r#""event":"alert","frame":42,"track_id":3"#));
+    assert!(line.ends_with(r#""frame_image":"evidence/2021-07-04T09-30-00.250Z/frame.jpg","chip_image":"evidence/2021-07-04T09-30-00.250Z/chip-0.jpg"}"#));
+    assert_eq!(serde_json::from_str::<Record>(&line).unwrap(), record);
+
+    let line = serde_json::to_string(&Record::new(time, Event::Stopped)).unwrap();
+    assert_eq!(
+        line,
+        r#"{"timestamp":"2021-07-04T09:30:00.250Z","event":"stopped"}"#
+    );
+    let line = serde_json::to_string(&Record::new(time, Event::Started { references: 2 })).unwrap();
+    assert!(line.ends_with(r#""event":"started","references":2}"#));
+}
@@ -0,0 +1,145 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Format a time as an RFC 3339 UTC timestamp with milliseconds, e.g. `2021-07-04T09:30:00.250Z`.
+pub fn format_rfc3339(time: SystemTime) -> String {
+    // times before 1970 don't happen on a running capture, clamp them rather than fail
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let millis = since_epoch.subsec_millis();
+
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let seconds_of_day = secs % 86_400;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        millis
+    )
+}
+
+/// Parse a timestamp in the form written by [`format_rfc3339`], e.g. `2021-07-04T09:30:00.250Z`.
+///
+/// Returns `None` for anything else, including other RFC 3339 forms and times before 1970.
+pub fn parse_rfc3339(text: &str) -> Option<SystemTime> {
+    let number = |start: usize, end: usize| -> Option<i64> {
+        let digits = text.get(start..end)?;
+        if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    };
+
+    let separators = [
+        (4, b'-'),
+        (7, b'-'),
+        (10, b'T'),
+        (13, b':'),
+        (16, b':'),
+        (19, b'.'),
+        (23, b'Z'),
+    ];
+    if text.len() != 24
+        || separators
+            .iter()
+            .any(|&(index, byte)| text.as_bytes()[index] != byte)
+    {
+        return None;
+    }
+
+    let (year, month, day) = (number(0, 4)?, number(5, 7)?, number(8, 10)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+    let seconds = days * 86_400 + number(11, 13)? * 3600 + number(14, 16)? * 60 + number(17, 19)?;
+    if seconds < 0 {
+        return None;
+    }
+    let time = UNIX_EPOCH
+        + Duration::from_secs(seconds as u64)
+        + Duration::from_millis(number(20, 23)? as u64);
+
+    // out of range days, hours and so on come out as another time
+    Some(time).filter(|time| format_rfc3339(*time) == text)
+}
+
+/// The number of days since 1970-01-01 of a Gregorian date, from Howard Hinnant's `days_from_civil`.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * mp + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The Gregorian date of a number of days since 1970-01-01, from Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[test]
+fn test_format_rfc3339() {
+    let at = |secs: u64, millis: u64| {
+        format_rfc3339(UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis))
+    };
+
+    assert_eq!(at(0, 0), "1970-01-01T00:00:00.000Z");
+    assert_eq!(at(951_782_400, 5), "2000-02-29T00:00:00.005Z");
+    assert_eq!(at(1_625_391_000, 250), "2021-07-04T09:30:00.250Z");
+    assert_eq!(at(4_102_444_799, 999), "2099-12-31T23:59:59.999Z");
+    assert_eq!(
+        format_rfc3339(UNIX_EPOCH - Duration::from_secs(1)),
+        "1970-01-01T00:00:00.000Z"
+    );
+
+    for text in [
+        "1970-01-01T00:00:00.000Z",
+        "2000-02-29T00:00:00.005Z",
+        "2021-07-04T09:30:00.250Z",
+        "2099-12-31T23:59:59.999Z",
+    ]
+    .iter()
+    {
+        assert_eq!(format_rfc3339(parse_rfc3339(text).unwrap()), *text);
+    }
+    assert_eq!(
+        parse_rfc3339("2021-07-04T09:30:00.250Z"),
+        Some(UNIX_EPOCH + Duration::from_millis(1_625_391_000_250))
+    );
+
+    for text in [
+        "",
+        "2021-07-04T09:30:00Z",
+        "2021-07-04T09:30:00.250+00:00",
+        "2021-07-04T09-30-00.250Z",
+        "2021-02-29T09:30:00.250Z",
+        "2021-07-04T24:30:00.250Z",
+        "2021-13-04T09:30:00.250Z",
+        "+021-07-04T09:30:00.250Z",
+        "1969-12-31T23:59:59.999Z",
+    ]
+    .iter()
+    {
+        assert_eq!(parse_rfc3339(text), None, "{}", text);
+    }
+}
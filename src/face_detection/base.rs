@@ -1,7 +1,18 @@
+use super::detection::FaceDetection;
 use super::location::FaceLocations;
 use crate::matrix::ImageMatrix;
 
 pub trait FaceDetectorTrait {
     /// Detect face rectangles from an image.
     fn face_locations(&self, image: &ImageMatrix) -> FaceLocations;
+
+    /// Detect faces from an image, with the confidence of each detection.
+    ///
+    /// The default implementation scores every face found by [`FaceDetectorTrait::face_locations`] as `1.0`.
+    fn face_detections(&self, image: &ImageMatrix) -> Vec<FaceDetection> {
+        self.face_locations(image)
+            .iter()
+            .map(|&rect| FaceDetection { rect, score: 1.0 })
+            .collect()
+    }
 }
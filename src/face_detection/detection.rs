@@ -0,0 +1,44 @@
+use std::slice;
+
+use serde::{Deserialize, Serialize};
+
+use crate::geometry::Rectangle;
+
+/// A face rectangle with the confidence of the detector that found it.
+///
+/// Scores are only comparable between detections of the same detector:
+/// the HOG detector scores are SVM margins, the CNN detector scores are network outputs.
+/// Detectors that have no notion of confidence score every face as `1.0`.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct FaceDetection {
+    pub rect: Rectangle,
+    pub score: f64,
+}
+
+cpp_class!(
+    /// A `std::vector<face_detection>` returned by the dlib detectors.
+    pub(crate) unsafe struct FaceDetections as "std::vector<face_detection>"
+);
+
+impl FaceDetections {
+    pub(crate) fn into_vec(self) -> Vec<FaceDetection> {
+        let len = unsafe {
+            cpp!([self as "std::vector<face_detection>*"] -> usize as "size_t" {
+                return self->size();
+            })
+        };
+
+        if len == 0 {
+            Vec::new()
+        } else {
+            unsafe {
+                let pointer = cpp!([self as "std::vector<face_detection>*"] -> *const FaceDetection as "face_detection*" {
+                    return &(*self)[0];
+                });
+
+                slice::from_raw_parts(pointer, len).to_vec()
+            }
+        }
+    }
+}
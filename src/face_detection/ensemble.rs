@@ -0,0 +1,220 @@
+use super::base::FaceDetectorTrait;
+use super::detection::FaceDetection;
+use super::location::FaceLocations;
+use crate::matrix::ImageMatrix;
+
+/// How overlapping detections are merged by [`non_max_suppression`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NmsOptions {
+    /// Two detections with an intersection over union above this are the same face.
+    pub iou_threshold: f64,
+    /// Detections scoring below this are dropped.
+    pub min_score: f64,
+}
+
+impl Default for NmsOptions {
+    fn default() -> Self {
+        Self {
+            iou_threshold: 0.4,
+            min_score: 0.0,
+        }
+    }
+}
+
+/// Keep the best scoring detection of every group of overlapping detections, best first.
+pub fn non_max_suppression(
+    mut detections: Vec<FaceDetection>,
+    options: &NmsOptions,
+) -> Vec<FaceDetection> {
+    detections.retain(|detection| detection.score >= options.min_score);
+    detections.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let mut kept: Vec<FaceDetection> = Vec::with_capacity(detections.len());
+
+    for detection in detections {
+        let overlaps = kept
+            .iter()
+            .any(|other| other.rect.iou(&detection.rect) > options.iou_threshold);
+
+        if !overlaps {
+            kept.push(detection);
+        }
+    }
+
+    kept
+}
+
+/// Runs several face detectors on the same image and merges their faces with [`non_max_suppression`].
+///
+/// Detectors can be different models, or the same model wrapped in [`super::ScaledDetector`]s of different scales
+/// to find both small and large faces. As scores of different models aren't comparable, each detector has a weight
+/// its scores are multiplied by before merging.
+///
+/// ```no_run
+/// use fofscreen::face_detection::{EnsembleDetector, FaceDetector, FaceDetectorCnn, ScaledDetector};
+///
+/// let cnn = FaceDetectorCnn::new("mmod_human_face_detector.dat").unwrap();
+/// let ensemble = EnsembleDetector::new()
+///     .with_detector(FaceDetector::new())
+///     .with_detector(ScaledDetector::new(FaceDetector::new(), 2.0).unwrap())
+///     .with_weighted_detector(cnn, 2.0);
+/// ```
+#[derive(Default)]
+pub struct EnsembleDetector<'a> {
+    detectors: Vec<(Box<dyn FaceDetectorTrait + Send + 'a>, f64)>,
+    options: NmsOptions,
+}
+
+impl<'a> EnsembleDetector<'a> {
+    /// Create an ensemble without detectors, merging with the default [`NmsOptions`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_detector<D: FaceDetectorTrait + Send + 'a>(self, detector: D) -> Self {
+        self.with_weighted_detector(detector, 1.0)
+    }
+
+    /// Add a detector whose scores are multiplied by `weight`.
+    pub fn with_weighted_detector<D: FaceDetectorTrait + Send + 'a>(
+        mut self,
+        detector: D,
+        weight: f64,
+    ) -> Self {
+        self.detectors.push((Box::new(detector), weight));
+        self
+    }
+
+    pub fn with_options(mut self, options: NmsOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn options(&self) -> &NmsOptions {
+        &self.options
+    }
+
+    /// The number of detectors in the ensemble.
+    pub fn len(&self) -> usize {
+        self.detectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.detectors.is_empty()
+    }
+}
+
+impl<'a> FaceDetectorTrait for EnsembleDetector<'a> {
+    fn face_locations(&self, image: &ImageMatrix) -> FaceLocations {
+        self.face_detections(image)
+            .iter()
+            .map(|detection| detection.rect)
+            .collect()
+    }
+
+    fn face_detections(&self, image: &ImageMatrix) -> Vec<FaceDetection> {
+        let detections = self
+            .detectors
+            .iter()
+            .flat_map(|(detector, weight)| {
+                detector
+                    .face_detections(image)
+                    .into_iter()
+                    .map(move |detection| FaceDetection {
+                        rect: detection.rect,
+                        score: detection.score * weight,
+                    })
+            })
+            .collect();
+
+        non_max_suppression(detections, &self.options)
+    }
+}
+
+#[cfg(test)]
+use crate::geometry::Rectangle;
+
+#[cfg(test)]
+fn detection(x: i64, y: i64, size: i64, score: f64) -> FaceDetection {
+    FaceDetection {
+        rect: Rectangle::from_xywh(x, y, size, size),
+        score,
+    }
+}
+
+#[test]
+fn test_non_max_suppression() {
+    let detections = vec![
+        detection(0, 0, 100, 0.5),
+        detection(5, 5, 100, 0.9),
+        detection(200, 0, 50, 0.3),
+        detection(210, 0, 50, -0.2),
+        detection(0, 300, 100, 0.6),
+        // mostly inside the best face, but with an iou of only 0.19
+        detection(0, 0, 50, 0.7),
+    ];
+
+    let kept = non_max_suppression(detections.clone(), &NmsOptions::default());
+    assert_eq!(
+        kept,
+        vec![
+            detection(5, 5, 100, 0.9),
+            detection(0, 0, 50, 0.7),
+            detection(0, 300, 100, 0.6),
+            detection(200, 0, 50, 0.3),
+        ]
+    );
+
+    let strict = NmsOptions {
+        iou_threshold: 0.15,
+        min_score: 0.55,
+    };
+    let kept = non_max_suppression(detections, &strict);
+    assert_eq!(
+        kept,
+        vec![detection(5, 5, 100, 0.9), detection(0, 300, 100, 0.6)]
+    );
+
+    assert!(non_max_suppression(vec![], &NmsOptions::default()).is_empty());
+}
+
+#[test]
+fn test_ensemble() {
+    struct Fixed(Vec<FaceDetection>);
+
+    impl FaceDetectorTrait for Fixed {
+        fn face_locations(&self, _: &ImageMatrix) -> FaceLocations {
+            self.0.iter().map(|detection| detection.rect).collect()
+        }
+
+        fn face_detections(&self, _: &ImageMatrix) -> Vec<FaceDetection> {
+            self.0.clone()
+        }
+    }
+
+    let image = ImageMatrix::default();
+
+    let ensemble = EnsembleDetector::new();
+    assert!(ensemble.is_empty());
+    assert!(ensemble.face_detections(&image).is_empty());
+
+    let ensemble = ensemble
+        .with_detector(Fixed(vec![
+            detection(0, 0, 100, 1.0),
+            detection(300, 0, 100, 1.0),
+        ]))
+        .with_weighted_detector(
+            Fixed(vec![detection(2, 2, 100, 0.8), detection(0, 300, 40, 0.2)]),
+            2.0,
+        );
+    assert_eq!(ensemble.len(), 2);
+
+    assert_eq!(
+        ensemble.face_detections(&image),
+        vec![
+            detection(2, 2, 100, 1.6),
+            detection(300, 0, 100, 1.0),
+            detection(0, 300, 40, 0.4),
+        ]
+    );
+}
@@ -1,4 +1,8 @@
+use std::cell::Cell;
+use std::marker::PhantomData;
+
 use super::base::FaceDetectorTrait;
+use super::detection::{FaceDetection, FaceDetections};
 use super::location::FaceLocations;
 use crate::matrix::ImageMatrix;
 
@@ -7,8 +11,14 @@ use crate::matrix::ImageMatrix;
 ///
 /// Pretty fast (~100ms for test images on my machine), but not as accurate (misses more faces)
 /// as the neural network face detector.
+///
+/// # Thread safety
+///
+/// The detector is `Send` but not `Sync`: dlib's `object_detector` loads each image into its scanner,
+/// so concurrent detections on one instance race. Clone it for each thread instead, clones are cheap.
 pub struct FaceDetector {
     inner: FaceDetectorInner,
+    _not_sync: PhantomData<Cell<()>>,
 }
 
 cpp_class!(unsafe struct FaceDetectorInner as "dlib::frontal_face_detector");
@@ -24,7 +34,10 @@ impl FaceDetector {
             })
         };
 
-        Self { inner }
+        Self {
+            inner,
+            _not_sync: PhantomData,
+        }
     }
 }
 
@@ -44,13 +57,35 @@ impl FaceDetectorTrait for FaceDetector {
             })
         }
     }
+
+    fn face_detections(&self, image: &ImageMatrix) -> Vec<FaceDetection> {
+        let detector = &self.inner;
+
+        let detections = unsafe {
+            cpp!([detector as "dlib::frontal_face_detector*", image as "dlib::matrix<dlib::rgb_pixel>*"] -> FaceDetections as "std::vector<face_detection>" {
+                std::vector<dlib::rect_detection> detections;
+                (*detector)(*image, detections);
+
+                std::vector<face_detection> faces;
+                faces.reserve(detections.size());
+
+                for (auto &detection: detections) {
+                    faces.push_back(face_detection { detection.rect, detection.detection_confidence });
+                }
+
+                return faces;
+            })
+        };
+
+        detections.into_vec()
+    }
 }
 
 #[test]
 fn test_face_detection() {
     use crate::geometry::Rectangle;
 
-    let image = image::open("assets/obama_1.jpg").unwrap().to_rgb();
+    let image = image::open("benches/obama_1.jpg").unwrap().to_rgb();
     let matrix = ImageMatrix::from_image(&image);
     let detector = FaceDetector::new();
 
@@ -66,4 +101,10 @@ fn test_face_detection() {
             bottom: 328
         }
     );
+
+    let detections = detector.face_detections(&matrix);
+
+    assert_eq!(detections.len(), 1);
+    assert_eq!(detections[0].rect, locations[0]);
+    assert!(detections[0].score > 0.0);
 }
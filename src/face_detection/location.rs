@@ -1,14 +1,50 @@
 use std::fmt;
+use std::iter::FromIterator;
 use std::ops::Deref;
 use std::slice;
+use std::vec;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::geometry::Rectangle;
 
 cpp_class!(
     /// A rust wrapper around a `std::vector<rectangle>`.
+    ///
+    /// Besides the detectors, locations can be built from rectangles in rust,
+    /// e.g. to run the landmark predictor on annotated faces.
     pub unsafe struct FaceLocations as "std::vector<dlib::rectangle>"
 );
 
+impl FaceLocations {
+    /// Create an empty list of locations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copy rectangles into a new `std::vector<rectangle>`.
+    pub fn from_slice(rects: &[Rectangle]) -> Self {
+        let len = rects.len();
+        let rects = rects.as_ptr();
+
+        unsafe {
+            cpp!([rects as "const dlib::rectangle*", len as "size_t"] -> FaceLocations as "std::vector<dlib::rectangle>" {
+                return std::vector<dlib::rectangle>(rects, rects + len);
+            })
+        }
+    }
+
+    pub fn push(&mut self, rect: Rectangle) {
+        let rect = &rect;
+
+        unsafe {
+            cpp!([self as "std::vector<dlib::rectangle>*", rect as "const dlib::rectangle*"] {
+                self->push_back(*rect);
+            })
+        }
+    }
+}
+
 impl Deref for FaceLocations {
     type Target = [Rectangle];
 
@@ -38,3 +74,110 @@ impl fmt::Debug for FaceLocations {
         self.deref().fmt(f)
     }
 }
+
+impl PartialEq for FaceLocations {
+    fn eq(&self, other: &Self) -> bool {
+        self.deref().eq(other.deref())
+    }
+}
+
+impl From<&[Rectangle]> for FaceLocations {
+    fn from(rects: &[Rectangle]) -> Self {
+        Self::from_slice(rects)
+    }
+}
+
+impl From<Vec<Rectangle>> for FaceLocations {
+    fn from(rects: Vec<Rectangle>) -> Self {
+        Self::from_slice(&rects)
+    }
+}
+
+impl From<FaceLocations> for Vec<Rectangle> {
+    fn from(locations: FaceLocations) -> Self {
+        locations.to_vec()
+    }
+}
+
+impl FromIterator<Rectangle> for FaceLocations {
+    fn from_iter<I: IntoIterator<Item = Rectangle>>(iter: I) -> Self {
+        let mut locations = Self::new();
+
+        for rect in iter {
+            locations.push(rect);
+        }
+
+        locations
+    }
+}
+
+impl Extend<Rectangle> for FaceLocations {
+    fn extend<I: IntoIterator<Item = Rectangle>>(&mut self, iter: I) {
+        for rect in iter {
+            self.push(rect);
+        }
+    }
+}
+
+impl IntoIterator for FaceLocations {
+    type Item = Rectangle;
+    type IntoIter = vec::IntoIter<Rectangle>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let rects: Vec<Rectangle> = self.to_vec();
+        rects.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a FaceLocations {
+    type Item = &'a Rectangle;
+    type IntoIter = slice::Iter<'a, Rectangle>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Serialized as a sequence of rectangles.
+impl Serialize for FaceLocations {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for FaceLocations {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<Rectangle>::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[test]
+fn test_rust_locations() {
+    let a = Rectangle::from_xywh(0, 0, 10, 10);
+    let b = Rectangle::from_xywh(20, 20, 5, 5);
+
+    let mut locations = FaceLocations::new();
+    assert!(locations.is_empty());
+
+    locations.push(a);
+    locations.extend(vec![b]);
+    assert_eq!(&locations[..], &[a, b]);
+
+    let collected: FaceLocations = vec![a, b].into_iter().collect();
+    assert_eq!(collected, locations);
+    assert_eq!(FaceLocations::from(vec![a, b]), locations);
+
+    let cloned = locations.clone();
+    assert_eq!(Vec::from(cloned.clone()), vec![a, b]);
+    assert_eq!(cloned.into_iter().rev().collect::<Vec<_>>(), vec![b, a]);
+
+    let json = serde_json::to_string(&collected).unwrap();
+    assert_eq!(
+        json,
+        r#"[{"left":0,"top":0,"right":9,"bottom":9},{"left":20,"top":20,"right":24,"bottom":24}]"#
+    );
+    assert_eq!(
+        serde_json::from_str::<FaceLocations>(&json).unwrap(),
+        collected
+    );
+}
@@ -0,0 +1,202 @@
+use std::cell::Cell;
+use std::ffi::CStr;
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::base::FaceDetectorTrait;
+use super::detection::{FaceDetection, FaceDetections};
+use super::location::FaceLocations;
+use crate::base::{path_as_cstring, CppString};
+use crate::geometry::Rectangle;
+use crate::matrix::ImageMatrix;
+
+/// An object found by a [`MmodDetector`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MmodDetection {
+    pub rect: Rectangle,
+    pub score: f64,
+    /// The label the detector was trained with, empty for detectors trained on unlabelled boxes.
+    pub label: String,
+}
+
+/// A max-margin object detector (MMOD) network with the architecture of dlib's CNN face detector.
+///
+/// Any network file trained with the network of dlib's `dnn_mmod_face_detection_ex.cpp` example can be loaded,
+/// whatever object it detects. Networks trained with batch normalisation layers are converted to affine layers
+/// on load, like dlib does itself.
+///
+/// # Compatible models
+///
+/// - `mmod_human_face_detector.dat`, the model of [`crate::FaceDetectorCnn`].
+/// - Custom detectors trained with `dnn_mmod_face_detection_ex.cpp` (three 45 filter `rcon5` layers over the
+///   16/32/32 filter downsampler), for any object and with any number of labels.
+///
+/// dlib's `mmod_dog_hipsterizer.dat`, `mmod_front_and_rear_end_vehicle_detector.dat` and
+/// `mmod_rear_end_vehicle_detector.dat` use 55 filter layers and are rejected by [`MmodDetector::new`]
+/// with an error, as is any file that isn't a network at all.
+/// `shape_predictor_5_face_landmarks.dat` is not a network, it loads with [`crate::LandmarkPredictor`].
+///
+/// # Thread safety
+///
+/// Like [`crate::FaceEncoderNetwork`], the network is `Send` but not `Sync`.
+#[derive(Clone)]
+pub struct MmodDetector {
+    inner: MmodNetwork,
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+cpp_class!(unsafe struct MmodNetwork as "face_detection_cnn");
+
+cpp_class!(unsafe struct MmodRects as "std::vector<dlib::mmod_rect>");
+
+impl MmodDetector {
+    /// Load a detector from a network file.
+    ///
+    /// Files with another architecture return an error with dlib's reason instead of aborting.
+    pub fn new<P: AsRef<Path>>(filename: P) -> Result<Self, String> {
+        let string = path_as_cstring(filename.as_ref())?;
+        let inner = MmodNetwork::default();
+        let message = CppString::default();
+
+        let deserialized = unsafe {
+            let filename = string.as_ptr();
+            let network = &inner;
+            let message = &message;
+
+            cpp!([filename as "char*", network as "face_detection_cnn*", message as "std::string*"] -> bool as "bool" {
+                try {
+                    dlib::deserialize(filename) >> *network;
+                    return true;
+                } catch (const std::exception& exception) {
+                    *message = exception.what();
+                    return false;
+                }
+            })
+        };
+
+        if !deserialized {
+            Err(format!(
+                "Failed to deserialize '{}' as an MMOD detector: {}",
+                filename.as_ref().display(),
+                message.to_string_lossy().trim()
+            ))
+        } else {
+            Ok(Self {
+                inner,
+                _not_sync: PhantomData,
+            })
+        }
+    }
+
+    /// Detect objects in an image, with their labels.
+    pub fn detect(&self, image: &ImageMatrix) -> Vec<MmodDetection> {
+        let detector = &self.inner;
+
+        let rects = unsafe {
+            cpp!([detector as "face_detection_cnn*", image as "dlib::matrix<dlib::rgb_pixel>*"] -> MmodRects as "std::vector<dlib::mmod_rect>" {
+                return (*detector)(*image);
+            })
+        };
+
+        let len = unsafe {
+            cpp!([rects as "std::vector<dlib::mmod_rect>*"] -> usize as "size_t" {
+                return rects->size();
+            })
+        };
+
+        (0..len)
+            .map(|index| unsafe {
+                let rects = &rects;
+                let rect = cpp!([rects as "std::vector<dlib::mmod_rect>*", index as "size_t"] -> Rectangle as "dlib::rectangle" {
+                    return (*rects)[index].rect;
+                });
+                let score = cpp!([rects as "std::vector<dlib::mmod_rect>*", index as "size_t"] -> f64 as "double" {
+                    return (*rects)[index].detection_confidence;
+                });
+                let label = cpp!([rects as "std::vector<dlib::mmod_rect>*", index as "size_t"] -> *const c_char as "const char*" {
+                    return (*rects)[index].label.c_str();
+                });
+
+                MmodDetection {
+                    rect,
+                    score,
+                    label: CStr::from_ptr(label).to_string_lossy().into_owned(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl FaceDetectorTrait for MmodDetector {
+    fn face_locations(&self, image: &ImageMatrix) -> FaceLocations {
+        let detector = &self.inner;
+
+        unsafe {
+            cpp!([detector as "face_detection_cnn*", image as "dlib::matrix<dlib::rgb_pixel>*"] -> FaceLocations as "std::vector<dlib::rectangle>" {
+                std::vector<dlib::mmod_rect> detections = (*detector)(*image);
+                // Convert from mmod rectangles
+                // see: https://github.com/davisking/dlib/blob/master/dlib/image_processing/full_object_detection.h#L132
+                // to regular rectangles
+
+                std::vector<dlib::rectangle> rects;
+                rects.reserve(detections.size());
+
+                for (auto &detection: detections) {
+                    rects.push_back(detection.rect);
+                }
+
+                return rects;
+            })
+        }
+    }
+
+    fn face_detections(&self, image: &ImageMatrix) -> Vec<FaceDetection> {
+        let detector = &self.inner;
+
+        let detections = unsafe {
+            cpp!([detector as "face_detection_cnn*", image as "dlib::matrix<dlib::rgb_pixel>*"] -> FaceDetections as "std::vector<face_detection>" {
+                std::vector<dlib::mmod_rect> detections = (*detector)(*image);
+
+                std::vector<face_detection> faces;
+                faces.reserve(detections.size());
+
+                for (auto &detection: detections) {
+                    faces.push_back(face_detection { detection.rect, detection.detection_confidence });
+                }
+
+                return faces;
+            })
+        };
+
+        detections.into_vec()
+    }
+}
+
+#[test]
+fn test_mismatched_files() {
+    let directory = std::env::temp_dir().join(format!("fofscreen-mmod-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    let missing = MmodDetector::new(directory.join("missing.dat"))
+        .err()
+        .unwrap();
+    assert!(missing.starts_with("File not found"), "{}", missing);
+
+    let garbage = directory.join("garbage.dat");
+    std::fs::write(&garbage, b"definitely not a dlib network").unwrap();
+    let error = MmodDetector::new(&garbage).err().unwrap();
+    assert!(error.starts_with("Failed to deserialize"), "{}", error);
+
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    // a dlib model, but a shape predictor instead of a network
+    #[cfg(feature = "embed-lp")]
+    {
+        let predictor = crate::embed::path_for_model(crate::models::Model::LandmarkPredictor);
+        let error = MmodDetector::new(&predictor).err().unwrap();
+        assert!(error.starts_with("Failed to deserialize"), "{}", error);
+    }
+}
@@ -2,10 +2,18 @@
 
 mod base;
 mod cnn;
+mod detection;
+mod ensemble;
 mod hog;
 mod location;
+mod mmod;
+mod scaled;
 
 pub use self::base::FaceDetectorTrait;
 pub use self::cnn::FaceDetectorCnn;
+pub use self::detection::FaceDetection;
+pub use self::ensemble::{non_max_suppression, EnsembleDetector, NmsOptions};
 pub use self::hog::FaceDetector;
 pub use self::location::FaceLocations;
+pub use self::mmod::{MmodDetection, MmodDetector};
+pub use self::scaled::ScaledDetector;
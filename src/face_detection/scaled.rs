@@ -0,0 +1,225 @@
+use super::base::FaceDetectorTrait;
+use super::detection::FaceDetection;
+use super::location::FaceLocations;
+use crate::geometry::Rectangle;
+use crate::matrix::ImageMatrix;
+
+/// Runs a face detector on a downscaled image, or on a region of interest, and maps the faces back.
+///
+/// Detection time grows with the number of pixels, so detecting on a 1080p frame scaled by `0.5`
+/// is about four times faster, at the cost of missing faces smaller than about twice the detector's window.
+/// The returned rectangles are in the coordinates of the original image,
+/// so landmarks can be predicted and faces encoded at full resolution.
+///
+/// ```no_run
+/// use fofscreen::{FaceDetector, Rectangle, ScaledDetector};
+///
+/// let detector = ScaledDetector::new(FaceDetector::new(), 0.5)
+///     .unwrap()
+///     .with_roi(Rectangle { left: 480, top: 0, right: 1439, bottom: 1079 });
+/// ```
+#[derive(Clone)]
+pub struct ScaledDetector<D> {
+    detector: D,
+    scale: f64,
+    roi: Option<Rectangle>,
+}
+
+impl<D: FaceDetectorTrait> ScaledDetector<D> {
+    /// Wrap `detector`, resizing images by `scale` before detecting faces.
+    ///
+    /// A scale below 1 speeds up detection, a scale above 1 finds smaller faces.
+    pub fn new(detector: D, scale: f64) -> Result<Self, String> {
+        let mut scaled = Self {
+            detector,
+            scale: 1.0,
+            roi: None,
+        };
+        scaled.set_scale(scale)?;
+
+        Ok(scaled)
+    }
+
+    /// Only look for faces inside `roi`, given in the coordinates of the original image.
+    pub fn with_roi(mut self, roi: Rectangle) -> Self {
+        self.roi = Some(roi);
+        self
+    }
+
+    pub fn set_scale(&mut self, scale: f64) -> Result<(), String> {
+        if !scale.is_finite() || scale <= 0.0 {
+            return Err(format!("Invalid detection scale {}", scale));
+        }
+
+        self.scale = scale;
+        Ok(())
+    }
+
+    pub fn set_roi(&mut self, roi: Option<Rectangle>) {
+        self.roi = roi;
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    pub fn roi(&self) -> Option<Rectangle> {
+        self.roi
+    }
+
+    pub fn detector(&self) -> &D {
+        &self.detector
+    }
+
+    pub fn into_inner(self) -> D {
+        self.detector
+    }
+}
+
+impl<D: FaceDetectorTrait> ScaledDetector<D> {
+    /// Run `detect` on the cropped and scaled image, and map the detections back.
+    fn detect<F>(&self, image: &ImageMatrix, detect: F) -> Vec<FaceDetection>
+    where
+        F: FnOnce(&D, &ImageMatrix) -> Vec<FaceDetection>,
+    {
+        let cropped;
+        let (image, offset) = match self.roi {
+            Some(roi) => {
+                cropped = image.crop(&roi);
+                (&cropped, (roi.left.max(0), roi.top.max(0)))
+            }
+            None => (image, (0, 0)),
+        };
+
+        let (width, height) = (image.width(), image.height());
+        if width == 0 || height == 0 {
+            return Vec::new();
+        }
+
+        let mut detections;
+        let factor;
+
+        // an unscaled image goes straight to the detector
+        if (self.scale - 1.0).abs() < f64::EPSILON {
+            detections = detect(&self.detector, image);
+            factor = (1.0, 1.0);
+        } else {
+            let scaled_width = ((width as f64 * self.scale).round() as usize).max(1);
+            let scaled_height = ((height as f64 * self.scale).round() as usize).max(1);
+            let scaled = image.resize(scaled_width, scaled_height);
+
+            detections = detect(&self.detector, &scaled);
+            // the actual ratio differs slightly from `scale` after rounding
+            factor = (
+                width as f64 / scaled_width as f64,
+                height as f64 / scaled_height as f64,
+            );
+        }
+
+        for detection in &mut detections {
+            detection.rect = map_back(&detection.rect, factor, offset);
+        }
+
+        detections
+    }
+}
+
+impl<D: FaceDetectorTrait> FaceDetectorTrait for ScaledDetector<D> {
+    fn face_locations(&self, image: &ImageMatrix) -> FaceLocations {
+        self.detect(image, |detector, image| {
+            detector
+                .face_locations(image)
+                .iter()
+                .map(|&rect| FaceDetection { rect, score: 1.0 })
+                .collect()
+        })
+        .iter()
+        .map(|detection| detection.rect)
+        .collect()
+    }
+
+    fn face_detections(&self, image: &ImageMatrix) -> Vec<FaceDetection> {
+        self.detect(image, |detector, image| detector.face_detections(image))
+    }
+}
+
+/// Map a rectangle found in a scaled and cropped image back to the original image.
+///
+/// Rectangles are inclusive, so the right and bottom edges are scaled as the pixel past them.
+fn map_back(rect: &Rectangle, (fx, fy): (f64, f64), (dx, dy): (i64, i64)) -> Rectangle {
+    Rectangle {
+        left: (rect.left as f64 * fx).round() as i64 + dx,
+        top: (rect.top as f64 * fy).round() as i64 + dy,
+        right: ((rect.right + 1) as f64 * fx).round() as i64 - 1 + dx,
+        bottom: ((rect.bottom + 1) as f64 * fy).round() as i64 - 1 + dy,
+    }
+}
+
+#[test]
+fn test_map_back() {
+    let rect = Rectangle {
+        left: 10,
+        top: 20,
+        right: 29,
+        bottom: 59,
+    };
+
+    assert_eq!(map_back(&rect, (1.0, 1.0), (0, 0)), rect);
+    assert_eq!(
+        map_back(&rect, (2.0, 4.0), (100, 200)),
+        Rectangle {
+            left: 120,
+            top: 280,
+            right: 159,
+            bottom: 439,
+        }
+    );
+}
+
+#[test]
+fn test_scaled_detection() {
+    use super::FaceDetector;
+
+    let image = image::open("benches/obama_1.jpg").unwrap().to_rgb8();
+    let matrix = ImageMatrix::from_image(&image);
+
+    // the face is at (305, 113) - (520, 328) at full resolution
+    let expected = Rectangle {
+        left: 305,
+        top: 113,
+        right: 520,
+        bottom: 328,
+    };
+    let close = |rect: &Rectangle| {
+        (rect.left - expected.left).abs() <= 12
+            && (rect.top - expected.top).abs() <= 12
+            && (rect.right - expected.right).abs() <= 12
+            && (rect.bottom - expected.bottom).abs() <= 12
+    };
+
+    assert!(ScaledDetector::new(FaceDetector::new(), 0.0).is_err());
+    assert!(ScaledDetector::new(FaceDetector::new(), f64::NAN).is_err());
+
+    let mut detector = ScaledDetector::new(FaceDetector::new(), 0.5).unwrap();
+    let locations = detector.face_locations(&matrix);
+    assert_eq!(locations.len(), 1);
+    assert!(close(&locations[0]), "{:?}", locations[0]);
+
+    detector.set_roi(Some(Rectangle {
+        left: 200,
+        top: 50,
+        right: 650,
+        bottom: 450,
+    }));
+    let locations = detector.face_locations(&matrix);
+    assert_eq!(locations.len(), 1);
+    assert!(close(&locations[0]), "{:?}", locations[0]);
+
+    detector.set_roi(Some(Rectangle {
+        left: 0,
+        top: 0,
+        right: 200,
+        bottom: 200,
+    }));
+    assert!(detector.face_locations(&matrix).is_empty());
+}
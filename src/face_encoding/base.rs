@@ -6,10 +6,70 @@ pub trait FaceEncoderTrait {
     /// Get a number of face encodings from an image and a list of landmarks, and jitter them a certain amount.
     ///
     /// It is recommended to keep `num_jitters` at 0 unless you know what you're doing.
+    ///
+    /// # Panics
+    ///
+    /// The face encoding network panics if any of the landmarks aren't [alignable](FaceLandmarks::is_alignable),
+    /// use [`FaceEncoderTrait::try_get_face_encodings`] for landmarks that weren't predicted by dlib.
     fn get_face_encodings(
         &self,
         image: &ImageMatrix,
         landmarks: &[FaceLandmarks],
         num_jitters: u32,
     ) -> FaceEncodings;
+
+    /// Get the face encodings of faces spread over several images, in the order they are given.
+    ///
+    /// The default implementation encodes the faces one at a time,
+    /// implementors should override it to run all faces through the network together.
+    fn get_face_encodings_batch(
+        &self,
+        faces: &[(&ImageMatrix, &FaceLandmarks)],
+        num_jitters: u32,
+    ) -> FaceEncodings {
+        let mut encodings = FaceEncodings::default();
+
+        for (image, landmarks) in faces {
+            let encoding = &self.get_face_encodings(image, &[(*landmarks).clone()], num_jitters)[0];
+            encodings.push(encoding.clone());
+        }
+
+        encodings
+    }
+
+    /// Like [`FaceEncoderTrait::get_face_encodings`], but fails if any of the landmarks aren't alignable.
+    fn try_get_face_encodings(
+        &self,
+        image: &ImageMatrix,
+        landmarks: &[FaceLandmarks],
+        num_jitters: u32,
+    ) -> Result<FaceEncodings, String> {
+        check_alignable(landmarks.iter())?;
+
+        Ok(self.get_face_encodings(image, landmarks, num_jitters))
+    }
+
+    /// Like [`FaceEncoderTrait::get_face_encodings_batch`], but fails if any of the landmarks aren't alignable.
+    fn try_get_face_encodings_batch(
+        &self,
+        faces: &[(&ImageMatrix, &FaceLandmarks)],
+        num_jitters: u32,
+    ) -> Result<FaceEncodings, String> {
+        check_alignable(faces.iter().map(|(_, landmarks)| *landmarks))?;
+
+        Ok(self.get_face_encodings_batch(faces, num_jitters))
+    }
+}
+
+/// dlib asserts the number of parts while aligning faces, and an exception thrown there aborts the process.
+pub(crate) fn check_alignable<'a, I: Iterator<Item = &'a FaceLandmarks>>(
+    mut landmarks: I,
+) -> Result<(), String> {
+    match landmarks.find(|landmarks| !landmarks.is_alignable()) {
+        Some(landmarks) => Err(format!(
+            "Can't encode a face with {} landmarks, only 68 or 5 point landmarks can be aligned",
+            landmarks.len()
+        )),
+        None => Ok(()),
+    }
 }
@@ -1,8 +1,18 @@
+use std::convert::TryFrom;
 use std::fmt;
 use std::ops::Deref;
 use std::slice;
 
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The number of values in an encoding of the face encoding network.
+pub const ENCODING_LEN: usize = 128;
+
 /// A wrapper around a `matrix<double,0,1>>`, an encoding.
+///
+/// Encodings serialize as a sequence of numbers, so they can be stored and loaded again without the network.
+/// Every encoding has [`ENCODING_LEN`] values, anything else is rejected when it is created or deserialized.
 #[derive(Clone)]
 pub struct FaceEncoding {
     inner: FaceEncodingInner,
@@ -29,11 +39,41 @@ impl FaceEncoding {
         Self { inner }
     }
 
+    /// Create an encoding from its values, e.g. to load a stored encoding.
+    ///
+    /// Fails unless there are exactly [`ENCODING_LEN`] values.
+    pub fn try_from_slice(values: &[f64]) -> Result<Self, String> {
+        if values.len() != ENCODING_LEN {
+            return Err(format!(
+                "An encoding has {} values, got {}",
+                ENCODING_LEN,
+                values.len()
+            ));
+        }
+
+        let len = values.len();
+        let values = values.as_ptr();
+
+        let inner = unsafe {
+            cpp!([values as "const double*", len as "size_t"] -> FaceEncodingInner as "dlib::matrix<double,0,1>" {
+                auto inner = dlib::matrix<double,0,1>(len);
+                for (size_t i = 0; i < len; i++) {
+                    inner(i) = values[i];
+                }
+
+                return inner;
+            })
+        };
+
+        Ok(Self { inner })
+    }
+
     /// Calculate the euclidean distance between two encodings.
     ///
     /// This value can be compared to a constant to determine if the faces are the same or not.
     /// A good value for this is `0.6`.
     pub fn distance(&self, other: &Self) -> f64 {
+        // dlib doesn't check the sizes of the matrices in release builds, every encoding has ENCODING_LEN values
         unsafe {
             cpp!([self as "const dlib::matrix<double,0,1>*", other as "const dlib::matrix<double,0,1>*"] -> f64 as "double" {
                 return dlib::length(*self - *other);
@@ -80,6 +120,35 @@ impl PartialEq for FaceEncoding {
     }
 }
 
+impl TryFrom<&[f64]> for FaceEncoding {
+    type Error = String;
+
+    fn try_from(values: &[f64]) -> Result<Self, Self::Error> {
+        Self::try_from_slice(values)
+    }
+}
+
+impl TryFrom<Vec<f64>> for FaceEncoding {
+    type Error = String;
+
+    fn try_from(values: Vec<f64>) -> Result<Self, Self::Error> {
+        Self::try_from_slice(&values)
+    }
+}
+
+impl Serialize for FaceEncoding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for FaceEncoding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = Vec::<f64>::deserialize(deserializer)?;
+        Self::try_from_slice(&values).map_err(D::Error::custom)
+    }
+}
+
 #[test]
 fn encoding_test() {
     let encoding_a = FaceEncoding::new_from_scalar(0.0);
@@ -90,3 +159,22 @@ fn encoding_test() {
 
     assert_eq!(encoding_a.distance(&encoding_b), 128.0_f64.sqrt());
 }
+
+#[test]
+fn test_encoding_from_values() {
+    let values: Vec<f64> = (0..128).map(|i| i as f64 / 128.0).collect();
+    let encoding = FaceEncoding::try_from(values.clone()).unwrap();
+
+    assert_eq!(&encoding[..], &values[..]);
+    assert!(FaceEncoding::try_from_slice(&[]).is_err());
+    assert!(FaceEncoding::try_from(&values[..3]).is_err());
+
+    let json = serde_json::to_string(&encoding).unwrap();
+    assert_eq!(
+        serde_json::from_str::<FaceEncoding>(&json).unwrap(),
+        encoding
+    );
+
+    let error = serde_json::from_str::<FaceEncoding>("[0.1, 0.2, 0.3]").unwrap_err();
+    assert!(error.to_string().contains("got 3"), "{}", error);
+}
@@ -1,5 +1,10 @@
+use std::fmt;
+use std::iter::FromIterator;
 use std::ops::Deref;
 use std::slice;
+use std::vec;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use super::encoding::FaceEncoding;
 
@@ -8,6 +13,23 @@ cpp_class!(
     pub unsafe struct FaceEncodings as "std::vector<dlib::matrix<double,0,1>>"
 );
 
+impl FaceEncodings {
+    /// Create an empty list of encodings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, encoding: FaceEncoding) {
+        let encoding = &encoding;
+
+        unsafe {
+            cpp!([self as "std::vector<dlib::matrix<double,0,1>>*", encoding as "const dlib::matrix<double,0,1>*"] {
+                self->push_back(*encoding);
+            })
+        }
+    }
+}
+
 impl Deref for FaceEncodings {
     type Target = [FaceEncoding];
 
@@ -32,6 +54,78 @@ impl Deref for FaceEncodings {
     }
 }
 
+impl fmt::Debug for FaceEncodings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.deref().fmt(f)
+    }
+}
+
+impl PartialEq for FaceEncodings {
+    fn eq(&self, other: &Self) -> bool {
+        self.deref().eq(other.deref())
+    }
+}
+
+impl From<Vec<FaceEncoding>> for FaceEncodings {
+    fn from(encodings: Vec<FaceEncoding>) -> Self {
+        encodings.into_iter().collect()
+    }
+}
+
+impl From<FaceEncodings> for Vec<FaceEncoding> {
+    fn from(encodings: FaceEncodings) -> Self {
+        encodings.to_vec()
+    }
+}
+
+impl FromIterator<FaceEncoding> for FaceEncodings {
+    fn from_iter<I: IntoIterator<Item = FaceEncoding>>(iter: I) -> Self {
+        let mut encodings = Self::new();
+        encodings.extend(iter);
+        encodings
+    }
+}
+
+impl Extend<FaceEncoding> for FaceEncodings {
+    fn extend<I: IntoIterator<Item = FaceEncoding>>(&mut self, iter: I) {
+        for encoding in iter {
+            self.push(encoding);
+        }
+    }
+}
+
+impl IntoIterator for FaceEncodings {
+    type Item = FaceEncoding;
+    type IntoIter = vec::IntoIter<FaceEncoding>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let encodings: Vec<FaceEncoding> = self.to_vec();
+        encodings.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a FaceEncodings {
+    type Item = &'a FaceEncoding;
+    type IntoIter = slice::Iter<'a, FaceEncoding>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Serialized as a sequence of encodings.
+impl Serialize for FaceEncodings {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for FaceEncodings {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<FaceEncoding>::deserialize(deserializer).map(Self::from)
+    }
+}
+
 #[test]
 fn test_default_encoding() {
     let encodings = FaceEncodings::default();
@@ -47,3 +141,26 @@ fn test_sizes() {
 
     assert_eq!(size_of::<FaceEncodings>(), size_of::<Vec<FaceEncoding>>());
 }
+
+#[test]
+fn test_rust_encodings() {
+    let a = FaceEncoding::new_from_scalar(0.25);
+    let b = FaceEncoding::new_from_scalar(-1.0);
+
+    let mut encodings = FaceEncodings::new();
+    encodings.push(a.clone());
+    encodings.extend(vec![b.clone()]);
+    assert_eq!(&encodings[..], &[a.clone(), b.clone()]);
+
+    let collected: FaceEncodings = vec![a.clone(), b.clone()].into_iter().collect();
+    assert_eq!(collected, encodings);
+
+    let cloned = encodings.clone();
+    assert_eq!(Vec::from(cloned), vec![a, b]);
+
+    let json = serde_json::to_string(&collected).unwrap();
+    assert_eq!(
+        serde_json::from_str::<FaceEncodings>(&json).unwrap(),
+        collected
+    );
+}
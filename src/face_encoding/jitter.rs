@@ -0,0 +1,94 @@
+/// Parameters of the random crops generated when encoding a face with `num_jitters > 1`.
+///
+/// Every jittered crop is the face chip randomly translated, scaled, rotated and possibly mirrored,
+/// and the encodings of all crops are averaged. The defaults are the values used by `dlib::jitter_image`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C)]
+pub struct JitterOptions {
+    /// Seed of the random number generator. The generator is reseeded for every face,
+    /// so the same options always produce the same crops.
+    pub seed: u64,
+    /// Maximum rotation of a crop, in degrees, in either direction.
+    pub max_rotation_degrees: f64,
+    /// The face height relative to the crop is drawn from `min_object_height..max_object_height`.
+    pub min_object_height: f64,
+    pub max_object_height: f64,
+    /// Maximum translation of a crop, as a fraction of the face size, in each direction.
+    pub translate_amount: f64,
+    /// Whether to mirror half of the crops.
+    pub randomly_flip: bool,
+}
+
+impl Default for JitterOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            max_rotation_degrees: 3.0,
+            min_object_height: 0.97,
+            max_object_height: 0.99999,
+            translate_amount: 0.02,
+            randomly_flip: true,
+        }
+    }
+}
+
+impl JitterOptions {
+    /// Check that the ranges are well formed.
+    pub fn validate(&self) -> Result<(), String> {
+        let finite = [
+            self.max_rotation_degrees,
+            self.min_object_height,
+            self.max_object_height,
+            self.translate_amount,
+        ]
+        .iter()
+        .all(|value| value.is_finite());
+
+        if !finite {
+            Err("Jitter options must be finite".into())
+        } else if self.max_rotation_degrees < 0.0 || self.translate_amount < 0.0 {
+            Err("Jitter rotation and translation must not be negative".into())
+        } else if self.min_object_height <= 0.0 || self.min_object_height > self.max_object_height {
+            Err(format!(
+                "Invalid jitter object height range {}..{}",
+                self.min_object_height, self.max_object_height
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn test_jitter_options() {
+    assert!(JitterOptions::default().validate().is_ok());
+
+    let no_jitter = JitterOptions {
+        max_rotation_degrees: 0.0,
+        min_object_height: 1.0,
+        max_object_height: 1.0,
+        translate_amount: 0.0,
+        randomly_flip: false,
+        ..Default::default()
+    };
+    assert!(no_jitter.validate().is_ok());
+
+    let inverted = JitterOptions {
+        min_object_height: 1.0,
+        max_object_height: 0.9,
+        ..Default::default()
+    };
+    assert!(inverted.validate().is_err());
+
+    let negative = JitterOptions {
+        translate_amount: -0.1,
+        ..Default::default()
+    };
+    assert!(negative.validate().is_err());
+
+    let nan = JitterOptions {
+        max_rotation_degrees: f64::NAN,
+        ..Default::default()
+    };
+    assert!(nan.validate().is_err());
+}
@@ -4,10 +4,12 @@ mod base;
 mod compare;
 mod encoding;
 mod encodings;
+mod jitter;
 mod nn;
 
 pub use self::base::FaceEncoderTrait;
 pub use self::compare::FaceComparer;
-pub use self::encoding::FaceEncoding;
+pub use self::encoding::{FaceEncoding, ENCODING_LEN};
 pub use self::encodings::FaceEncodings;
+pub use self::jitter::JitterOptions;
 pub use self::nn::FaceEncoderNetwork;
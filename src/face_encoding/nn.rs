@@ -1,15 +1,33 @@
+use std::cell::Cell;
+use std::marker::PhantomData;
 use std::path::Path;
 
-use super::base::FaceEncoderTrait;
+use super::base::{check_alignable, FaceEncoderTrait};
 use super::encodings::FaceEncodings;
+use super::jitter::JitterOptions;
 use crate::base::path_as_cstring;
 use crate::landmark_prediction::FaceLandmarks;
 use crate::matrix::ImageMatrix;
+use crate::models::ModelInfo;
 
 /// A face encoding network.
+///
+/// # Thread safety
+///
+/// The network is `Send` but not `Sync`: dlib networks store intermediate outputs in their layers while running,
+/// so a single instance can't be used from two threads at once. Give each thread its own clone,
+/// or share instances through a [`crate::ModelPool`].
+///
+/// ```compile_fail
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<fofscreen::FaceEncoderNetwork>();
+/// ```
 #[derive(Clone)]
 pub struct FaceEncoderNetwork {
     inner: FaceEncoderNetworkInner,
+    jitter: JitterOptions,
+    model: ModelInfo,
+    _not_sync: PhantomData<Cell<()>>,
 }
 
 cpp_class!(unsafe struct FaceEncoderNetworkInner as "face_encoding_nn");
@@ -41,43 +59,83 @@ impl FaceEncoderNetwork {
                 filename.as_ref().display()
             ))
         } else {
-            Ok(Self { inner })
+            Ok(Self {
+                inner,
+                jitter: JitterOptions::default(),
+                model: ModelInfo::for_file(filename.as_ref()),
+                _not_sync: PhantomData,
+            })
         }
     }
-}
 
-#[cfg(feature = "embed-fe-nn")]
-impl Default for FaceEncoderNetwork {
-    fn default() -> Self {
-        Self::new(crate::embed::path_for_file(
-            "dlib_face_recognition_resnet_model_v1.dat",
-        ))
-        .unwrap()
+    /// The model of the network, to tie stored encodings to it.
+    pub fn model_info(&self) -> &ModelInfo {
+        &self.model
     }
-}
 
-impl FaceEncoderTrait for FaceEncoderNetwork {
-    fn get_face_encodings(
+    /// Replace the model guessed from the file name, e.g. with the verified model of a [`crate::models::ResolvedModel`].
+    pub fn set_model_info(&mut self, model: ModelInfo) {
+        self.model = model;
+    }
+
+    /// Set the seed of the random number generator used to jitter faces when `num_jitters > 1`.
+    ///
+    /// The generator is reseeded for every face, so jittered encodings are reproducible
+    /// across calls, batches and threads.
+    pub fn set_jitter_seed(&mut self, seed: u64) {
+        self.jitter.seed = seed;
+    }
+
+    pub fn jitter_seed(&self) -> u64 {
+        self.jitter.seed
+    }
+
+    /// Set how jittered crops are generated when `num_jitters > 1`.
+    pub fn set_jitter_options(&mut self, options: JitterOptions) -> Result<(), String> {
+        options.validate()?;
+        self.jitter = options;
+        Ok(())
+    }
+
+    pub fn jitter_options(&self) -> &JitterOptions {
+        &self.jitter
+    }
+
+    /// Like [`FaceEncoderTrait::get_face_encodings`], but jitter with `options` instead of the network's own options.
+    pub fn get_face_encodings_with_jitter(
         &self,
         image: &ImageMatrix,
         landmarks: &[FaceLandmarks],
         num_jitters: u32,
-    ) -> FaceEncodings {
+        options: &JitterOptions,
+    ) -> Result<FaceEncodings, String> {
+        options.validate()?;
+
+        self.encode(image, landmarks, num_jitters, options)
+    }
+
+    fn encode(
+        &self,
+        image: &ImageMatrix,
+        landmarks: &[FaceLandmarks],
+        num_jitters: u32,
+        jitter: &JitterOptions,
+    ) -> Result<FaceEncodings, String> {
+        check_alignable(landmarks.iter())?;
+
         let num_faces = landmarks.len();
         let landmarks = landmarks.as_ptr();
         let net = &self.inner;
 
-        unsafe {
+        let encodings = unsafe {
             cpp!([
                     net as "face_encoding_nn*",
                     image as "dlib::matrix<dlib::rgb_pixel>*",
                     landmarks as "dlib::full_object_detection*",
                     num_faces as "size_t",
-                    num_jitters as "uint"
+                    num_jitters as "uint",
+                    jitter as "const jitter_options*"
                 ] -> FaceEncodings as "std::vector<dlib::matrix<double,0,1>>" {
-                std::vector<dlib::matrix<double,0,1>> encodings;
-                encodings.reserve(num_faces);
-
                 // first we need to use the landmarks to get image chips for each face
 
                 std::vector<dlib::chip_details> dets;
@@ -90,23 +148,74 @@ impl FaceEncoderTrait for FaceEncoderNetwork {
                 }
                 dlib::extract_image_chips(*image, dets, face_chips);
 
-                // extract descriptors and convert from float vectors to double vectors
+                return encode_face_chips(*net, face_chips, num_jitters, *jitter);
+            })
+        };
+
+        Ok(encodings)
+    }
+}
+
+#[cfg(feature = "embed-fe-nn")]
+impl Default for FaceEncoderNetwork {
+    fn default() -> Self {
+        Self::new(crate::embed::path_for_model(
+            crate::models::Model::FaceEncoder,
+        ))
+        .unwrap()
+    }
+}
 
-                if (num_jitters <= 1) {
-                    auto network_output = (*net)(face_chips, 16);
-                    for (auto& float_encoding : network_output) {
-                        encodings.push_back((dlib::matrix_cast<double>(float_encoding)));
-                    }
-                } else {
-                    for (auto& chip : face_chips) {
-                        auto network_output = (*net)(jitter_image(chip, num_jitters), 16);
-                        dlib::matrix<float,0,1> float_encoding = dlib::mean(dlib::mat(network_output));
+impl FaceEncoderTrait for FaceEncoderNetwork {
+    fn get_face_encodings(
+        &self,
+        image: &ImageMatrix,
+        landmarks: &[FaceLandmarks],
+        num_jitters: u32,
+    ) -> FaceEncodings {
+        self.encode(image, landmarks, num_jitters, &self.jitter)
+            .unwrap_or_else(|why| panic!("{}", why))
+    }
 
-                        encodings.push_back(dlib::matrix_cast<double>(float_encoding));
-                    }
+    fn get_face_encodings_batch(
+        &self,
+        faces: &[(&ImageMatrix, &FaceLandmarks)],
+        num_jitters: u32,
+    ) -> FaceEncodings {
+        if let Err(why) = check_alignable(faces.iter().map(|(_, landmarks)| *landmarks)) {
+            panic!("{}", why);
+        }
+
+        let num_faces = faces.len();
+        let images: Vec<&ImageMatrix> = faces.iter().map(|(image, _)| *image).collect();
+        let landmarks: Vec<&FaceLandmarks> =
+            faces.iter().map(|(_, landmarks)| *landmarks).collect();
+
+        let images = images.as_ptr();
+        let landmarks = landmarks.as_ptr();
+        let net = &self.inner;
+        let jitter = &self.jitter;
+
+        unsafe {
+            cpp!([
+                    net as "face_encoding_nn*",
+                    images as "const dlib::matrix<dlib::rgb_pixel>* const*",
+                    landmarks as "const dlib::full_object_detection* const*",
+                    num_faces as "size_t",
+                    num_jitters as "uint",
+                    jitter as "const jitter_options*"
+                ] -> FaceEncodings as "std::vector<dlib::matrix<double,0,1>>" {
+                // chips from every image go through the network together, in batches of 16
+
+                dlib::array<dlib::matrix<dlib::rgb_pixel>> face_chips;
+                face_chips.resize(num_faces);
+
+                for (size_t offset = 0; offset < num_faces; offset++) {
+                    dlib::chip_details details = dlib::get_face_chip_details(*landmarks[offset], 150, 0.25);
+                    dlib::extract_image_chip(*images[offset], details, face_chips[offset]);
                 }
 
-                return encodings;
+                return encode_face_chips(*net, face_chips, num_jitters, *jitter);
             })
         }
     }
@@ -0,0 +1,459 @@
+//! A persistent gallery of known faces.
+//!
+//! The gallery keeps the encodings of every enrolled photo, grouped by identity, in a JSON file,
+//! so faces only have to be detected and encoded once:
+//!
+//! ```text
+//! {"version":1,"encoder":{"name":"dlib_face_recognition_resnet_model","version":"1"},"identities":[{"name":"alice","samples":[{"encoding":[..],"source":"alice.jpg","enrolled":"2021-07-04T09:30:00.250Z"}]}]}
+//! ```
+//!
+//! Encodings of different encoder models can't be compared, so the gallery records the encoder that produced them,
+//! see [`Gallery::check_encoder`].
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::enrollment::{EnrollmentReport, NO_FACE_FOUND};
+use crate::events::format_rfc3339;
+use crate::face_encoding::FaceEncoding;
+use crate::models::ModelInfo;
+use crate::pipeline::{nearest_match, Match};
+
+/// The version of the gallery file format written by [`Gallery::save`].
+pub const GALLERY_VERSION: u32 = 1;
+
+/// One enrolled photo of an identity.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    pub encoding: FaceEncoding,
+    /// The file the face was enrolled from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// When the face was enrolled, as an RFC 3339 timestamp.
+    pub enrolled: String,
+}
+
+impl Sample {
+    pub fn new(encoding: FaceEncoding, source: Option<String>, enrolled: SystemTime) -> Self {
+        Self {
+            encoding,
+            source,
+            enrolled: format_rfc3339(enrolled),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Identity {
+    pub name: String,
+    pub samples: Vec<Sample>,
+}
+
+/// What happened to an image given to [`Gallery::enroll`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Diagnosis {
+    Enrolled,
+    NoFace,
+    /// The image has several faces, so it's unclear which one to enroll.
+    MultipleFaces(usize),
+    /// The face isn't of acceptable quality, e.g. too small, blurry, badly lit or turned away, with the issues found.
+    LowQuality(String),
+    /// The image couldn't be read or decoded.
+    Unreadable(String),
+}
+
+impl Diagnosis {
+    pub fn is_enrolled(&self) -> bool {
+        *self == Diagnosis::Enrolled
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diagnosis::Enrolled => write!(f, "enrolled"),
+            Diagnosis::NoFace => write!(f, "no face found"),
+            Diagnosis::MultipleFaces(faces) => write!(f, "{} faces found, expected one", faces),
+            Diagnosis::LowQuality(why) => write!(f, "low quality: {}", why),
+            Diagnosis::Unreadable(why) => write!(f, "unreadable: {}", why),
+        }
+    }
+}
+
+/// Known faces, grouped by identity.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Gallery {
+    version: u32,
+    /// The encoder of the samples, unknown for galleries written before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encoder: Option<ModelInfo>,
+    identities: Vec<Identity>,
+}
+
+impl Default for Gallery {
+    fn default() -> Self {
+        Self {
+            version: GALLERY_VERSION,
+            encoder: None,
+            identities: Vec::new(),
+        }
+    }
+}
+
+impl Gallery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a gallery file, or start an empty gallery if it doesn't exist yet.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+
+        Self::load(path)
+    }
+
+    /// Load a gallery file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = fs::read(path)
+            .map_err(|why| format!("Can't read gallery {}: {}", path.display(), why))?;
+        let gallery: Self = serde_json::from_slice(&file)
+            .map_err(|why| format!("Invalid gallery {}: {}", path.display(), why))?;
+
+        if gallery.version > GALLERY_VERSION {
+            return Err(format!(
+                "Gallery {} has version {}, only versions up to {} are supported",
+                path.display(),
+                gallery.version,
+                GALLERY_VERSION
+            ));
+        }
+
+        Ok(gallery)
+    }
+
+    /// Write the gallery to `path`, replacing it only once the new file is complete.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+
+        fs::write(&temporary, json)
+            .and_then(|_| fs::rename(&temporary, path))
+            .map_err(|why| format!("Can't write gallery {}: {}", path.display(), why))
+    }
+
+    /// The encoder model that produced the samples, if it is known.
+    pub fn encoder(&self) -> Option<&ModelInfo> {
+        self.encoder.as_ref()
+    }
+
+    /// Check that the samples were produced by `encoder`, and record it if the gallery doesn't know its encoder yet.
+    ///
+    /// Call this before comparing encodings of `encoder` with the gallery or adding them to it.
+    pub fn check_encoder(&mut self, encoder: &ModelInfo) -> Result<(), String> {
+        match &self.encoder {
+            Some(known) if !known.is_compatible(encoder) => Err(format!(
+                "Gallery was enrolled with {}, not {}. Re-enroll the identities with the current encoder",
+                known, encoder
+            )),
+            Some(known) if known.sha256.is_some() => Ok(()),
+            _ => {
+                self.encoder = Some(encoder.clone());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn identities(&self) -> &[Identity] {
+        &self.identities
+    }
+
+    pub fn identity(&self, name: &str) -> Option<&Identity> {
+        self.identities
+            .iter()
+            .find(|identity| identity.name == name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.identities.is_empty()
+    }
+
+    /// The number of samples of every identity.
+    pub fn num_samples(&self) -> usize {
+        self.identities
+            .iter()
+            .map(|identity| identity.samples.len())
+            .sum()
+    }
+
+    /// Add a sample of `name`, creating the identity if needed.
+    pub fn add(&mut self, name: &str, sample: Sample) {
+        match self
+            .identities
+            .iter_mut()
+            .find(|identity| identity.name == name)
+        {
+            Some(identity) => identity.samples.push(sample),
+            None => self.identities.push(Identity {
+                name: name.to_string(),
+                samples: vec![sample],
+            }),
+        }
+    }
+
+    /// Add the faces of an [`EnrollmentReport`] as samples of `name`, returning what happened to every image.
+    ///
+    /// Images with several faces and faces that aren't of [acceptable](crate::quality::Quality::acceptable)
+    /// quality aren't added.
+    pub fn enroll(&mut self, name: &str, report: EnrollmentReport) -> Vec<(PathBuf, Diagnosis)> {
+        let mut diagnoses = Vec::new();
+
+        for enrolled in report.enrolled {
+            let diagnosis = if enrolled.faces_found > 1 {
+                Diagnosis::MultipleFaces(enrolled.faces_found)
+            } else if !enrolled.quality.acceptable {
+                Diagnosis::LowQuality(enrolled.quality.reasons())
+            } else {
+                let source = enrolled
+                    .path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned());
+                self.add(
+                    name,
+                    Sample::new(enrolled.encoding, source, SystemTime::now()),
+                );
+                Diagnosis::Enrolled
+            };
+
+            diagnoses.push((enrolled.path, diagnosis));
+        }
+
+        for (path, error) in report.errors {
+            let diagnosis = if error == NO_FACE_FOUND {
+                Diagnosis::NoFace
+            } else {
+                Diagnosis::Unreadable(error)
+            };
+            diagnoses.push((path, diagnosis));
+        }
+
+        diagnoses
+    }
+
+    /// Remove an identity and all its samples.
+    pub fn remove(&mut self, name: &str) -> Result<Identity, String> {
+        let index = self
+            .identities
+            .iter()
+            .position(|identity| identity.name == name)
+            .ok_or_else(|| format!("No identity named {}", name))?;
+
+        Ok(self.identities.remove(index))
+    }
+
+    /// Rename an identity. Fails if `to` is already taken, merge galleries to join two identities instead.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), String> {
+        if from != to && self.identity(to).is_some() {
+            return Err(format!("There already is an identity named {}", to));
+        }
+
+        let identity = self
+            .identities
+            .iter_mut()
+            .find(|identity| identity.name == from)
+            .ok_or_else(|| format!("No identity named {}", from))?;
+        identity.name = to.to_string();
+
+        Ok(())
+    }
+
+    /// A gallery with only the identities in `names`, e.g. to export them.
+    pub fn subset(&self, names: &[&str]) -> Result<Self, String> {
+        let identities = names
+            .iter()
+            .map(|name| {
+                self.identity(name)
+                    .cloned()
+                    .ok_or_else(|| format!("No identity named {}", name))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            version: GALLERY_VERSION,
+            encoder: self.encoder.clone(),
+            identities,
+        })
+    }
+
+    /// Add the samples of another gallery, skipping samples already present. Returns the number of samples added.
+    ///
+    /// Fails if the galleries were enrolled with different encoders.
+    pub fn merge(&mut self, other: Gallery) -> Result<usize, String> {
+        if let Some(encoder) = &other.encoder {
+            self.check_encoder(encoder)?;
+        }
+
+        let mut added = 0;
+
+        for identity in other.identities {
+            for sample in identity.samples {
+                let known = self.identity(&identity.name).is_some_and(|known| {
+                    known.samples.iter().any(|s| s.encoding == sample.encoding)
+                });
+
+                if !known {
+                    self.add(&identity.name, sample);
+                    added += 1;
+                }
+            }
+        }
+
+        Ok(added)
+    }
+
+    /// Every sample as an `(identity, encoding)` pair, e.g. to match against with [`nearest_match`].
+    pub fn references(&self) -> Vec<(String, FaceEncoding)> {
+        self.identities
+            .iter()
+            .flat_map(|identity| {
+                identity
+                    .samples
+                    .iter()
+                    .map(move |sample| (identity.name.clone(), sample.encoding.clone()))
+            })
+            .collect()
+    }
+
+    /// The identity nearest to `encoding`, if it is at most `threshold` away.
+    pub fn identify(&self, encoding: &FaceEncoding, threshold: f64) -> Option<Match> {
+        nearest_match(&self.references(), encoding, threshold)
+    }
+}
+
+#[test]
+fn test_gallery() {
+    use crate::enrollment::Enrolled;
+    use crate::geometry::Rectangle;
+    use crate::models::Model;
+    use crate::quality::QualityScorer;
+
+    let enrolled = |path: &str, size: i64, faces_found: usize, value: f64| Enrolled {
+        identity: String::new(),
+        path: PathBuf::from(path),
+        rect: Rectangle::from_xywh(0, 0, size, size),
+        faces_found,
+        encoding: FaceEncoding::new_from_scalar(value),
+        quality: QualityScorer::default().rate(size as f64, 400.0, 120.0, 50.0, None),
+    };
+
+    let mut gallery = Gallery::new();
+    let report = EnrollmentReport {
+        enrolled: vec![
+            enrolled("faces/alice.jpg", 120, 1, 0.0),
+            enrolled("faces/group.jpg", 120, 3, 0.1),
+            enrolled("faces/tiny.jpg", 20, 1, 0.2),
+        ],
+        errors: vec![
+            (PathBuf::from("faces/wall.jpg"), NO_FACE_FOUND.to_string()),
+            (PathBuf::from("faces/notes.txt"), "unsupported".to_string()),
+        ],
+    };
+
+    let diagnoses = gallery.enroll("alice", report);
+    let diagnoses: Vec<&Diagnosis> = diagnoses.iter().map(|(_, d)| d).collect();
+    assert_eq!(diagnoses[0], &Diagnosis::Enrolled);
+    assert_eq!(diagnoses[1], &Diagnosis::MultipleFaces(3));
+    assert_eq!(
+        diagnoses[2].to_string(),
+        "low quality: face is 20px, at least 80px is needed"
+    );
+    assert_eq!(diagnoses[3], &Diagnosis::NoFace);
+    assert_eq!(
+        diagnoses[4].to_string(),
+        "unreadable: unsupported".to_string()
+    );
+
+    assert_eq!(gallery.num_samples(), 1);
+    assert_eq!(
+        gallery.identity("alice").unwrap().samples[0]
+            .source
+            .as_deref(),
+        Some("alice.jpg")
+    );
+
+    let bob = Sample::new(FaceEncoding::new_from_scalar(1.0), None, SystemTime::now());
+    gallery.add("bob", bob.clone());
+    let probe = FaceEncoding::new_from_scalar(0.01);
+    assert_eq!(gallery.identify(&probe, 0.6).unwrap().identity, "alice");
+    assert_eq!(
+        gallery.identify(&FaceEncoding::new_from_scalar(0.5), 0.6),
+        None
+    );
+
+    assert!(gallery.rename("alice", "bob").is_err());
+    gallery.rename("alice", "carol").unwrap();
+    assert!(gallery.identity("alice").is_none());
+    assert!(gallery.rename("alice", "dave").is_err());
+
+    // merging skips the samples already known
+    let exported = gallery.subset(&["bob"]).unwrap();
+    assert_eq!(exported.identities().len(), 1);
+    assert!(gallery.subset(&["eve"]).is_err());
+    assert_eq!(gallery.merge(exported.clone()), Ok(0));
+    let mut other = Gallery::new();
+    assert_eq!(other.merge(exported), Ok(1));
+
+    // encodings of another encoder can't be mixed in
+    let encoder = ModelInfo::new(Model::FaceEncoder);
+    gallery.check_encoder(&encoder).unwrap();
+    assert_eq!(gallery.encoder(), Some(&encoder));
+    let retrained = ModelInfo {
+        version: "2".to_string(),
+        ..encoder.clone()
+    };
+    assert!(gallery.check_encoder(&retrained).is_err());
+    let mut other = Gallery::new();
+    other.check_encoder(&retrained).unwrap();
+    assert!(gallery.merge(other).unwrap_err().contains("v2"));
+
+    assert_eq!(gallery.remove("bob").unwrap().samples, vec![bob]);
+    assert!(gallery.remove("bob").is_err());
+
+    let dir = std::env::temp_dir().join(format!("fofscreen-gallery-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("gallery.json");
+
+    assert!(Gallery::open(&path).unwrap().is_empty());
+    assert!(Gallery::load(&path).is_err());
+    gallery.save(&path).unwrap();
+    assert_eq!(Gallery::open(&path).unwrap(), gallery);
+
+    // galleries written before the encoder was recorded still load
+    fs::write(&path, r#"{"version":1,"identities":[]}"#).unwrap();
+    assert_eq!(Gallery::load(&path).unwrap().encoder(), None);
+
+    fs::write(&path, r#"{"version":99,"identities":[]}"#).unwrap();
+    assert!(Gallery::load(&path).unwrap_err().contains("version 99"));
+
+    // encodings that can't be compared with the encoder's are refused while loading
+    let short = r#"{"version":1,"identities":[{"name":"mallory","samples":[
+        {"encoding":[0.1,0.2,0.3],"enrolled":"2021-07-04T09:30:00.250Z"}]}]}"#;
+    fs::write(&path, short).unwrap();
+    let error = Gallery::load(&path).unwrap_err();
+    assert!(error.starts_with("Invalid gallery"), "{}", error);
+    assert!(error.contains("128"), "{}", error);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,8 +1,10 @@
-use std::ops::Deref;
+use std::ops::{Add, AddAssign, Deref, Sub, SubAssign};
 
-#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[repr(C)]
-/// A 2D Point.
+/// A 2D Point, serialized as `[x, y]`.
 pub struct Point([i64; 2]);
 
 impl AsRef<[i64]> for Point {
@@ -31,11 +33,69 @@ impl Point {
     pub fn y(&self) -> i64 {
         self.0[1]
     }
+
+    /// The euclidean distance to another point.
+    pub fn distance(&self, other: &Point) -> f64 {
+        let dx = (self.x() - other.x()) as f64;
+        let dy = (self.y() - other.y()) as f64;
+
+        dx.hypot(dy)
+    }
+
+    /// The mean of a set of points, such as the landmarks of an eye, or `None` if there are none.
+    pub fn centroid(points: &[Point]) -> Option<[f64; 2]> {
+        if points.is_empty() {
+            return None;
+        }
+
+        let (x, y) = points.iter().fold((0.0, 0.0), |(x, y), point| {
+            (x + point.x() as f64, y + point.y() as f64)
+        });
+        let len = points.len() as f64;
+
+        Some([x / len, y / len])
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+
+    fn add(self, other: Point) -> Point {
+        Point::new(self.x() + other.x(), self.y() + other.y())
+    }
 }
 
-#[derive(Copy, Clone, Default, Debug, PartialEq)]
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x() - other.x(), self.y() - other.y())
+    }
+}
+
+impl AddAssign for Point {
+    fn add_assign(&mut self, other: Point) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Point {
+    fn sub_assign(&mut self, other: Point) {
+        *self = *self - other;
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[repr(C)]
 /// A Rectangle.
+///
+/// Like `dlib::rectangle`, the rectangle includes the `right` column and the `bottom` row,
+/// so a rectangle with `right < left` or `bottom < top` is empty.
+///
+/// This changes the meaning of existing values: [`Rectangle::width`] and [`Rectangle::height`] count both edges,
+/// `Rectangle { left: 0, top: 0, right: 10, bottom: 10 }` is 11 pixels wide instead of 10,
+/// and the default rectangle is empty instead of the single pixel at the origin.
+/// Use [`Rectangle::from_xywh`] to build a rectangle from its size.
 pub struct Rectangle {
     pub left: i64,
     pub top: i64,
@@ -43,13 +103,27 @@ pub struct Rectangle {
     pub bottom: i64,
 }
 
+impl Default for Rectangle {
+    /// An empty rectangle, like `dlib::rectangle()`.
+    fn default() -> Self {
+        Self {
+            left: 0,
+            top: 0,
+            right: -1,
+            bottom: -1,
+        }
+    }
+}
+
 impl Rectangle {
+    /// The number of columns, `right - left + 1`, negative or zero for empty rectangles.
     pub fn width(&self) -> i64 {
-        self.right - self.left
+        self.right - self.left + 1
     }
 
+    /// The number of rows, `bottom - top + 1`, negative or zero for empty rectangles.
     pub fn height(&self) -> i64 {
-        self.bottom - self.top
+        self.bottom - self.top + 1
     }
 
     pub fn size(&self) -> Point {
@@ -67,6 +141,111 @@ impl Rectangle {
     pub fn center(&self) -> [f64; 2] {
         [self.center_x(), self.center_y()]
     }
+
+    /// Create a rectangle from its top left corner and its size.
+    pub fn from_xywh(x: i64, y: i64, width: i64, height: i64) -> Self {
+        Self {
+            left: x,
+            top: y,
+            right: x + width - 1,
+            bottom: y + height - 1,
+        }
+    }
+
+    /// The top left corner and the size of the rectangle.
+    pub fn to_xywh(&self) -> (i64, i64, i64, i64) {
+        (self.left, self.top, self.width(), self.height())
+    }
+
+    /// Whether the rectangle covers no area.
+    pub fn is_empty(&self) -> bool {
+        self.width() <= 0 || self.height() <= 0
+    }
+
+    /// The area of the rectangle, 0 if it is empty.
+    pub fn area(&self) -> i64 {
+        if self.is_empty() {
+            0
+        } else {
+            self.width() * self.height()
+        }
+    }
+
+    pub fn contains(&self, point: &Point) -> bool {
+        point.x() >= self.left
+            && point.x() <= self.right
+            && point.y() >= self.top
+            && point.y() <= self.bottom
+    }
+
+    /// The overlap of two rectangles, which is empty if they don't overlap.
+    pub fn intersect(&self, other: &Rectangle) -> Rectangle {
+        Rectangle {
+            left: self.left.max(other.left),
+            top: self.top.max(other.top),
+            right: self.right.min(other.right),
+            bottom: self.bottom.min(other.bottom),
+        }
+    }
+
+    /// The smallest rectangle containing both rectangles.
+    pub fn union(&self, other: &Rectangle) -> Rectangle {
+        if self.is_empty() {
+            return *other;
+        } else if other.is_empty() {
+            return *self;
+        }
+
+        Rectangle {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+
+    /// The intersection over union of two rectangles, from 0 when they don't overlap to 1 when they are equal.
+    pub fn iou(&self, other: &Rectangle) -> f64 {
+        let intersection = self.intersect(other).area();
+        let union = self.area() + other.area() - intersection;
+
+        if union == 0 {
+            0.0
+        } else {
+            intersection as f64 / union as f64
+        }
+    }
+
+    /// Move the rectangle by `dx` and `dy`.
+    pub fn translate(&self, dx: i64, dy: i64) -> Rectangle {
+        Rectangle {
+            left: self.left + dx,
+            top: self.top + dy,
+            right: self.right + dx,
+            bottom: self.bottom + dy,
+        }
+    }
+
+    /// Grow or shrink the rectangle by `factor` while keeping its center,
+    /// e.g. to crop a face with some margin.
+    pub fn scale_about_center(&self, factor: f64) -> Rectangle {
+        let [x, y] = self.center();
+        // the distance from the center to the center of the outermost pixels
+        let half_width = (self.width() as f64 * factor - 1.0) / 2.0;
+        let half_height = (self.height() as f64 * factor - 1.0) / 2.0;
+
+        Rectangle {
+            left: (x - half_width).round() as i64,
+            top: (y - half_height).round() as i64,
+            right: (x + half_width).round() as i64,
+            bottom: (y + half_height).round() as i64,
+        }
+    }
+
+    /// Clip the rectangle to an image of `width` by `height` pixels, which is empty if it lies outside of the image.
+    pub fn clamp_to_image(&self, width: usize, height: usize) -> Rectangle {
+        self.intersect(&Rectangle::from_xywh(0, 0, width as i64, height as i64))
+    }
 }
 
 #[cfg(test)]
@@ -100,4 +279,72 @@ mod tests {
     dbg!(&point);
     // assert_eq!(point, Point { x: 42, y: -1000 });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_point_arithmetic() {
+        use super::*;
+
+        let a = Point::new(1, 2);
+        let b = Point::new(4, 6);
+
+        assert_eq!(a + b, Point::new(5, 8));
+        assert_eq!(b - a, Point::new(3, 4));
+        assert_eq!(a.distance(&b), 5.0);
+
+        let mut c = a;
+        c += b;
+        c -= Point::new(1, 1);
+        assert_eq!(c, Point::new(4, 7));
+
+        assert_eq!(Point::centroid(&[]), None);
+        assert_eq!(Point::centroid(&[a, b]), Some([2.5, 4.0]));
+    }
+
+    #[test]
+    fn test_rectangle_geometry() {
+        use super::*;
+
+        let a = Rectangle::from_xywh(0, 0, 10, 10);
+        let b = Rectangle::from_xywh(5, 5, 10, 10);
+        let far = Rectangle::from_xywh(100, 100, 10, 10);
+
+        assert_eq!(a.to_xywh(), (0, 0, 10, 10));
+        assert_eq!(a.area(), 100);
+        assert!(!a.is_empty());
+
+        assert_eq!(a.right, 9);
+        assert!(a.contains(&Point::new(0, 9)));
+        assert!(a.contains(&Point::new(9, 9)));
+        assert!(!a.contains(&Point::new(10, 5)));
+        assert!(Rectangle::default().is_empty());
+
+        assert_eq!(a.intersect(&b), Rectangle::from_xywh(5, 5, 5, 5));
+        assert!(a.intersect(&far).is_empty());
+        assert_eq!(a.intersect(&far).area(), 0);
+
+        assert_eq!(a.union(&b), Rectangle::from_xywh(0, 0, 15, 15));
+        assert_eq!(a.union(&Rectangle::default()), a);
+
+        assert_eq!(a.iou(&a), 1.0);
+        assert_eq!(a.iou(&far), 0.0);
+        assert!((a.iou(&b) - 25.0 / 175.0).abs() < 1e-12);
+        assert_eq!(Rectangle::default().iou(&Rectangle::default()), 0.0);
+
+        assert_eq!(a.translate(3, -2), Rectangle::from_xywh(3, -2, 10, 10));
+        assert_eq!(
+            a.scale_about_center(2.0),
+            Rectangle::from_xywh(-5, -5, 20, 20)
+        );
+        assert_eq!(a.scale_about_center(0.5), Rectangle::from_xywh(3, 3, 5, 5));
+
+        assert_eq!(
+            Rectangle::from_xywh(-5, 5, 20, 20).clamp_to_image(12, 8),
+            Rectangle::from_xywh(0, 5, 12, 3)
+        );
+        let clamped = Rectangle::from_xywh(5, 5, 20, 20).clamp_to_image(12, 8);
+        assert_eq!((clamped.right, clamped.bottom), (11, 7));
+        assert!(Rectangle::from_xywh(20, 0, 5, 5)
+            .clamp_to_image(12, 8)
+            .is_empty());
+    }
+}
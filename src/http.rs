@@ -0,0 +1,71 @@
+//! The HTTP server behind [`crate::metrics::MetricsServer`] and [`crate::service::RecognitionServer`].
+
+use std::io::Cursor;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use tiny_http::{Request, Response, Server};
+
+/// Answers requests one at a time on a background thread.
+///
+/// The server stops when dropped.
+pub(crate) struct HttpServer {
+    server: Arc<Server>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HttpServer {
+    /// Listen on `addr`, e.g. `127.0.0.1:9898`, and answer every request with `handle`.
+    /// Port 0 picks a free port, see [`HttpServer::addr`].
+    pub fn start<F>(addr: &str, mut handle: F) -> Result<Self, String>
+    where
+        F: FnMut(&mut Request) -> Response<Cursor<Vec<u8>>> + Send + 'static,
+    {
+        let server = Arc::new(Server::http(addr).map_err(|why| why.to_string())?);
+
+        let thread = {
+            let server = server.clone();
+
+            thread::spawn(move || {
+                for mut request in server.incoming_requests() {
+                    let response = handle(&mut request);
+
+                    // the client hanging up early isn't our problem
+                    let _ = request.respond(response);
+                }
+            })
+        };
+
+        Ok(Self {
+            server,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.server.server_addr()
+    }
+
+    /// Serve until the process is killed.
+    pub fn join(mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The path of a request's URL, without the query.
+pub(crate) fn path(request: &Request) -> &str {
+    request.url().split('?').next().unwrap_or("")
+}
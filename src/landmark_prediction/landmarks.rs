@@ -1,14 +1,66 @@
+use std::fmt;
+use std::iter::FromIterator;
 use std::ops::Deref;
 use std::slice;
+use std::vec;
 
-use crate::geometry::Point;
+use serde::de::Deserializer;
+use serde::ser::{SerializeStruct, Serializer};
+use serde::{Deserialize, Serialize};
+
+use crate::geometry::{Point, Rectangle};
 
 cpp_class!(
     /// A wrapper around the dlib `full_object_detection` class, which internally has a `std::vector<point>`.
     /// https://github.com/davisking/dlib/blob/master/dlib/image_processing/full_object_detection.h#L21
+    ///
+    /// Landmarks can also be built in rust, e.g. from stored annotations, and passed to
+    /// [`crate::FaceEncoderTrait::get_face_encodings`]. They serialize as `{"rect": .., "parts": [[x, y], ..]}`.
     pub unsafe struct FaceLandmarks as "dlib::full_object_detection"
 );
 
+impl FaceLandmarks {
+    /// Create landmarks of the face in `rect` from their points.
+    ///
+    /// The encoder aligns faces with the points alone, `rect` is kept for reference.
+    pub fn new(rect: Rectangle, parts: &[Point]) -> Self {
+        let rect = &rect;
+        let len = parts.len();
+        let parts = parts.as_ptr();
+
+        unsafe {
+            cpp!([rect as "const dlib::rectangle*", parts as "const dlib::point*", len as "size_t"] -> FaceLandmarks as "dlib::full_object_detection" {
+                return dlib::full_object_detection(*rect, std::vector<dlib::point>(parts, parts + len));
+            })
+        }
+    }
+
+    /// The face rectangle the landmarks were predicted from.
+    pub fn rect(&self) -> Rectangle {
+        unsafe {
+            cpp!([self as "const dlib::full_object_detection*"] -> Rectangle as "dlib::rectangle" {
+                return self->get_rect();
+            })
+        }
+    }
+
+    /// Whether the face can be aligned to encode it or cut out a face chip,
+    /// which dlib only knows how to do for the 68 and 5 point landmark models.
+    pub fn is_alignable(&self) -> bool {
+        self.len() == 68 || self.len() == 5
+    }
+
+    /// Add a point.
+    ///
+    /// `full_object_detection` can't grow in place, so this copies the points.
+    pub fn push(&mut self, point: Point) {
+        let mut parts = self.to_vec();
+        parts.push(point);
+
+        *self = Self::new(self.rect(), &parts);
+    }
+}
+
 impl Deref for FaceLandmarks {
     type Target = [Point];
 
@@ -34,6 +86,99 @@ impl Deref for FaceLandmarks {
     }
 }
 
+impl fmt::Debug for FaceLandmarks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FaceLandmarks")
+            .field("rect", &self.rect())
+            .field("parts", &self.deref())
+            .finish()
+    }
+}
+
+impl PartialEq for FaceLandmarks {
+    fn eq(&self, other: &Self) -> bool {
+        self.rect() == other.rect() && self.deref().eq(other.deref())
+    }
+}
+
+/// The rectangle of landmarks built from points alone is the bounding box of the points.
+impl From<&[Point]> for FaceLandmarks {
+    fn from(parts: &[Point]) -> Self {
+        let rect = match parts.split_first() {
+            Some((first, rest)) => rest.iter().fold(
+                Rectangle {
+                    left: first.x(),
+                    top: first.y(),
+                    right: first.x(),
+                    bottom: first.y(),
+                },
+                |rect, point| Rectangle {
+                    left: rect.left.min(point.x()),
+                    top: rect.top.min(point.y()),
+                    right: rect.right.max(point.x()),
+                    bottom: rect.bottom.max(point.y()),
+                },
+            ),
+            None => Rectangle::default(),
+        };
+
+        Self::new(rect, parts)
+    }
+}
+
+impl From<Vec<Point>> for FaceLandmarks {
+    fn from(parts: Vec<Point>) -> Self {
+        Self::from(&parts[..])
+    }
+}
+
+impl FromIterator<Point> for FaceLandmarks {
+    fn from_iter<I: IntoIterator<Item = Point>>(iter: I) -> Self {
+        Self::from(iter.into_iter().collect::<Vec<Point>>())
+    }
+}
+
+impl IntoIterator for FaceLandmarks {
+    type Item = Point;
+    type IntoIter = vec::IntoIter<Point>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let parts: Vec<Point> = self.to_vec();
+        parts.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a FaceLandmarks {
+    type Item = &'a Point;
+    type IntoIter = slice::Iter<'a, Point>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Serialize for FaceLandmarks {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("FaceLandmarks", 2)?;
+        state.serialize_field("rect", &self.rect())?;
+        state.serialize_field("parts", self.deref())?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for FaceLandmarks {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Landmarks {
+            rect: Rectangle,
+            parts: Vec<Point>,
+        }
+
+        let landmarks = Landmarks::deserialize(deserializer)?;
+        Ok(Self::new(landmarks.rect, &landmarks.parts))
+    }
+}
+
 #[test]
 fn test_default_landmarks() {
     // ensure that FaceLandmarks::default() doesnt allow memory violations in safe code
@@ -43,3 +188,38 @@ fn test_default_landmarks() {
     assert_eq!(landmarks.len(), 0);
     assert_eq!(landmarks.get(0), None);
 }
+
+#[test]
+fn test_rust_landmarks() {
+    let points = vec![Point::new(10, 20), Point::new(30, 5), Point::new(15, 40)];
+
+    let mut landmarks: FaceLandmarks = points.iter().copied().collect();
+    assert_eq!(&landmarks[..], &points[..]);
+    assert!(!landmarks.is_alignable());
+    assert!(FaceLandmarks::from(vec![Point::new(1, 1); 5]).is_alignable());
+    assert_eq!(
+        landmarks.rect(),
+        Rectangle {
+            left: 10,
+            top: 5,
+            right: 30,
+            bottom: 40,
+        }
+    );
+
+    let cloned = landmarks.clone();
+    landmarks.push(Point::new(0, 0));
+    assert_eq!(landmarks.len(), 4);
+    assert_eq!(landmarks.rect(), cloned.rect());
+    assert_eq!(cloned.into_iter().collect::<Vec<_>>(), points);
+
+    let json = serde_json::to_string(&landmarks).unwrap();
+    assert_eq!(
+        json,
+        r#"{"rect":{"left":10,"top":5,"right":30,"bottom":40},"parts":[[10,20],[30,5],[15,40],[0,0]]}"#
+    );
+    assert_eq!(
+        serde_json::from_str::<FaceLandmarks>(&json).unwrap(),
+        landmarks
+    );
+}
@@ -7,6 +7,11 @@ use crate::geometry::Rectangle;
 use crate::matrix::ImageMatrix;
 
 /// A face landmark predictor.
+///
+/// # Thread safety
+///
+/// The predictor is `Send` and `Sync`, dlib's `shape_predictor` does not modify itself while predicting,
+/// so one instance can be shared between threads.
 #[derive(Clone)]
 pub struct LandmarkPredictor {
     inner: LandmarkPredictorInner,
@@ -48,8 +53,8 @@ impl LandmarkPredictor {
 #[cfg(feature = "embed-lp")]
 impl Default for LandmarkPredictor {
     fn default() -> Self {
-        Self::new(crate::embed::path_for_file(
-            "shape_predictor_68_face_landmarks.dat",
+        Self::new(crate::embed::path_for_model(
+            crate::models::Model::LandmarkPredictor,
         ))
         .unwrap()
     }
@@ -12,6 +12,13 @@
 //! These encodings consist of 128 floating point numbers that represent the face in 128-dimensional space.
 //! To determine if two face encodings belong to the same face, the euclideon distance between them can be used.
 //! For the dlib encodings, a distance of 0.6 is generally appropriate.
+//!
+//! # Thread safety
+//!
+//! Images, rectangles, landmarks and encodings are plain data and are `Send` and `Sync`.
+//! The landmark predictor only reads its model and is `Send` and `Sync` too, but the face detectors
+//! and the encoding network modify internal buffers while running and are only `Send`.
+//! Clone them for each thread, or share them through a [`ModelPool`].
 // Ignore the `forget_copy` clippy lint to remove noise from `cargo clippy` output
 
 #![recursion_limit = "1024"]
@@ -21,23 +28,41 @@ extern crate cpp;
 
 mod wrapper;
 
+pub mod attributes;
 mod base;
+pub mod drawing;
 mod embed;
+pub mod enrollment;
+pub mod evaluation;
+pub mod events;
 pub mod face_detection;
 pub mod face_encoding;
+pub mod gallery;
 mod geometry;
+mod http;
 pub mod landmark_prediction;
+pub mod liveness;
 pub mod matrix;
+pub mod metrics;
+pub mod models;
+pub mod pipeline;
+mod pool;
+pub mod quality;
+pub mod service;
 
 
 pub mod capture;
 
 
 pub use self::geometry::{Point, Rectangle};
-pub use self::matrix::ImageMatrix;
+pub use self::matrix::{ImageMatrix, PixelFormat};
+pub use self::pool::{ModelPool, PooledModel};
 
-pub use self::face_detection::{FaceDetector, FaceDetectorCnn, FaceDetectorTrait, FaceLocations};
+pub use self::face_detection::{
+    EnsembleDetector, FaceDetection, FaceDetector, FaceDetectorCnn, FaceDetectorTrait,
+    FaceLocations, MmodDetection, MmodDetector, ScaledDetector,
+};
 pub use self::face_encoding::{
-    FaceComparer, FaceEncoderNetwork, FaceEncoderTrait, FaceEncoding, FaceEncodings,
+    FaceComparer, FaceEncoderNetwork, FaceEncoderTrait, FaceEncoding, FaceEncodings, JitterOptions,
 };
 pub use self::landmark_prediction::{LandmarkPredictor, LandmarkPredictorTrait};
@@ -0,0 +1,341 @@
+//! Presentation attack detection: telling a live face from a photo or a screen held up to the camera.
+//!
+//! A [`SpoofDetector`] looks at the aligned chip of a face for three signs of a replay:
+//!
+//! - moiré: the pixel grid of a screen, filmed by the pixel grid of the camera, adds periodic patterns,
+//!   which show up as a few strong peaks in the high frequencies of the chip's spectrum,
+//! - specular highlights: the glossy surface of a phone or a print reflects light in large white patches,
+//! - texture: recaptured faces lose the fine texture of skin, so their local binary patterns (LBP)
+//!   are dominated by a few flat patterns.
+//!
+//! Each sign is scored from 0 to 1, and the [`SpoofAnalysis::score`] of a face is its strongest sign.
+//! The scores are heuristics calibrated on the chips of the face encoder (150x150 pixels), not a trained model.
+
+use std::sync::{Arc, Mutex};
+
+use image::RgbImage;
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use crate::quality::luma;
+
+/// The number of LBP histogram bins: one per uniform pattern, plus one for all the others.
+pub const LBP_BINS: usize = 59;
+
+/// The signs of a replay found in a face chip, each from 0 (none) to 1 (certain).
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpoofAnalysis {
+    /// How much of the high frequency energy is concentrated in a few peaks.
+    pub moire: f64,
+    /// How much of the chip is covered by specular highlights.
+    pub specular: f64,
+    /// How poor the chip's texture is.
+    pub texture: f64,
+    /// The strongest sign.
+    pub score: f64,
+    /// Whether the score reaches the detector's threshold.
+    pub is_spoof: bool,
+}
+
+impl SpoofAnalysis {
+    /// How likely the face is live, from 0 to 1, as logged in a [`Sighting`](crate::events::Sighting).
+    pub fn liveness(&self) -> f64 {
+        1.0 - self.score
+    }
+}
+
+/// Scores face chips for signs of a replay.
+///
+/// The detector keeps the FFT plans of the chip sizes it has seen, and its clones share them.
+#[derive(Clone)]
+pub struct SpoofDetector {
+    threshold: f64,
+    planner: Arc<Mutex<FftPlanner<f64>>>,
+}
+
+impl Default for SpoofDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpoofDetector {
+    /// A detector flagging faces scoring 0.5 or more.
+    pub fn new() -> Self {
+        Self {
+            threshold: 0.5,
+            planner: Arc::new(Mutex::new(FftPlanner::new())),
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Analyse the aligned chip of a face, e.g. from [`ImageMatrix::face_chip`](crate::ImageMatrix::face_chip).
+    pub fn analyse(&self, chip: &RgbImage) -> SpoofAnalysis {
+        let (width, height) = (chip.width() as usize, chip.height() as usize);
+        let gray: Vec<f64> = chip.pixels().map(luma).collect();
+
+        let moire = ramp(self.peak_concentration(&gray, width, height), 0.1, 0.4);
+        let specular = ramp(specular_fraction(chip), 0.02, 0.1);
+        let texture = ramp(
+            1.0 - histogram_entropy(&lbp_histogram(&gray, width, height)),
+            0.4,
+            0.6,
+        );
+        let score = moire.max(specular).max(texture);
+
+        SpoofAnalysis {
+            moire,
+            specular,
+            texture,
+            score,
+            is_spoof: score >= self.threshold,
+        }
+    }
+
+    /// The share of the high frequency energy held by the strongest 1% of the frequencies,
+    /// in the centered square of the image.
+    ///
+    /// Noise and natural texture spread their energy, so this is about 0.05, a periodic pattern puts
+    /// all of its energy in a few frequencies.
+    fn peak_concentration(&self, gray: &[f64], width: usize, height: usize) -> f64 {
+        let n = width.min(height);
+        if n < 16 {
+            return 0.0;
+        }
+
+        let fft: Arc<dyn Fft<f64>> = self.planner.lock().unwrap().plan_fft_forward(n);
+        let (left, top) = ((width - n) / 2, (height - n) / 2);
+        let mean = gray.iter().sum::<f64>() / gray.len() as f64;
+
+        // a Hann window keeps the borders of the chip from leaking into every frequency
+        let hann: Vec<f64> = (0..n)
+            .map(|i| 0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / n as f64).cos())
+            .collect();
+        let mut rows: Vec<Complex<f64>> = (0..n * n)
+            .map(|i| {
+                let (x, y) = (i % n, i / n);
+                let value = gray[(top + y) * width + left + x] - mean;
+                Complex::new(value * hann[x] * hann[y], 0.0)
+            })
+            .collect();
+        fft.process(&mut rows);
+
+        let mut columns: Vec<Complex<f64>> =
+            (0..n * n).map(|i| rows[(i % n) * n + i / n]).collect();
+        fft.process(&mut columns);
+
+        let frequency = |k: usize| {
+            let k = if k < n / 2 {
+                k as f64
+            } else {
+                k as f64 - n as f64
+            };
+            k / n as f64
+        };
+        let mut band: Vec<f64> = columns
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| {
+                let radius = frequency(i % n).hypot(frequency(i / n));
+                (0.15..=0.45).contains(&radius)
+            })
+            .map(|(_, value)| value.norm_sqr())
+            .collect();
+
+        let total: f64 = band.iter().sum();
+        if total == 0.0 {
+            return 0.0;
+        }
+
+        band.sort_by(|a, b| b.total_cmp(a));
+        let peaks = (band.len() / 100).max(1);
+
+        band[..peaks].iter().sum::<f64>() / total
+    }
+}
+
+/// A linear ramp from 0 at `low` to 1 at `high`.
+pub(crate) fn ramp(value: f64, low: f64, high: f64) -> f64 {
+    ((value - low) / (high - low)).clamp(0.0, 1.0)
+}
+
+/// The share of the pixels that are nearly white: bright, and without colour.
+fn specular_fraction(chip: &RgbImage) -> f64 {
+    let pixels = (chip.width() * chip.height()) as f64;
+    if pixels == 0.0 {
+        return 0.0;
+    }
+
+    let highlights = chip
+        .pixels()
+        .filter(|pixel| {
+            let max = *pixel.0.iter().max().unwrap();
+            let min = *pixel.0.iter().min().unwrap();
+            min >= 220 && max - min <= 30
+        })
+        .count();
+
+    highlights as f64 / pixels
+}
+
+/// The normalised histogram of the uniform local binary patterns of a `width` by `height` gray image.
+///
+/// Every pixel with all its neighbours is coded by which of its 8 neighbours are at least as bright.
+/// Codes with at most two changes between dark and bright around the circle (uniform patterns, such as edges,
+/// corners and flat areas) get a bin each, the others share the last bin.
+pub fn lbp_histogram(gray: &[f64], width: usize, height: usize) -> [f64; LBP_BINS] {
+    let mut bins = [0usize; 256];
+    let mut histogram = [0.0; LBP_BINS];
+    if width < 3 || height < 3 {
+        return histogram;
+    }
+
+    const NEIGHBOURS: [(isize, isize); 8] = [
+        (-1, -1),
+        (0, -1),
+        (1, -1),
+        (1, 0),
+        (1, 1),
+        (0, 1),
+        (-1, 1),
+        (-1, 0),
+    ];
+
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let center = gray[y * width + x];
+            let code = NEIGHBOURS
+                .iter()
+                .enumerate()
+                .fold(0u8, |code, (bit, (dx, dy))| {
+                    let neighbour =
+                        gray[(y as isize + dy) as usize * width + (x as isize + dx) as usize];
+                    code | (((neighbour >= center) as u8) << bit)
+                });
+            bins[code as usize] += 1;
+        }
+    }
+
+    let mut uniform = 0;
+    for (code, count) in bins.iter().enumerate() {
+        let code = code as u8;
+        let bin = if (code ^ code.rotate_left(1)).count_ones() <= 2 {
+            uniform += 1;
+            uniform - 1
+        } else {
+            LBP_BINS - 1
+        };
+        histogram[bin] += *count as f64;
+    }
+
+    let total = ((width - 2) * (height - 2)) as f64;
+    for bin in histogram.iter_mut() {
+        *bin /= total;
+    }
+
+    histogram
+}
+
+/// The chi-square distance between two normalised histograms, 0 for equal histograms.
+pub fn chi_square(a: &[f64], b: &[f64]) -> f64 {
+    a.iter()
+        .zip(b)
+        .filter(|(a, b)| *a + *b > 0.0)
+        .map(|(a, b)| (a - b).powi(2) / (a + b))
+        .sum()
+}
+
+/// The entropy of a normalised histogram, relative to a uniform one: 0 if everything falls in one bin, 1 if
+/// it's spread evenly.
+fn histogram_entropy(histogram: &[f64]) -> f64 {
+    let entropy: f64 = histogram
+        .iter()
+        .filter(|p| **p > 0.0)
+        .map(|p| -p * p.ln())
+        .sum();
+
+    entropy / (histogram.len() as f64).ln()
+}
+
+#[cfg(test)]
+fn synthetic_face(size: u32) -> RgbImage {
+    // deterministic noise over a shaded oval, standing in for skin texture
+    let mut seed = 12345u32;
+    RgbImage::from_fn(size, size, |x, y| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        let noise = (seed >> 16) % 40;
+        let (dx, dy) = (x as f64 / size as f64 - 0.5, y as f64 / size as f64 - 0.5);
+        let shade = 170.0 - 120.0 * (dx * dx + dy * dy);
+        let value = (shade + noise as f64 - 20.0) as u8;
+        image::Rgb([
+            value,
+            (value as f64 * 0.8) as u8,
+            (value as f64 * 0.7) as u8,
+        ])
+    })
+}
+
+#[test]
+fn test_lbp_histogram() {
+    let flat = vec![100.0; 25];
+    let histogram = lbp_histogram(&flat, 5, 5);
+    // every neighbour is as bright as the center, all 9 pixels get the same uniform code
+    assert_eq!(histogram.iter().filter(|bin| **bin > 0.0).count(), 1);
+    assert_eq!(histogram.iter().sum::<f64>(), 1.0);
+    assert_eq!(histogram_entropy(&histogram), 0.0);
+
+    let uniform = [1.0 / LBP_BINS as f64; LBP_BINS];
+    assert!((histogram_entropy(&uniform) - 1.0).abs() < 1e-9);
+    assert_eq!(chi_square(&uniform, &uniform), 0.0);
+    assert!(chi_square(&histogram, &uniform) > 0.5);
+
+    assert_eq!(lbp_histogram(&flat, 2, 2), [0.0; LBP_BINS]);
+}
+
+#[test]
+fn test_spoof_detection() {
+    let detector = SpoofDetector::new();
+
+    let live = detector.analyse(&synthetic_face(150));
+    assert!(live.score < 0.5, "{:?}", live);
+    assert!(!live.is_spoof);
+    assert!(live.liveness() > 0.5);
+
+    // a screen: the same face with a fine grating over it
+    let mut screen = synthetic_face(150);
+    for (x, y, pixel) in screen.enumerate_pixels_mut() {
+        let grating = 40.0 * (2.0 * std::f64::consts::PI * (0.3 * x as f64 + 0.1 * y as f64)).sin();
+        for channel in pixel.0.iter_mut() {
+            *channel = (*channel as f64 + grating).clamp(0.0, 255.0) as u8;
+        }
+    }
+    let analysis = detector.analyse(&screen);
+    assert_eq!(analysis.moire, 1.0, "{:?}", analysis);
+    assert!(analysis.is_spoof);
+
+    // a glossy photo: a large reflection across the face
+    let mut glossy = synthetic_face(150);
+    for (x, y, pixel) in glossy.enumerate_pixels_mut() {
+        if (40..90).contains(&x) && (20..70).contains(&y) {
+            *pixel = image::Rgb([250, 250, 245]);
+        }
+    }
+    let analysis = detector.analyse(&glossy);
+    assert_eq!(analysis.specular, 1.0, "{:?}", analysis);
+    assert!(analysis.is_spoof);
+
+    // a blurry print: smooth shading without any texture
+    let print = RgbImage::from_fn(150, 150, |x, _| image::Rgb([100 + (x / 10) as u8, 90, 80]));
+    let analysis = detector.analyse(&print);
+    assert_eq!(analysis.texture, 1.0, "{:?}", analysis);
+    assert!(analysis.is_spoof);
+
+    // chips too small for the spectrum are still scored
+    let tiny = detector.analyse(&RgbImage::new(4, 4));
+    assert_eq!(tiny.moire, 0.0);
+    assert_eq!(tiny.texture, 1.0);
+}
@@ -1,12 +1,61 @@
-use std::ops::Deref;
+use std::fmt;
+use std::ops::{Deref, Index, IndexMut};
+use std::slice;
 
-use image::{ImageBuffer, Rgb};
+use image::{Bgr, Bgra, DynamicImage, ImageBuffer, Luma, Rgb, RgbImage, Rgba};
+
+use crate::geometry::Rectangle;
+use crate::landmark_prediction::FaceLandmarks;
 
 cpp_class!(
     /// A wrapper around a `matrix<rgb_pixel>`, dlibs own image class.
     pub unsafe struct ImageMatrix as "dlib::matrix<dlib::rgb_pixel>"
 );
 
+/// The layout of a raw pixel buffer passed to [`ImageMatrix::from_raw`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum PixelFormat {
+    /// Interleaved 8-bit channels (r, g, b, r, g, b).
+    Rgb = 0,
+    /// Interleaved 8-bit channels (r, g, b, a), the alpha channel is ignored.
+    Rgba = 1,
+    /// Interleaved 8-bit channels (b, g, r), as used by OpenCV.
+    Bgr = 2,
+    /// Interleaved 8-bit channels (b, g, r, a), the alpha channel is ignored.
+    Bgra = 3,
+    /// A single 8-bit grayscale channel.
+    Luma = 4,
+    /// Packed 4:2:2 YUV (y0, u, y1, v), two pixels share their chroma. The native format of most webcams.
+    Yuyv = 5,
+    /// Planar 4:2:0 YUV: a Y plane followed by a half height plane of interleaved (u, v) pairs, both with the same stride.
+    Nv12 = 6,
+}
+
+impl PixelFormat {
+    /// The smallest row stride, in bytes, of an image `width` pixels wide.
+    pub fn min_stride(self, width: usize) -> Option<usize> {
+        match self {
+            PixelFormat::Rgb | PixelFormat::Bgr => width.checked_mul(3),
+            PixelFormat::Rgba | PixelFormat::Bgra => width.checked_mul(4),
+            PixelFormat::Luma => Some(width),
+            PixelFormat::Yuyv => (width.checked_add(1)? / 2).checked_mul(4),
+            PixelFormat::Nv12 => (width.checked_add(1)? / 2).checked_mul(2),
+        }
+    }
+
+    /// The number of bytes read from a buffer of `height` rows of `stride` bytes.
+    pub fn buffer_len(self, height: usize, stride: usize) -> Option<usize> {
+        match self {
+            PixelFormat::Nv12 => {
+                let chroma_rows = height.checked_add(1)? / 2;
+                height.checked_add(chroma_rows)?.checked_mul(stride)
+            }
+            _ => height.checked_mul(stride),
+        }
+    }
+}
+
 impl ImageMatrix {
     /// # Safety
     ///
@@ -34,6 +83,76 @@ impl ImageMatrix {
         })
     }
 
+    /// Copy a matrix from a raw buffer of `height` rows, each `stride` bytes apart.
+    ///
+    /// The pixels are converted to rgb while they're copied, so frames go to dlib with a single copy
+    /// instead of going through an [`RgbImage`] first. The matrix can't borrow `data`: dlib matrices own
+    /// their pixels, and dlib only reads `rgb_pixel` matrices, so construction is never zero-copy.
+    ///
+    /// Fails if the stride is too small for the width, or the buffer too small for the image.
+    pub fn from_raw(
+        width: usize,
+        height: usize,
+        stride: usize,
+        data: &[u8],
+        format: PixelFormat,
+    ) -> Result<Self, String> {
+        let min_stride = format
+            .min_stride(width)
+            .ok_or_else(|| format!("Image width {} is too large", width))?;
+
+        if stride < min_stride {
+            return Err(format!(
+                "Stride {} is smaller than the {} bytes of a {} pixel wide {:?} row",
+                stride, min_stride, width, format
+            ));
+        }
+
+        let len = format
+            .buffer_len(height, stride)
+            .ok_or_else(|| format!("Image height {} is too large", height))?;
+
+        if data.len() < len {
+            return Err(format!(
+                "Buffer of {} bytes is smaller than the {} bytes of a {}x{} {:?} image",
+                data.len(),
+                len,
+                width,
+                height,
+                format
+            ));
+        }
+
+        let ptr = data.as_ptr();
+        let format = format as u32;
+
+        Ok(unsafe {
+            cpp!([ptr as "const uint8_t*", width as "size_t", height as "size_t", stride as "size_t", format as "uint32_t"] -> ImageMatrix as "dlib::matrix<dlib::rgb_pixel>" {
+                return matrix_from_raw(ptr, width, height, stride, format);
+            })
+        })
+    }
+
+    /// Copy a matrix from packed YUYV (YUV 4:2:2) data, e.g. a raw webcam frame.
+    pub fn from_yuyv(
+        width: usize,
+        height: usize,
+        stride: usize,
+        data: &[u8],
+    ) -> Result<Self, String> {
+        Self::from_raw(width, height, stride, data, PixelFormat::Yuyv)
+    }
+
+    /// Copy a matrix from NV12 (YUV 4:2:0) data.
+    pub fn from_nv12(
+        width: usize,
+        height: usize,
+        stride: usize,
+        data: &[u8],
+    ) -> Result<Self, String> {
+        Self::from_raw(width, height, stride, data, PixelFormat::Nv12)
+    }
+
     /// Copy a matrix from an rgb image
     pub fn from_image<C: Deref<Target = [u8]>>(image: &ImageBuffer<Rgb<u8>, C>) -> Self {
         let width = image.width() as usize;
@@ -42,6 +161,179 @@ impl ImageMatrix {
 
         unsafe { Self::new(width, height, ptr) }
     }
+
+    /// Copy a matrix from an rgba image, dropping the alpha channel.
+    pub fn from_rgba_image<C: Deref<Target = [u8]>>(image: &ImageBuffer<Rgba<u8>, C>) -> Self {
+        Self::from_buffer(image.width(), image.height(), image, PixelFormat::Rgba)
+    }
+
+    /// Copy a matrix from a bgr image.
+    pub fn from_bgr_image<C: Deref<Target = [u8]>>(image: &ImageBuffer<Bgr<u8>, C>) -> Self {
+        Self::from_buffer(image.width(), image.height(), image, PixelFormat::Bgr)
+    }
+
+    /// Copy a matrix from a bgra image, dropping the alpha channel.
+    pub fn from_bgra_image<C: Deref<Target = [u8]>>(image: &ImageBuffer<Bgra<u8>, C>) -> Self {
+        Self::from_buffer(image.width(), image.height(), image, PixelFormat::Bgra)
+    }
+
+    /// Copy a matrix from a grayscale image, every channel gets the luma value.
+    pub fn from_luma_image<C: Deref<Target = [u8]>>(image: &ImageBuffer<Luma<u8>, C>) -> Self {
+        Self::from_buffer(image.width(), image.height(), image, PixelFormat::Luma)
+    }
+
+    /// Copy a matrix from any decoded image.
+    ///
+    /// 8-bit images are copied directly, other images are converted to 8-bit rgb first.
+    pub fn from_dynamic_image(image: &DynamicImage) -> Self {
+        match image {
+            DynamicImage::ImageRgb8(image) => Self::from_image(image),
+            DynamicImage::ImageRgba8(image) => Self::from_rgba_image(image),
+            DynamicImage::ImageBgr8(image) => Self::from_bgr_image(image),
+            DynamicImage::ImageBgra8(image) => Self::from_bgra_image(image),
+            DynamicImage::ImageLuma8(image) => Self::from_luma_image(image),
+            image => Self::from_image(&image.to_rgb8()),
+        }
+    }
+
+    // image buffers always hold at least width * height pixels, so this can't fail
+    fn from_buffer(width: u32, height: u32, data: &[u8], format: PixelFormat) -> Self {
+        let width = width as usize;
+        let stride = format.min_stride(width).unwrap();
+
+        Self::from_raw(width, height as usize, stride, data, format).unwrap()
+    }
+}
+
+impl ImageMatrix {
+    /// The number of columns.
+    pub fn width(&self) -> usize {
+        unsafe {
+            cpp!([self as "const dlib::matrix<dlib::rgb_pixel>*"] -> usize as "size_t" {
+                return self->nc();
+            })
+        }
+    }
+
+    /// The number of rows.
+    pub fn height(&self) -> usize {
+        unsafe {
+            cpp!([self as "const dlib::matrix<dlib::rgb_pixel>*"] -> usize as "size_t" {
+                return self->nr();
+            })
+        }
+    }
+
+    /// All pixels, row by row, backed by the dlib buffer.
+    ///
+    /// `dlib::rgb_pixel` and [`Rgb<u8>`] are both three packed bytes, red first.
+    pub fn pixels(&self) -> &[Rgb<u8>] {
+        let len = self.width() * self.height();
+
+        if len == 0 {
+            &[]
+        } else {
+            unsafe {
+                let pointer = cpp!([self as "const dlib::matrix<dlib::rgb_pixel>*"] -> *const Rgb<u8> as "const dlib::rgb_pixel*" {
+                    return &(*self)(0, 0);
+                });
+
+                slice::from_raw_parts(pointer, len)
+            }
+        }
+    }
+
+    /// All pixels, row by row, mutably.
+    pub fn pixels_mut(&mut self) -> &mut [Rgb<u8>] {
+        let len = self.width() * self.height();
+
+        if len == 0 {
+            &mut []
+        } else {
+            unsafe {
+                let pointer = cpp!([self as "dlib::matrix<dlib::rgb_pixel>*"] -> *mut Rgb<u8> as "dlib::rgb_pixel*" {
+                    return &(*self)(0, 0);
+                });
+
+                slice::from_raw_parts_mut(pointer, len)
+            }
+        }
+    }
+
+    /// The pixels of row `y`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is out of bounds.
+    pub fn row(&self, y: usize) -> &[Rgb<u8>] {
+        assert!(y < self.height(), "row {} out of bounds", y);
+
+        let width = self.width();
+        &self.pixels()[y * width..(y + 1) * width]
+    }
+
+    /// Iterate over the rows of the image.
+    pub fn rows(&self) -> impl Iterator<Item = &[Rgb<u8>]> {
+        // chunks panics on zero, an image without columns has no pixels anyway
+        self.pixels().chunks(self.width().max(1))
+    }
+
+    /// The pixel at column `x` and row `y`, if it is in bounds.
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<&Rgb<u8>> {
+        if x < self.width() && y < self.height() {
+            Some(&self.pixels()[y * self.width() + x])
+        } else {
+            None
+        }
+    }
+
+    /// Copy the matrix into an rgb image.
+    pub fn to_rgb_image(&self) -> RgbImage {
+        let data = self.pixels().iter().flat_map(|pixel| pixel.0).collect();
+
+        RgbImage::from_raw(self.width() as u32, self.height() as u32, data).unwrap()
+    }
+}
+
+/// Index pixels by `(x, y)`, like [`ImageBuffer`].
+impl Index<(usize, usize)> for ImageMatrix {
+    type Output = Rgb<u8>;
+
+    fn index(&self, (x, y): (usize, usize)) -> &Rgb<u8> {
+        self.get_pixel(x, y).unwrap_or_else(|| {
+            panic!(
+                "pixel ({}, {}) out of bounds of a {}x{} image",
+                x,
+                y,
+                self.width(),
+                self.height()
+            )
+        })
+    }
+}
+
+impl IndexMut<(usize, usize)> for ImageMatrix {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Rgb<u8> {
+        let (width, height) = (self.width(), self.height());
+
+        if x >= width || y >= height {
+            panic!(
+                "pixel ({}, {}) out of bounds of a {}x{} image",
+                x, y, width, height
+            );
+        }
+
+        &mut self.pixels_mut()[y * width + x]
+    }
+}
+
+impl fmt::Debug for ImageMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ImageMatrix")
+            .field("width", &self.width())
+            .field("height", &self.height())
+            .finish()
+    }
 }
 
 impl ImageMatrix {
@@ -54,4 +346,379 @@ impl ImageMatrix {
             })
         }
     }
+
+    /// Copy the part of the image inside `rect`, clipped to the image.
+    ///
+    /// Like dlib rectangles, the crop includes the `right` column and `bottom` row.
+    /// An empty matrix is returned if `rect` lies outside of the image.
+    pub fn crop(&self, rect: &Rectangle) -> Self {
+        unsafe {
+            cpp!([self as "const dlib::matrix<dlib::rgb_pixel>*", rect as "const dlib::rectangle*"] -> ImageMatrix as "dlib::matrix<dlib::rgb_pixel>" {
+                const dlib::rectangle area = rect->intersect(dlib::get_rect(*self));
+
+                if (area.is_empty()) {
+                    return dlib::matrix<dlib::rgb_pixel>();
+                }
+
+                return dlib::subm(*self, area);
+            })
+        }
+    }
+
+    /// Extract the face described by `landmarks` as a `size` x `size` chip, rotated and scaled so the eyes
+    /// are level, with `padding` around the face as a fraction of its size.
+    ///
+    /// The encoder sees faces as `face_chip(landmarks, 150, 0.25)`.
+    ///
+    /// Returns `None` if the landmarks can't be [aligned](FaceLandmarks::is_alignable), `size` is 0
+    /// or `padding` is negative or not finite.
+    pub fn face_chip(&self, landmarks: &FaceLandmarks, size: usize, padding: f64) -> Option<Self> {
+        if !landmarks.is_alignable() || size == 0 || !padding.is_finite() || padding < 0.0 {
+            return None;
+        }
+
+        let chip = unsafe {
+            cpp!([self as "const dlib::matrix<dlib::rgb_pixel>*", landmarks as "const dlib::full_object_detection*", size as "size_t", padding as "double"] -> ImageMatrix as "dlib::matrix<dlib::rgb_pixel>" {
+                dlib::matrix<dlib::rgb_pixel> chip;
+                dlib::extract_image_chip(*self, dlib::get_face_chip_details(*landmarks, size, padding), chip);
+                return chip;
+            })
+        };
+
+        Some(chip)
+    }
+
+    /// Rotate the image by `angle` radians, counter-clockwise, with bilinear interpolation.
+    ///
+    /// The output is large enough to hold the whole rotated image, uncovered corners are black.
+    pub fn rotate(&self, angle: f64) -> Self {
+        unsafe {
+            cpp!([self as "const dlib::matrix<dlib::rgb_pixel>*", angle as "double"] -> ImageMatrix as "dlib::matrix<dlib::rgb_pixel>" {
+                dlib::matrix<dlib::rgb_pixel> out;
+                dlib::rotate_image(*self, out, angle);
+                return out;
+            })
+        }
+    }
+
+    /// Rotate the image 90 degrees clockwise, e.g. for a camera mounted in portrait mode.
+    pub fn rotate90(&self) -> Self {
+        unsafe {
+            cpp!([self as "const dlib::matrix<dlib::rgb_pixel>*"] -> ImageMatrix as "dlib::matrix<dlib::rgb_pixel>" {
+                return dlib::fliplr(dlib::trans(*self));
+            })
+        }
+    }
+
+    /// Rotate the image 180 degrees.
+    pub fn rotate180(&self) -> Self {
+        unsafe {
+            cpp!([self as "const dlib::matrix<dlib::rgb_pixel>*"] -> ImageMatrix as "dlib::matrix<dlib::rgb_pixel>" {
+                return dlib::flipud(dlib::fliplr(*self));
+            })
+        }
+    }
+
+    /// Rotate the image 270 degrees clockwise.
+    pub fn rotate270(&self) -> Self {
+        unsafe {
+            cpp!([self as "const dlib::matrix<dlib::rgb_pixel>*"] -> ImageMatrix as "dlib::matrix<dlib::rgb_pixel>" {
+                return dlib::flipud(dlib::trans(*self));
+            })
+        }
+    }
+
+    /// Mirror the image left to right, e.g. to undo a mirrored webcam.
+    pub fn flip_horizontal(&self) -> Self {
+        unsafe {
+            cpp!([self as "const dlib::matrix<dlib::rgb_pixel>*"] -> ImageMatrix as "dlib::matrix<dlib::rgb_pixel>" {
+                return dlib::fliplr(*self);
+            })
+        }
+    }
+
+    /// Mirror the image top to bottom.
+    pub fn flip_vertical(&self) -> Self {
+        unsafe {
+            cpp!([self as "const dlib::matrix<dlib::rgb_pixel>*"] -> ImageMatrix as "dlib::matrix<dlib::rgb_pixel>" {
+                return dlib::flipud(*self);
+            })
+        }
+    }
+
+    /// Upsample the image to about twice its size with `dlib::pyramid_up`,
+    /// which lets the detectors find faces half as large.
+    pub fn pyramid_up(&self) -> Self {
+        unsafe {
+            cpp!([self as "const dlib::matrix<dlib::rgb_pixel>*"] -> ImageMatrix as "dlib::matrix<dlib::rgb_pixel>" {
+                dlib::matrix<dlib::rgb_pixel> out;
+                dlib::pyramid_up(*self, out);
+                return out;
+            })
+        }
+    }
+
+    /// Equalize the histogram of each channel, spreading out the intensities of dark or washed out images.
+    pub fn equalize_histogram(&self) -> Self {
+        unsafe {
+            cpp!([self as "const dlib::matrix<dlib::rgb_pixel>*"] -> ImageMatrix as "dlib::matrix<dlib::rgb_pixel>" {
+                dlib::matrix<dlib::rgb_pixel> out = *self;
+                dlib::matrix<unsigned char> channel(self->nr(), self->nc());
+
+                for (int c = 0; c < 3; c++) {
+                    for (long y = 0; y < self->nr(); y++) {
+                        for (long x = 0; x < self->nc(); x++) {
+                            const dlib::rgb_pixel& pixel = (*self)(y, x);
+                            channel(y, x) = c == 0 ? pixel.red : (c == 1 ? pixel.green : pixel.blue);
+                        }
+                    }
+
+                    dlib::equalize_histogram(channel);
+
+                    for (long y = 0; y < self->nr(); y++) {
+                        for (long x = 0; x < self->nc(); x++) {
+                            dlib::rgb_pixel& pixel = out(y, x);
+                            (c == 0 ? pixel.red : (c == 1 ? pixel.green : pixel.blue)) = channel(y, x);
+                        }
+                    }
+                }
+
+                return out;
+            })
+        }
+    }
+
+    /// Convert the image to grayscale, using dlib's rgb to gray conversion. Every channel gets the gray value.
+    pub fn to_grayscale(&self) -> Self {
+        unsafe {
+            cpp!([self as "const dlib::matrix<dlib::rgb_pixel>*"] -> ImageMatrix as "dlib::matrix<dlib::rgb_pixel>" {
+                dlib::matrix<unsigned char> gray;
+                dlib::assign_image(gray, *self);
+
+                dlib::matrix<dlib::rgb_pixel> out;
+                dlib::assign_image(out, gray);
+                return out;
+            })
+        }
+    }
+}
+
+#[test]
+fn test_raw_buffer_checks() {
+    let data = [0u8; 64];
+
+    // 4x4 rgb needs 48 bytes
+    assert!(ImageMatrix::from_raw(4, 4, 12, &data[..48], PixelFormat::Rgb).is_ok());
+    assert!(ImageMatrix::from_raw(4, 4, 12, &data[..47], PixelFormat::Rgb).is_err());
+    assert!(ImageMatrix::from_raw(4, 4, 11, &data, PixelFormat::Rgb).is_err());
+
+    // odd widths round up to a whole yuyv pair, and nv12 needs half as many chroma rows again
+    assert_eq!(PixelFormat::Yuyv.min_stride(3), Some(8));
+    assert_eq!(PixelFormat::Nv12.min_stride(3), Some(4));
+    assert_eq!(PixelFormat::Nv12.buffer_len(3, 4), Some(20));
+    assert!(ImageMatrix::from_nv12(4, 4, 4, &data[..24]).is_ok());
+    assert!(ImageMatrix::from_nv12(4, 4, 4, &data[..23]).is_err());
+
+    assert!(ImageMatrix::from_raw(usize::MAX, 1, 0, &data, PixelFormat::Bgra).is_err());
+    assert!(ImageMatrix::from_raw(1, usize::MAX, 1, &data, PixelFormat::Luma).is_err());
+}
+
+#[test]
+fn test_rgb_round_trip() {
+    let image = image::open("assets/obama.jpg").unwrap().to_rgb8();
+    let matrix = ImageMatrix::from_image(&image);
+
+    assert_eq!(matrix.width(), image.width() as usize);
+    assert_eq!(matrix.height(), image.height() as usize);
+    assert_eq!(matrix.rows().count(), matrix.height());
+    assert_eq!(matrix[(10, 20)], *image.get_pixel(10, 20));
+    assert_eq!(matrix.row(20)[10], *image.get_pixel(10, 20));
+    assert_eq!(matrix.get_pixel(matrix.width(), 0), None);
+    assert_eq!(matrix.to_rgb_image(), image);
+
+    let mut matrix = matrix;
+    matrix[(0, 0)] = Rgb([1, 2, 3]);
+    assert_eq!(matrix.pixels()[0], Rgb([1, 2, 3]));
+
+    let empty = ImageMatrix::default();
+    assert!(empty.pixels().is_empty());
+    assert_eq!(empty.rows().count(), 0);
+    assert_eq!(empty.to_rgb_image().len(), 0);
+}
+
+#[test]
+fn test_pixel_formats() {
+    let image = image::open("assets/obama.jpg").unwrap();
+    let rgb = ImageMatrix::from_image(&image.to_rgb8());
+
+    assert_eq!(
+        ImageMatrix::from_dynamic_image(&image).pixels(),
+        rgb.pixels()
+    );
+    assert_eq!(
+        ImageMatrix::from_rgba_image(&image.to_rgba8()).pixels(),
+        rgb.pixels()
+    );
+    assert_eq!(
+        ImageMatrix::from_bgr_image(&image.to_bgr8()).pixels(),
+        rgb.pixels()
+    );
+    assert_eq!(
+        ImageMatrix::from_bgra_image(&image.to_bgra8()).pixels(),
+        rgb.pixels()
+    );
+
+    let luma = image.to_luma8();
+    let gray = ImageMatrix::from_luma_image(&luma);
+    assert_eq!(gray[(5, 7)], Rgb([luma.get_pixel(5, 7)[0]; 3]));
+}
+
+#[test]
+fn test_raw_strides() {
+    const RED: Rgb<u8> = Rgb([200, 10, 20]);
+    const BLUE: Rgb<u8> = Rgb([20, 10, 200]);
+
+    // 2x2 images with 3 bytes of padding at the end of every row, which must be skipped
+    let rgb = [
+        200, 10, 20, 20, 10, 200, 9, 9, 9, 20, 10, 200, 200, 10, 20, 9, 9, 9,
+    ];
+    let matrix = ImageMatrix::from_raw(2, 2, 9, &rgb, PixelFormat::Rgb).unwrap();
+    assert_eq!(matrix.pixels(), &[RED, BLUE, BLUE, RED]);
+
+    let bgra = [
+        20, 10, 200, 0, 200, 10, 20, 0, 9, 9, 9, 200, 10, 20, 0, 20, 10, 200, 0, 9, 9, 9,
+    ];
+    let matrix = ImageMatrix::from_raw(2, 2, 11, &bgra, PixelFormat::Bgra).unwrap();
+    assert_eq!(matrix.pixels(), &[RED, BLUE, BLUE, RED]);
+
+    let luma = [1, 2, 9, 9, 9, 3, 4, 9, 9, 9];
+    let matrix = ImageMatrix::from_raw(2, 2, 5, &luma, PixelFormat::Luma).unwrap();
+    assert_eq!(
+        matrix.pixels(),
+        &[Rgb([1; 3]), Rgb([2; 3]), Rgb([3; 3]), Rgb([4; 3])]
+    );
+}
+
+#[test]
+fn test_yuv_formats() {
+    const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+    const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+
+    // two pixels sharing neutral chroma, with padding at the end of each row
+    let yuyv = [235, 128, 16, 128, 0, 0, 16, 128, 235, 128, 0, 0];
+    let matrix = ImageMatrix::from_yuyv(2, 2, 6, &yuyv).unwrap();
+    assert_eq!(matrix.pixels(), &[WHITE, BLACK, BLACK, WHITE]);
+
+    let nv12 = [235, 16, 16, 235, 128, 128];
+    let matrix = ImageMatrix::from_nv12(2, 2, 2, &nv12).unwrap();
+    assert_eq!(matrix.pixels(), &[WHITE, BLACK, BLACK, WHITE]);
+}
+
+#[test]
+fn test_transforms() {
+    use image::imageops;
+
+    // order sensitive, so that transposed or mirrored outputs don't match
+    fn checksum(image: &RgbImage) -> u64 {
+        image
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| (i as u64 % 251 + 1) * value as u64)
+            .sum()
+    }
+
+    let image = image::open("assets/obama.jpg").unwrap().to_rgb8();
+    let matrix = ImageMatrix::from_image(&image);
+
+    let check = |transformed: ImageMatrix, expected: RgbImage| {
+        let transformed = transformed.to_rgb_image();
+        assert_eq!(transformed.dimensions(), expected.dimensions());
+        assert_eq!(checksum(&transformed), checksum(&expected));
+    };
+
+    check(matrix.rotate90(), imageops::rotate90(&image));
+    check(matrix.rotate180(), imageops::rotate180(&image));
+    check(matrix.rotate270(), imageops::rotate270(&image));
+    check(matrix.flip_horizontal(), imageops::flip_horizontal(&image));
+    check(matrix.flip_vertical(), imageops::flip_vertical(&image));
+
+    let rect = Rectangle {
+        left: 10,
+        top: 20,
+        right: 109,
+        bottom: 69,
+    };
+    check(
+        matrix.crop(&rect),
+        imageops::crop_imm(&image, 10, 20, 100, 50).to_image(),
+    );
+
+    // crops are clipped to the image
+    let (width, height) = (matrix.width() as i64, matrix.height() as i64);
+    let overhanging = Rectangle {
+        left: width - 10,
+        top: -5,
+        right: width + 10,
+        bottom: 4,
+    };
+    assert_eq!(matrix.crop(&overhanging).width(), 10);
+    assert_eq!(matrix.crop(&overhanging).height(), 5);
+
+    let outside = Rectangle {
+        left: width,
+        top: height,
+        right: width + 10,
+        bottom: height + 10,
+    };
+    assert!(matrix.crop(&outside).pixels().is_empty());
+
+    let up = matrix.pyramid_up();
+    assert!((up.width() as i64 - 2 * width).abs() <= 2);
+    assert!((up.height() as i64 - 2 * height).abs() <= 2);
+
+    let gray = matrix.to_grayscale();
+    assert_eq!(
+        (gray.width(), gray.height()),
+        (matrix.width(), matrix.height())
+    );
+    assert!(gray.pixels().iter().all(|p| p[0] == p[1] && p[1] == p[2]));
+
+    // equalizing spreads every channel over the whole range
+    let equalized = matrix.equalize_histogram();
+    assert_eq!(equalized.width(), matrix.width());
+    for c in 0..3 {
+        let max = equalized.pixels().iter().map(|p| p[c]).max().unwrap();
+        assert_eq!(max, 255);
+    }
+
+    let rotated = matrix.rotate(0.0);
+    assert_eq!(
+        (rotated.width(), rotated.height()),
+        (matrix.width(), matrix.height())
+    );
+}
+
+#[test]
+fn test_face_chip_checks() {
+    use crate::geometry::Point;
+
+    let matrix = ImageMatrix::from_image(&RgbImage::new(200, 200));
+
+    // the outer and inner corners of both eyes, and the bottom of the nose
+    let five: FaceLandmarks = vec![
+        Point::new(130, 80),
+        Point::new(110, 80),
+        Point::new(70, 80),
+        Point::new(90, 80),
+        Point::new(100, 120),
+    ]
+    .into();
+    let chip = matrix.face_chip(&five, 150, 0.25).unwrap();
+    assert_eq!((chip.width(), chip.height()), (150, 150));
+
+    assert!(matrix.face_chip(&five[..3].into(), 150, 0.25).is_none());
+    assert!(matrix.face_chip(&five, 0, 0.25).is_none());
+    assert!(matrix.face_chip(&five, 150, -0.25).is_none());
+    assert!(matrix.face_chip(&five, 150, f64::NAN).is_none());
+    assert!(matrix.face_chip(&five, 150, f64::INFINITY).is_none());
 }
@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+/// Bucket bounds for latencies, in seconds.
+pub const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// A histogram of observed values, with the same buckets as a Prometheus histogram.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    bounds: Vec<f64>,
+    /// The number of values in each bucket, not cumulative. The last bucket holds values above every bound.
+    buckets: Vec<u64>,
+    count: u64,
+    sum: f64,
+    max: f64,
+}
+
+impl Histogram {
+    /// Create a histogram with buckets for values up to each of `bounds`.
+    pub fn new(bounds: &[f64]) -> Self {
+        let mut bounds = bounds.to_vec();
+        bounds.sort_by(|a, b| a.total_cmp(b));
+        bounds.dedup();
+
+        Self {
+            buckets: vec![0; bounds.len() + 1],
+            bounds,
+            count: 0,
+            sum: 0.0,
+            max: 0.0,
+        }
+    }
+
+    /// A histogram of latencies in seconds, from 1ms to 5s.
+    pub fn latency() -> Self {
+        Self::new(&LATENCY_BUCKETS)
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum += value;
+        self.max = self.max.max(value);
+    }
+
+    pub fn observe_duration(&mut self, duration: Duration) {
+        self.observe(duration.as_secs_f64());
+    }
+
+    pub fn bounds(&self) -> &[f64] {
+        &self.bounds
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    /// The mean value, zero if nothing was observed.
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+
+    /// The number of values up to each bound, ending with every value at `f64::INFINITY`.
+    pub fn cumulative(&self) -> Vec<(f64, u64)> {
+        let mut total = 0;
+
+        self.bounds
+            .iter()
+            .copied()
+            .chain(std::iter::once(f64::INFINITY))
+            .zip(self.buckets.iter())
+            .map(|(bound, count)| {
+                total += count;
+                (bound, total)
+            })
+            .collect()
+    }
+
+    /// An upper bound of the `q` quantile: the bound of the bucket it falls in,
+    /// or the largest value if it's above every bound.
+    pub fn quantile(&self, q: f64) -> f64 {
+        let rank = (q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64;
+
+        self.cumulative()
+            .into_iter()
+            .find(|(_, count)| *count >= rank.max(1))
+            .map_or(0.0, |(bound, _)| bound.min(self.max))
+    }
+}
+
+#[test]
+fn test_histogram() {
+    let mut histogram = Histogram::new(&[0.1, 0.01, 1.0]);
+    assert_eq!(histogram.bounds(), &[0.01, 0.1, 1.0]);
+    assert_eq!(histogram.quantile(0.5), 0.0);
+
+    for value in [0.005, 0.01, 0.05, 0.5, 3.0].iter() {
+        histogram.observe(*value);
+    }
+    histogram.observe_duration(Duration::from_millis(20));
+
+    assert_eq!(histogram.count(), 6);
+    assert!((histogram.sum() - 3.585).abs() < 1e-9);
+    assert!((histogram.mean() - 0.5975).abs() < 1e-9);
+    assert_eq!(histogram.max(), 3.0);
+    assert_eq!(
+        histogram.cumulative(),
+        vec![(0.01, 2), (0.1, 4), (1.0, 5), (f64::INFINITY, 6)]
+    );
+
+    assert_eq!(histogram.quantile(0.0), 0.01);
+    assert_eq!(histogram.quantile(0.5), 0.1);
+    assert_eq!(histogram.quantile(0.8), 1.0);
+    assert_eq!(histogram.quantile(1.0), 3.0);
+}
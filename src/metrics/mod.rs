@@ -0,0 +1,257 @@
+//! Runtime metrics.
+//!
+//! [`Metrics`] counts captured frames, detected, matched and unknown faces and dropped frames,
+//! and keeps a latency [`Histogram`] for every stage of the [`Pipeline`](crate::pipeline::Pipeline).
+//! Share it with a pipeline through [`Pipeline::with_metrics`](crate::pipeline::Pipeline::with_metrics),
+//! then log [`Metrics::summary`] now and then, or serve [`Metrics::to_prometheus`] with a [`MetricsServer`].
+
+mod histogram;
+mod prometheus;
+mod server;
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub use self::histogram::{Histogram, LATENCY_BUCKETS};
+pub use self::server::MetricsServer;
+
+use crate::pipeline::Stage;
+
+/// The capture frame rate is measured over this window.
+const FPS_WINDOW: Duration = Duration::from_secs(5);
+
+/// Metrics of a running recognition pipeline, safe to update and read from any thread.
+pub struct Metrics {
+    started: Instant,
+    frames_captured: AtomicU64,
+    captured_at: Mutex<VecDeque<Instant>>,
+    faces_detected: AtomicU64,
+    faces_matched: AtomicU64,
+    faces_unknown: AtomicU64,
+    /// Frames dropped in front of each stage, in the order of [`Stage::ALL`].
+    dropped: Vec<Arc<AtomicU64>>,
+    output_dropped: Arc<AtomicU64>,
+    stage_latency: Vec<Mutex<Histogram>>,
+    end_to_end: Mutex<Histogram>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn stage_index(stage: Stage) -> usize {
+    Stage::ALL.iter().position(|s| *s == stage).unwrap()
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            frames_captured: AtomicU64::new(0),
+            captured_at: Mutex::new(VecDeque::new()),
+            faces_detected: AtomicU64::new(0),
+            faces_matched: AtomicU64::new(0),
+            faces_unknown: AtomicU64::new(0),
+            dropped: Stage::ALL.iter().map(|_| Arc::default()).collect(),
+            output_dropped: Arc::default(),
+            stage_latency: Stage::ALL
+                .iter()
+                .map(|_| Mutex::new(Histogram::latency()))
+                .collect(),
+            end_to_end: Mutex::new(Histogram::latency()),
+        }
+    }
+
+    /// Record a frame taken from the capture.
+    pub fn record_frame(&self, now: Instant) {
+        self.frames_captured.fetch_add(1, Ordering::Relaxed);
+
+        let mut captured_at = self.captured_at.lock().unwrap();
+        captured_at.push_back(now);
+        while let Some(&first) = captured_at.front() {
+            if now.duration_since(first) > FPS_WINDOW {
+                captured_at.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Record the time a stage spent on a frame.
+    pub fn record_stage(&self, stage: Stage, latency: Duration) {
+        self.stage_latency[stage_index(stage)]
+            .lock()
+            .unwrap()
+            .observe_duration(latency);
+    }
+
+    /// Record the faces of a frame that went through every stage, and how long the frame took.
+    ///
+    /// Faces can be detected without being either matched or unknown, e.g. when they're too poor to match.
+    pub fn record_result(
+        &self,
+        detected: usize,
+        matched: usize,
+        unknown: usize,
+        latency: Duration,
+    ) {
+        self.faces_detected
+            .fetch_add(detected as u64, Ordering::Relaxed);
+        self.faces_matched
+            .fetch_add(matched as u64, Ordering::Relaxed);
+        self.faces_unknown
+            .fetch_add(unknown as u64, Ordering::Relaxed);
+        self.end_to_end.lock().unwrap().observe_duration(latency);
+    }
+
+    /// Record a frame a stage threw away itself, e.g. because it was out of date.
+    pub(crate) fn record_drop(&self, stage: Stage) {
+        self.dropped[stage_index(stage)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The counter of frames dropped in front of `stage`, shared with the stage's queue.
+    pub(crate) fn dropped_counter(&self, stage: Stage) -> Arc<AtomicU64> {
+        self.dropped[stage_index(stage)].clone()
+    }
+
+    /// The counter of results dropped because they weren't read in time.
+    pub(crate) fn output_dropped_counter(&self) -> Arc<AtomicU64> {
+        self.output_dropped.clone()
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn frames_captured(&self) -> u64 {
+        self.frames_captured.load(Ordering::Relaxed)
+    }
+
+    /// Frames captured per second, over the last few seconds.
+    pub fn capture_fps(&self) -> f64 {
+        let captured_at = self.captured_at.lock().unwrap();
+
+        match (captured_at.front(), captured_at.back()) {
+            (Some(first), Some(last)) if captured_at.len() > 1 => {
+                let elapsed = last.duration_since(*first).as_secs_f64();
+                (captured_at.len() - 1) as f64 / elapsed.max(f64::EPSILON)
+            }
+            _ => 0.0,
+        }
+    }
+
+    pub fn faces_detected(&self) -> u64 {
+        self.faces_detected.load(Ordering::Relaxed)
+    }
+
+    pub fn faces_matched(&self) -> u64 {
+        self.faces_matched.load(Ordering::Relaxed)
+    }
+
+    pub fn faces_unknown(&self) -> u64 {
+        self.faces_unknown.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped(&self, stage: Stage) -> u64 {
+        self.dropped[stage_index(stage)].load(Ordering::Relaxed)
+    }
+
+    pub fn output_dropped(&self) -> u64 {
+        self.output_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Frames dropped anywhere in the pipeline.
+    pub fn total_dropped(&self) -> u64 {
+        Stage::ALL
+            .iter()
+            .map(|stage| self.dropped(*stage))
+            .sum::<u64>()
+            + self.output_dropped()
+    }
+
+    pub fn stage_latency(&self, stage: Stage) -> Histogram {
+        self.stage_latency[stage_index(stage)]
+            .lock()
+            .unwrap()
+            .clone()
+    }
+
+    /// The time from taking frames from the capture to their result being ready.
+    pub fn end_to_end(&self) -> Histogram {
+        self.end_to_end.lock().unwrap().clone()
+    }
+
+    /// A one line summary for logs.
+    pub fn summary(&self) -> String {
+        let end_to_end = self.end_to_end();
+        let mut summary = format!(
+            "{:.1} fps, {} frames, {} dropped, {} faces ({} matched, {} unknown), latency {:.1}ms p95 {:.1}ms",
+            self.capture_fps(),
+            self.frames_captured(),
+            self.total_dropped(),
+            self.faces_detected(),
+            self.faces_matched(),
+            self.faces_unknown(),
+            end_to_end.mean() * 1000.0,
+            end_to_end.quantile(0.95) * 1000.0,
+        );
+
+        for stage in Stage::ALL.iter() {
+            let latency = self.stage_latency(*stage);
+            summary += &format!(
+                " | {} {:.1}ms p95 {:.1}ms",
+                stage,
+                latency.mean() * 1000.0,
+                latency.quantile(0.95) * 1000.0,
+            );
+        }
+
+        summary
+    }
+}
+
+#[test]
+fn test_metrics() {
+    let metrics = Metrics::new();
+    assert_eq!(metrics.capture_fps(), 0.0);
+
+    let start = Instant::now();
+    for i in 0..11 {
+        metrics.record_frame(start + Duration::from_millis(100 * i));
+    }
+    assert_eq!(metrics.frames_captured(), 11);
+    assert!((metrics.capture_fps() - 10.0).abs() < 1e-6);
+
+    // frames older than the window no longer count towards the frame rate
+    metrics.record_frame(start + Duration::from_secs(10));
+    metrics.record_frame(start + Duration::from_millis(10_500));
+    assert!((metrics.capture_fps() - 2.0).abs() < 1e-6);
+
+    metrics.record_stage(Stage::Encode, Duration::from_millis(30));
+    metrics.record_result(4, 2, 1, Duration::from_millis(40));
+    metrics.record_result(0, 0, 0, Duration::from_millis(20));
+    metrics
+        .dropped_counter(Stage::Detect)
+        .fetch_add(3, Ordering::Relaxed);
+    metrics
+        .output_dropped_counter()
+        .fetch_add(1, Ordering::Relaxed);
+
+    assert_eq!(metrics.faces_detected(), 4);
+    assert_eq!(metrics.faces_matched(), 2);
+    assert_eq!(metrics.faces_unknown(), 1);
+    assert_eq!(metrics.dropped(Stage::Detect), 3);
+    assert_eq!(metrics.dropped(Stage::Encode), 0);
+    assert_eq!(metrics.total_dropped(), 4);
+    assert_eq!(metrics.stage_latency(Stage::Encode).count(), 1);
+    assert_eq!(metrics.stage_latency(Stage::Detect).count(), 0);
+    assert_eq!(metrics.end_to_end().count(), 2);
+
+    let summary = metrics.summary();
+    assert!(summary.starts_with("2.0 fps, 13 frames, 4 dropped, 4 faces (2 matched, 1 unknown)"));
+    assert!(summary.contains("| encode 30.0ms p95 30.0ms |"));
+}
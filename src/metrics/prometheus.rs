@@ -0,0 +1,165 @@
+use std::fmt::Write;
+
+use super::{Histogram, Metrics};
+use crate::pipeline::Stage;
+
+/// Every metric name starts with this prefix.
+const PREFIX: &str = "fofscreen";
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {}_{} {}", PREFIX, name, help).unwrap();
+    writeln!(out, "# TYPE {}_{} {}", PREFIX, name, kind).unwrap();
+}
+
+fn sample<V: std::fmt::Display>(out: &mut String, name: &str, labels: &str, value: V) {
+    if labels.is_empty() {
+        writeln!(out, "{}_{} {}", PREFIX, name, value).unwrap();
+    } else {
+        writeln!(out, "{}_{}{{{}}} {}", PREFIX, name, labels, value).unwrap();
+    }
+}
+
+fn histogram(out: &mut String, name: &str, labels: &str, histogram: &Histogram) {
+    let separator = if labels.is_empty() { "" } else { "," };
+
+    for (bound, count) in histogram.cumulative() {
+        let le = if bound.is_infinite() {
+            "+Inf".to_string()
+        } else {
+            bound.to_string()
+        };
+        let labels = format!("{}{}le=\"{}\"", labels, separator, le);
+        sample(out, &format!("{}_bucket", name), &labels, count);
+    }
+
+    sample(out, &format!("{}_sum", name), labels, histogram.sum());
+    sample(out, &format!("{}_count", name), labels, histogram.count());
+}
+
+impl Metrics {
+    /// The metrics in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let out = &mut out;
+
+        let gauges = [
+            (
+                "uptime_seconds",
+                "Seconds since the metrics were created.",
+                self.uptime().as_secs_f64(),
+            ),
+            (
+                "capture_fps",
+                "Frames captured per second over the last few seconds.",
+                self.capture_fps(),
+            ),
+        ];
+        for (name, help, value) in gauges.iter() {
+            header(out, name, "gauge", help);
+            sample(out, name, "", value);
+        }
+
+        let counters = [
+            (
+                "frames_captured_total",
+                "Frames taken from the capture.",
+                self.frames_captured(),
+            ),
+            (
+                "faces_detected_total",
+                "Faces found in recognised frames.",
+                self.faces_detected(),
+            ),
+            (
+                "faces_matched_total",
+                "Faces recognised as a reference.",
+                self.faces_matched(),
+            ),
+            (
+                "faces_unknown_total",
+                "Faces that didn't match any reference.",
+                self.faces_unknown(),
+            ),
+        ];
+        for (name, help, value) in counters.iter() {
+            header(out, name, "counter", help);
+            sample(out, name, "", value);
+        }
+
+        let name = "frames_dropped_total";
+        header(
+            out,
+            name,
+            "counter",
+            "Frames dropped in front of a stage, or results dropped before being read.",
+        );
+        for stage in Stage::ALL.iter() {
+            let labels = format!("stage=\"{}\"", stage);
+            sample(out, name, &labels, self.dropped(*stage));
+        }
+        sample(out, name, "stage=\"output\"", self.output_dropped());
+
+        let name = "stage_latency_seconds";
+        header(
+            out,
+            name,
+            "histogram",
+            "Time a pipeline stage spent on a frame.",
+        );
+        for stage in Stage::ALL.iter() {
+            let labels = format!("stage=\"{}\"", stage);
+            histogram(out, name, &labels, &self.stage_latency(*stage));
+        }
+
+        let name = "latency_seconds";
+        header(
+            out,
+            name,
+            "histogram",
+            "Time from capturing a frame to its result being ready.",
+        );
+        histogram(out, name, "", &self.end_to_end());
+
+        out.clone()
+    }
+}
+
+#[test]
+fn test_prometheus_format() {
+    use std::time::Duration;
+
+    let metrics = Metrics::new();
+    metrics.record_stage(Stage::Detect, Duration::from_millis(3));
+    metrics.record_stage(Stage::Detect, Duration::from_millis(40));
+    metrics.record_result(1, 1, 0, Duration::from_millis(60));
+
+    let text = metrics.to_prometheus();
+    let lines: Vec<&str> = text.lines().collect();
+
+    assert!(lines.contains(&"# TYPE fofscreen_frames_captured_total counter"));
+    assert!(lines.contains(&"fofscreen_frames_captured_total 0"));
+    assert!(lines.contains(&"fofscreen_frames_dropped_total{stage=\"match\"} 0"));
+    assert!(lines.contains(&"fofscreen_frames_dropped_total{stage=\"output\"} 0"));
+    assert!(lines.contains(&"fofscreen_faces_matched_total 1"));
+    assert!(lines.contains(&"# TYPE fofscreen_stage_latency_seconds histogram"));
+    assert!(
+        lines.contains(&"fofscreen_stage_latency_seconds_bucket{stage=\"detect\",le=\"0.001\"} 0")
+    );
+    assert!(
+        lines.contains(&"fofscreen_stage_latency_seconds_bucket{stage=\"detect\",le=\"0.005\"} 1")
+    );
+    assert!(
+        lines.contains(&"fofscreen_stage_latency_seconds_bucket{stage=\"detect\",le=\"+Inf\"} 2")
+    );
+    assert!(lines.contains(&"fofscreen_stage_latency_seconds_count{stage=\"detect\"} 2"));
+    assert!(lines.contains(&"fofscreen_stage_latency_seconds_count{stage=\"encode\"} 0"));
+    assert!(lines.contains(&"fofscreen_latency_seconds_bucket{le=\"0.1\"} 1"));
+    assert!(lines.contains(&"fofscreen_latency_seconds_sum 0.06"));
+
+    // every sample is a name, optional labels and a number
+    for line in lines.iter().filter(|line| !line.starts_with('#')) {
+        let value = line.rsplit(' ').next().unwrap();
+        assert!(value.parse::<f64>().is_ok(), "{}", line);
+        assert!(line.starts_with("fofscreen_"), "{}", line);
+    }
+}
@@ -0,0 +1,69 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use tiny_http::{Header, Method, Response};
+
+use super::Metrics;
+use crate::http::{self, HttpServer};
+
+/// The content type of the Prometheus text format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Serves [`Metrics::to_prometheus`] at `/metrics` on a background thread.
+///
+/// The server stops when dropped.
+pub struct MetricsServer {
+    server: HttpServer,
+}
+
+impl MetricsServer {
+    /// Listen on `addr`, e.g. `127.0.0.1:9898`. Port 0 picks a free port, see [`MetricsServer::addr`].
+    pub fn start(addr: &str, metrics: Arc<Metrics>) -> Result<Self, String> {
+        let server = HttpServer::start(addr, move |request| {
+            if *request.method() != Method::Get {
+                Response::from_string("method not allowed\n").with_status_code(405)
+            } else if http::path(request) == "/metrics" {
+                let header = Header::from_bytes(&b"Content-Type"[..], CONTENT_TYPE).unwrap();
+                Response::from_string(metrics.to_prometheus()).with_header(header)
+            } else {
+                Response::from_string("not found, try /metrics\n").with_status_code(404)
+            }
+        })
+        .map_err(|why| format!("Can't serve metrics on {}: {}", addr, why))?;
+
+        Ok(Self { server })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.server.addr()
+    }
+}
+
+#[test]
+fn test_metrics_server() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.0\r\nHost: localhost\r\n\r\n", path).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    let metrics = Arc::new(Metrics::new());
+    metrics.record_result(3, 1, 2, std::time::Duration::from_millis(5));
+
+    let server = MetricsServer::start("127.0.0.1:0", metrics).unwrap();
+
+    let response = get(server.addr(), "/metrics");
+    assert!(response.starts_with("HTTP/1.0 200"));
+    assert!(response.contains("Content-Type: text/plain; version=0.0.4"));
+    assert!(response.contains("\nfofscreen_faces_unknown_total 2\n"));
+
+    assert!(get(server.addr(), "/").starts_with("HTTP/1.0 404"));
+
+    assert!(MetricsServer::start(&server.addr().to_string(), Arc::new(Metrics::new())).is_err());
+}
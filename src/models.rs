@@ -0,0 +1,471 @@
+//! Locating and verifying the dlib model files.
+//!
+//! A [`ModelRegistry`] looks for the file of a [`Model`] in order:
+//!
+//! - a path given for that model with [`ModelRegistry::with_path`],
+//! - directories added with [`ModelRegistry::with_directory`],
+//! - the directory in the `FOFSCREEN_MODEL_DIR` environment variable,
+//! - `$XDG_DATA_HOME/fofscreen/models`, `~/.local/share/fofscreen/models` if `XDG_DATA_HOME` isn't set,
+//! - the `files` directory of the source tree, where the `download-models` feature puts them.
+//!
+//! Files are hashed and rejected if they don't match the SHA-256 dlib published for the model, see [`Model::sha256`],
+//! or the one pinned with [`ModelRegistry::with_sha256`] or a `sha256sum` style checksums file instead, e.g. for
+//! a retrained model. The resulting [`ModelInfo`] names the model that was loaded,
+//! so stored encodings can be tied to the encoder that produced them, see [`crate::gallery::Gallery::check_encoder`].
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The environment variable with a directory to look for models in.
+pub const MODEL_DIR_VAR: &str = "FOFSCREEN_MODEL_DIR";
+
+/// A model file used by fofscreen.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Model {
+    FaceDetectorCnn,
+    LandmarkPredictor,
+    FaceEncoder,
+}
+
+impl Model {
+    pub const ALL: [Model; 3] = [
+        Model::FaceDetectorCnn,
+        Model::LandmarkPredictor,
+        Model::FaceEncoder,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Model::FaceDetectorCnn => "mmod_human_face_detector",
+            Model::LandmarkPredictor => "shape_predictor_68_face_landmarks",
+            Model::FaceEncoder => "dlib_face_recognition_resnet_model",
+        }
+    }
+
+    /// The version of the model, models without a version in their file name are version `1`.
+    pub fn version(self) -> &'static str {
+        "1"
+    }
+
+    pub fn filename(self) -> &'static str {
+        match self {
+            Model::FaceDetectorCnn => "mmod_human_face_detector.dat",
+            Model::LandmarkPredictor => "shape_predictor_68_face_landmarks.dat",
+            Model::FaceEncoder => "dlib_face_recognition_resnet_model_v1.dat",
+        }
+    }
+
+    /// Where dlib publishes the model, compressed with bzip2.
+    pub fn url(self) -> String {
+        format!("http://dlib.net/files/{}.bz2", self.filename())
+    }
+
+    /// The SHA-256 of the uncompressed file published at [`Model::url`], as a lowercase hex string, which
+    /// [`ModelRegistry::resolve`] checks files against unless another checksum is pinned.
+    ///
+    /// `None` for a model whose checksum isn't recorded here yet, its files are only checked against pinned checksums.
+    pub fn sha256(self) -> Option<&'static str> {
+        match self {
+            Model::FaceDetectorCnn => None,
+            Model::LandmarkPredictor => None,
+            Model::FaceEncoder => None,
+        }
+    }
+
+    /// The model a file name belongs to.
+    pub fn from_filename(filename: &str) -> Option<Model> {
+        Model::ALL
+            .iter()
+            .copied()
+            .find(|model| model.filename() == filename)
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} v{}", self.name(), self.version())
+    }
+}
+
+/// Which model a file is, stored with the data it produced.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub name: String,
+    pub version: String,
+    /// The SHA-256 of the file, if it was verified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl ModelInfo {
+    pub fn new(model: Model) -> Self {
+        Self {
+            name: model.name().to_string(),
+            version: model.version().to_string(),
+            sha256: None,
+        }
+    }
+
+    /// The model of a file going by its name: a known [`Model`], or the file stem as version `1` for other files.
+    pub fn for_file(path: &Path) -> Self {
+        let filename = path.file_name().and_then(|name| name.to_str());
+        match filename.and_then(Model::from_filename) {
+            Some(model) => Self::new(model),
+            None => Self {
+                name: path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                version: "1".to_string(),
+                sha256: None,
+            },
+        }
+    }
+
+    /// Whether data of both models can be mixed: same name and version, and same file if both were verified.
+    pub fn is_compatible(&self, other: &ModelInfo) -> bool {
+        self.name == other.name
+            && self.version == other.version
+            && match (&self.sha256, &other.sha256) {
+                (Some(ours), Some(theirs)) => ours == theirs,
+                _ => true,
+            }
+    }
+}
+
+impl fmt::Display for ModelInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} v{}", self.name, self.version)?;
+        if let Some(sha256) = &self.sha256 {
+            write!(f, " ({})", &sha256[..sha256.len().min(12)])?;
+        }
+        Ok(())
+    }
+}
+
+/// A model file that was found, and verified if it has a checksum.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedModel {
+    pub path: PathBuf,
+    pub info: ModelInfo,
+}
+
+/// Finds model files and checks them against the published or pinned checksums.
+#[derive(Clone, Debug)]
+pub struct ModelRegistry {
+    paths: HashMap<Model, PathBuf>,
+    directories: Vec<PathBuf>,
+    checksums: HashMap<Model, String>,
+}
+
+impl Default for ModelRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModelRegistry {
+    /// A registry searching the directories of the environment, checking files against the published checksums.
+    pub fn new() -> Self {
+        Self {
+            paths: HashMap::new(),
+            directories: default_directories(|name| std::env::var_os(name)),
+            checksums: HashMap::new(),
+        }
+    }
+
+    /// Use `path` for `model` instead of searching for it.
+    pub fn with_path<P: Into<PathBuf>>(mut self, model: Model, path: P) -> Self {
+        self.paths.insert(model, path.into());
+        self
+    }
+
+    /// Search `directory` before the default directories.
+    pub fn with_directory<P: Into<PathBuf>>(mut self, directory: P) -> Self {
+        self.directories.insert(0, directory.into());
+        self
+    }
+
+    /// Only accept a file for `model` with this SHA-256, as a hex string, instead of the published one.
+    pub fn with_sha256(mut self, model: Model, sha256: &str) -> Self {
+        self.checksums.insert(model, sha256.trim().to_lowercase());
+        self
+    }
+
+    /// Pin the checksums listed in a file written by `sha256sum`, lines of `<sha256>  <file name>`.
+    ///
+    /// Lines of files that aren't models are ignored.
+    pub fn with_checksums_file<P: AsRef<Path>>(mut self, path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let checksums = fs::read_to_string(path)
+            .map_err(|why| format!("Can't read checksums {}: {}", path.display(), why))?;
+
+        for line in checksums.lines().filter(|line| !line.trim().is_empty()) {
+            let mut columns = line.split_whitespace();
+            let (sha256, filename) = match (columns.next(), columns.next()) {
+                (Some(sha256), Some(filename)) => (sha256, filename.trim_start_matches('*')),
+                _ => {
+                    return Err(format!(
+                        "Invalid checksum line in {}: {}",
+                        path.display(),
+                        line
+                    ))
+                }
+            };
+
+            let filename = Path::new(filename)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or(filename);
+            if let Some(model) = Model::from_filename(filename) {
+                self = self.with_sha256(model, sha256);
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// The directories searched for models, in order.
+    pub fn directories(&self) -> &[PathBuf] {
+        &self.directories
+    }
+
+    /// The file of `model`, without verifying it.
+    pub fn locate(&self, model: Model) -> Result<PathBuf, String> {
+        if let Some(path) = self.paths.get(&model) {
+            return if path.exists() {
+                Ok(path.clone())
+            } else {
+                Err(format!("Model file not found: '{}'", path.display()))
+            };
+        }
+
+        self.directories
+            .iter()
+            .map(|directory| directory.join(model.filename()))
+            .find(|path| path.exists())
+            .ok_or_else(|| {
+                let searched: Vec<String> = self
+                    .directories
+                    .iter()
+                    .map(|directory| directory.display().to_string())
+                    .collect();
+                format!(
+                    "Model {} not found in {}. Download it from {} or set {}",
+                    model.filename(),
+                    searched.join(", "),
+                    model.url(),
+                    MODEL_DIR_VAR
+                )
+            })
+    }
+
+    /// The SHA-256 a file of `model` must have: the pinned one, or else the published one.
+    pub fn checksum(&self, model: Model) -> Option<&str> {
+        self.checksums
+            .get(&model)
+            .map(String::as_str)
+            .or_else(|| model.sha256())
+    }
+
+    /// The file of `model`, checked against its [checksum](ModelRegistry::checksum) if there is one.
+    pub fn resolve(&self, model: Model) -> Result<ResolvedModel, String> {
+        let path = self.locate(model)?;
+        let mut info = ModelInfo::new(model);
+
+        if let Some(expected) = self.checksum(model) {
+            let actual = sha256_file(&path)?;
+            if actual != expected {
+                let source = if self.checksums.contains_key(&model) {
+                    "pinned"
+                } else {
+                    "published"
+                };
+                return Err(format!(
+                    "'{}' has SHA-256 {}, expected the {} {} for {}",
+                    path.display(),
+                    actual,
+                    source,
+                    expected,
+                    model
+                ));
+            }
+            info.sha256 = Some(actual);
+        }
+
+        Ok(ResolvedModel { path, info })
+    }
+}
+
+/// The SHA-256 of a file, as a lowercase hex string.
+pub fn sha256_file<P: AsRef<Path>>(path: P) -> Result<String, String> {
+    let path = path.as_ref();
+    let mut file =
+        File::open(path).map_err(|why| format!("Can't read {}: {}", path.display(), why))?;
+
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|why| format!("Can't read {}: {}", path.display(), why))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+fn default_directories<F: Fn(&str) -> Option<OsString>>(env: F) -> Vec<PathBuf> {
+    let mut directories = Vec::new();
+
+    if let Some(directory) = env(MODEL_DIR_VAR).filter(|value| !value.is_empty()) {
+        directories.push(PathBuf::from(directory));
+    }
+
+    let data_home = env("XDG_DATA_HOME")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env("HOME").map(|home| PathBuf::from(home).join(".local").join("share")));
+    if let Some(data_home) = data_home {
+        directories.push(data_home.join("fofscreen").join("models"));
+    }
+
+    directories.push(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("files"));
+    directories
+}
+
+#[test]
+fn test_model_registry() {
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |name: &str| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| OsString::from(value))
+        }
+    };
+    let files = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("files");
+
+    assert_eq!(
+        default_directories(env(&[
+            (MODEL_DIR_VAR, "/opt/models"),
+            ("HOME", "/home/alice")
+        ])),
+        vec![
+            PathBuf::from("/opt/models"),
+            PathBuf::from("/home/alice/.local/share/fofscreen/models"),
+            files.clone(),
+        ]
+    );
+    assert_eq!(
+        default_directories(env(&[("XDG_DATA_HOME", "/data"), ("HOME", "/home/alice")])),
+        vec![PathBuf::from("/data/fofscreen/models"), files]
+    );
+
+    let directory = std::env::temp_dir().join(format!("fofscreen-models-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let detector = directory.join(Model::FaceDetectorCnn.filename());
+    fs::write(&detector, b"abc").unwrap();
+
+    // the SHA-256 test vector of "abc" from FIPS 180-2
+    let abc = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    assert_eq!(sha256_file(&detector).unwrap(), abc);
+
+    // every published checksum is a SHA-256, and rejects a file that isn't the model
+    for model in Model::ALL.iter().copied() {
+        if let Some(sha256) = model.sha256() {
+            assert_eq!(sha256.len(), 64, "{}", model);
+            assert!(sha256
+                .bytes()
+                .all(|byte| byte.is_ascii_hexdigit() && !byte.is_ascii_uppercase()));
+
+            let tampered = directory.join(model.filename());
+            fs::write(&tampered, b"abc").unwrap();
+            let registry = ModelRegistry::new().with_directory(&directory);
+            let error = registry.resolve(model).unwrap_err();
+            assert!(error.contains("expected the published"), "{}", error);
+        }
+    }
+
+    let registry = ModelRegistry::new().with_directory(&directory);
+
+    // models without a published checksum are only located
+    if Model::FaceDetectorCnn.sha256().is_none() {
+        let resolved = registry.resolve(Model::FaceDetectorCnn).unwrap();
+        assert_eq!(resolved.path, detector);
+        assert_eq!(resolved.info, ModelInfo::new(Model::FaceDetectorCnn));
+    }
+
+    // a pinned checksum replaces the published one, e.g. for a retrained model, and rejects any other file
+    let pinned = registry.clone().with_sha256(Model::FaceDetectorCnn, abc);
+    let resolved = pinned.resolve(Model::FaceDetectorCnn).unwrap();
+    assert_eq!(resolved.path, detector);
+    assert_eq!(resolved.info.sha256.as_deref(), Some(abc));
+    fs::write(&detector, b"abd").unwrap();
+    let error = pinned.resolve(Model::FaceDetectorCnn).unwrap_err();
+    assert!(error.contains("expected the pinned ba7816bf"), "{}", error);
+    fs::write(&detector, b"abc").unwrap();
+
+    let sums = directory.join("SHA256SUMS");
+    fs::write(
+        &sums,
+        format!(
+            "{}  other.dat\n{}  models/mmod_human_face_detector.dat\n",
+            abc,
+            "0".repeat(64)
+        ),
+    )
+    .unwrap();
+    let error = registry
+        .clone()
+        .with_checksums_file(&sums)
+        .unwrap()
+        .resolve(Model::FaceDetectorCnn)
+        .unwrap_err();
+    assert!(error.contains("expected the pinned 0000"), "{}", error);
+
+    let missing = directory.join("missing.dat");
+    let error = registry
+        .clone()
+        .with_path(Model::FaceEncoder, &missing)
+        .resolve(Model::FaceEncoder)
+        .unwrap_err();
+    assert!(error.contains("missing.dat"), "{}", error);
+
+    fs::remove_dir_all(&directory).unwrap();
+
+    let old = ModelInfo::new(Model::FaceEncoder);
+    let verified = ModelInfo {
+        sha256: Some(abc.to_string()),
+        ..old.clone()
+    };
+    assert!(old.is_compatible(&verified));
+    assert!(!verified.is_compatible(&ModelInfo {
+        sha256: Some("0".repeat(64)),
+        ..old.clone()
+    }));
+    assert!(!old.is_compatible(&ModelInfo::new(Model::LandmarkPredictor)));
+    assert_eq!(old.to_string(), "dlib_face_recognition_resnet_model v1");
+    assert_eq!(
+        ModelInfo::for_file(Path::new("files/dlib_face_recognition_resnet_model_v1.dat")),
+        old
+    );
+    assert_eq!(
+        ModelInfo::for_file(Path::new("custom_encoder.dat")).name,
+        "custom_encoder"
+    );
+}
@@ -0,0 +1,853 @@
+//! A multi-stage recognition pipeline.
+//!
+//! Recognising the faces of a frame takes four steps of very different cost. Running them one after the other
+//! on the capture thread means a slow encoding network holds up the capture, and frames pile up or go stale.
+//! The [`Pipeline`] runs each step as a stage with its own worker threads, connected by bounded queues:
+//!
+//! ```text
+//! capture -> detect -> landmark -> encode -> match -> results
+//! ```
+//!
+//! Each stage can have several workers, the [`DropPolicy`] decides what happens when a queue is full,
+//! and [`RunningPipeline::stats`] reports the latency, queue length and dropped frames of every stage.
+//!
+//! ```no_run
+//! use fofscreen::pipeline::{nearest_match, DropPolicy, Pipeline};
+//! use fofscreen::{FaceDetector, FaceEncoderNetwork, LandmarkPredictor};
+//!
+//! # fn run(frames: flume::Receiver<image::RgbImage>, references: Vec<(String, fofscreen::FaceEncoding)>) {
+//! let pipeline = Pipeline::new(
+//!     FaceDetector::new(),
+//!     LandmarkPredictor::default(),
+//!     FaceEncoderNetwork::default(),
+//!     move |encoding| nearest_match(&references, encoding, 0.6),
+//! )
+//! .with_detect_workers(2)
+//! .with_drop_policy(DropPolicy::LatestWins)
+//! .start(frames);
+//!
+//! for frame in pipeline.iter() {
+//!     for face in &frame.faces {
+//!         println!("{:?} {:?}", face.rect, face.identity);
+//!     }
+//! }
+//! # }
+//! ```
+
+mod queue;
+mod stats;
+mod track;
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+use flume::Receiver;
+use image::RgbImage;
+
+pub use self::queue::DropPolicy;
+pub use self::stats::{Latency, PipelineStats, Stage, StageStats};
+pub use self::track::Tracker;
+
+use self::queue::{pop, Queue, QueueProbe};
+use crate::attributes::{self, AttributeClassifier, Estimate, FaceSample};
+use crate::face_detection::FaceDetectorTrait;
+use crate::face_encoding::{FaceEncoderTrait, FaceEncoding};
+use crate::geometry::Rectangle;
+use crate::landmark_prediction::{FaceLandmarks, LandmarkPredictorTrait};
+use crate::liveness::{SpoofAnalysis, SpoofDetector};
+use crate::matrix::ImageMatrix;
+use crate::metrics::Metrics;
+use crate::quality::{Quality, QualityScorer};
+
+/// The reference a face was recognised as.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub identity: String,
+    pub distance: f64,
+}
+
+/// A face found by the pipeline.
+#[derive(Clone, Debug)]
+pub struct RecognisedFace {
+    pub rect: Rectangle,
+    /// The face's track, the same for the face of consecutive frames that overlaps it, see [`Tracker`].
+    pub track_id: u64,
+    pub landmarks: FaceLandmarks,
+    pub encoding: FaceEncoding,
+    /// `None` if the face didn't match any reference, or wasn't matched because of its quality
+    /// or because it looks like a replay.
+    pub identity: Option<Match>,
+    /// `None` if the pipeline doesn't rate faces, see [`Pipeline::with_quality_scorer`].
+    pub quality: Option<Quality>,
+    /// `None` if the pipeline doesn't check faces for replays, see [`Pipeline::with_spoof_detector`],
+    /// or if the face's landmarks can't be [aligned into a chip](ImageMatrix::face_chip).
+    pub spoof: Option<SpoofAnalysis>,
+    /// The estimates of the pipeline's [attribute classifiers](Pipeline::with_attribute_classifier).
+    pub attributes: Vec<Estimate>,
+}
+
+impl RecognisedFace {
+    /// Whether the face was left unmatched because it isn't of acceptable quality.
+    pub fn is_low_quality(&self) -> bool {
+        self.quality
+            .as_ref()
+            .is_some_and(|quality| !quality.acceptable)
+    }
+
+    /// Whether the face looks like a photo or a screen held up to the camera.
+    pub fn is_spoof(&self) -> bool {
+        self.spoof.as_ref().is_some_and(|spoof| spoof.is_spoof)
+    }
+
+    /// The most confident estimate of something hiding part of the face, e.g. a mask.
+    pub fn occlusion(&self) -> Option<&Estimate> {
+        attributes::occlusion(&self.attributes)
+    }
+}
+
+/// A frame that went through every stage of the pipeline.
+#[derive(Clone, Debug)]
+pub struct RecognisedFrame {
+    /// The position of the frame in the capture, counting the frames dropped by the pipeline.
+    pub seq: u64,
+    /// When the frame was taken from the capture.
+    pub captured: SystemTime,
+    pub image: RgbImage,
+    pub faces: Vec<RecognisedFace>,
+    /// The time from taking the frame from the capture to its result being ready.
+    pub latency: Duration,
+}
+
+/// Find the nearest of `references` to `encoding`, if it is at most `threshold` away.
+pub fn nearest_match(
+    references: &[(String, FaceEncoding)],
+    encoding: &FaceEncoding,
+    threshold: f64,
+) -> Option<Match> {
+    references
+        .iter()
+        .map(|(identity, reference)| (identity, encoding.distance(reference)))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .filter(|(_, distance)| *distance <= threshold)
+        .map(|(identity, distance)| Match {
+            identity: identity.clone(),
+            distance,
+        })
+}
+
+/// A frame on its way through the pipeline.
+struct Work {
+    seq: u64,
+    captured_at: Instant,
+    captured: SystemTime,
+    image: RgbImage,
+    matrix: Option<ImageMatrix>,
+    rects: Vec<Rectangle>,
+    landmarks: Vec<FaceLandmarks>,
+    qualities: Vec<Quality>,
+    spoofs: Vec<Option<SpoofAnalysis>>,
+    attributes: Vec<Vec<Estimate>>,
+    encodings: Vec<FaceEncoding>,
+}
+
+impl Work {
+    fn matrix(&self) -> &ImageMatrix {
+        // set by the detect stage, before any other stage sees the frame
+        self.matrix.as_ref().unwrap()
+    }
+}
+
+/// A recognition pipeline, configured with the models for each stage and a matcher.
+///
+/// The detector and encoder modify internal buffers while running, so each worker gets its own clone.
+/// The landmark predictor is shared by its workers. The matcher runs on a single thread and turns encodings
+/// into identities, e.g. with [`nearest_match`].
+pub struct Pipeline<D, P, E, M> {
+    detector: D,
+    predictor: P,
+    encoder: E,
+    matcher: M,
+    detect_workers: usize,
+    landmark_workers: usize,
+    encode_workers: usize,
+    queue_capacity: usize,
+    drop_policy: DropPolicy,
+    num_jitters: u32,
+    quality_scorer: Option<QualityScorer>,
+    spoof_detector: Option<SpoofDetector>,
+    attribute_classifiers: Vec<Arc<dyn AttributeClassifier>>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl<D, P, E, M> Pipeline<D, P, E, M>
+where
+    D: FaceDetectorTrait + Clone + Send + 'static,
+    P: LandmarkPredictorTrait + Send + Sync + 'static,
+    E: FaceEncoderTrait + Clone + Send + 'static,
+    M: FnMut(&FaceEncoding) -> Option<Match> + Send + 'static,
+{
+    /// Create a pipeline with one worker per stage, queues of 2 frames and [`DropPolicy::LatestWins`].
+    pub fn new(detector: D, predictor: P, encoder: E, matcher: M) -> Self {
+        Self {
+            detector,
+            predictor,
+            encoder,
+            matcher,
+            detect_workers: 1,
+            landmark_workers: 1,
+            encode_workers: 1,
+            queue_capacity: 2,
+            drop_policy: DropPolicy::LatestWins,
+            num_jitters: 0,
+            quality_scorer: None,
+            spoof_detector: None,
+            attribute_classifiers: Vec::new(),
+            metrics: None,
+        }
+    }
+
+    pub fn with_detect_workers(mut self, workers: usize) -> Self {
+        self.detect_workers = workers.max(1);
+        self
+    }
+
+    pub fn with_landmark_workers(mut self, workers: usize) -> Self {
+        self.landmark_workers = workers.max(1);
+        self
+    }
+
+    pub fn with_encode_workers(mut self, workers: usize) -> Self {
+        self.encode_workers = workers.max(1);
+        self
+    }
+
+    /// The number of frames each queue holds, including the queue of results.
+    pub fn with_queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity.max(1);
+        self
+    }
+
+    pub fn with_drop_policy(mut self, policy: DropPolicy) -> Self {
+        self.drop_policy = policy;
+        self
+    }
+
+    pub fn with_num_jitters(mut self, num_jitters: u32) -> Self {
+        self.num_jitters = num_jitters;
+        self
+    }
+
+    /// Rate faces with `scorer` in the landmark stage. Faces that aren't of acceptable quality are
+    /// passed on without being matched, and aren't counted as unknown faces.
+    pub fn with_quality_scorer(mut self, scorer: QualityScorer) -> Self {
+        self.quality_scorer = Some(scorer);
+        self
+    }
+
+    /// Check the chips of the faces for replays with `detector` in the landmark stage. Faces that look like
+    /// a photo or a screen aren't matched, so they count as unknown faces whoever they show.
+    pub fn with_spoof_detector(mut self, detector: SpoofDetector) -> Self {
+        self.spoof_detector = Some(detector);
+        self
+    }
+
+    /// Estimate the attributes of faces with `classifier` in the landmark stage, in addition to the
+    /// classifiers added before. Faces are matched whatever their attributes.
+    pub fn with_attribute_classifier<C>(mut self, classifier: C) -> Self
+    where
+        C: AttributeClassifier + 'static,
+    {
+        self.attribute_classifiers.push(Arc::new(classifier));
+        self
+    }
+
+    /// Record the pipeline's metrics in `metrics`, e.g. to serve them with a
+    /// [`MetricsServer`](crate::metrics::MetricsServer). By default, the pipeline keeps its own.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Start the worker threads, recognising the faces of `frames`.
+    ///
+    /// The pipeline takes frames as fast as they arrive, so the capture is never held up
+    /// unless the drop policy is [`DropPolicy::Block`]. It finishes once `frames` disconnects
+    /// and the remaining frames went through, or when the [`RunningPipeline`] is stopped or dropped.
+    pub fn start(self, frames: Receiver<RgbImage>) -> RunningPipeline {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let metrics = self.metrics.clone().unwrap_or_default();
+        let new_queue = |dropped| {
+            Queue::<Work>::new(
+                self.queue_capacity,
+                self.drop_policy,
+                stopped.clone(),
+                dropped,
+            )
+        };
+
+        let detect_queue = new_queue(metrics.dropped_counter(Stage::Detect));
+        let landmark_queue = new_queue(metrics.dropped_counter(Stage::Landmark));
+        let encode_queue = new_queue(metrics.dropped_counter(Stage::Encode));
+        let match_queue = new_queue(metrics.dropped_counter(Stage::Match));
+        let output_queue = Queue::new(
+            self.queue_capacity,
+            self.drop_policy,
+            stopped.clone(),
+            metrics.output_dropped_counter(),
+        );
+
+        let mut threads = vec![];
+        let mut stages = vec![];
+
+        threads.push({
+            let detect_queue = detect_queue.clone();
+            let stopped = stopped.clone();
+            let metrics = metrics.clone();
+
+            thread::spawn(move || {
+                let mut seq = 0;
+
+                while let Some(image) = pop(&frames, &stopped) {
+                    let captured_at = Instant::now();
+                    metrics.record_frame(captured_at);
+
+                    let work = Work {
+                        seq,
+                        captured_at,
+                        captured: SystemTime::now(),
+                        image,
+                        matrix: None,
+                        rects: vec![],
+                        landmarks: vec![],
+                        qualities: vec![],
+                        spoofs: vec![],
+                        attributes: vec![],
+                        encodings: vec![],
+                    };
+
+                    if detect_queue.push(work).is_err() {
+                        break;
+                    }
+                    seq += 1;
+                }
+            })
+        });
+
+        let detector = self.detector;
+        stages.push(spawn_stage(
+            Stage::Detect,
+            self.detect_workers,
+            &detect_queue,
+            &landmark_queue,
+            &stopped,
+            &metrics,
+            &mut threads,
+            || {
+                let detector = detector.clone();
+                move |mut work: Work| {
+                    let matrix = ImageMatrix::from_image(&work.image);
+                    work.rects = detector.face_locations(&matrix).into();
+                    work.matrix = Some(matrix);
+                    work
+                }
+            },
+        ));
+
+        let predictor = Arc::new(self.predictor);
+        let quality_scorer = self.quality_scorer;
+        let spoof_detector = self.spoof_detector;
+        let classifiers = Arc::new(self.attribute_classifiers);
+        stages.push(spawn_stage(
+            Stage::Landmark,
+            self.landmark_workers,
+            &landmark_queue,
+            &encode_queue,
+            &stopped,
+            &metrics,
+            &mut threads,
+            || {
+                let predictor = predictor.clone();
+                let spoof_detector = spoof_detector.clone();
+                let classifiers = classifiers.clone();
+                move |mut work: Work| {
+                    work.landmarks = work
+                        .rects
+                        .iter()
+                        .map(|rect| predictor.face_landmarks(work.matrix(), rect))
+                        .collect();
+
+                    if let Some(scorer) = &quality_scorer {
+                        work.qualities = work
+                            .rects
+                            .iter()
+                            .zip(&work.landmarks)
+                            .map(|(rect, landmarks)| scorer.assess(work.matrix(), rect, landmarks))
+                            .collect();
+                    }
+
+                    // the chips are only needed to check for replays and estimate attributes
+                    if spoof_detector.is_some() || !classifiers.is_empty() {
+                        let mut spoofs = vec![];
+                        let mut attributes = vec![];
+
+                        for landmarks in &work.landmarks {
+                            // faces without a chip can't be checked or classified
+                            let chip = match work.matrix().face_chip(landmarks, 150, 0.25) {
+                                Some(chip) => chip.to_rgb_image(),
+                                None => {
+                                    spoofs.push(None);
+                                    attributes.push(vec![]);
+                                    continue;
+                                }
+                            };
+
+                            if let Some(detector) = &spoof_detector {
+                                spoofs.push(Some(detector.analyse(&chip)));
+                            }
+
+                            let face = FaceSample {
+                                image: work.matrix(),
+                                landmarks,
+                                chip: &chip,
+                            };
+                            attributes.push(
+                                classifiers
+                                    .iter()
+                                    .flat_map(|classifier| classifier.classify(&face))
+                                    .collect(),
+                            );
+                        }
+
+                        work.spoofs = spoofs;
+                        work.attributes = attributes;
+                    }
+                    work
+                }
+            },
+        ));
+
+        let encoder = self.encoder;
+        let num_jitters = self.num_jitters;
+        stages.push(spawn_stage(
+            Stage::Encode,
+            self.encode_workers,
+            &encode_queue,
+            &match_queue,
+            &stopped,
+            &metrics,
+            &mut threads,
+            || {
+                let encoder = encoder.clone();
+                move |mut work: Work| {
+                    if !work.landmarks.is_empty() {
+                        work.encodings = encoder
+                            .get_face_encodings(work.matrix(), &work.landmarks, num_jitters)
+                            .into();
+                    }
+                    work
+                }
+            },
+        ));
+
+        // matching runs on one thread, which also puts the frames back in order and tracks the faces
+        stages.push(StageProbe {
+            stage: Stage::Match,
+            workers: 1,
+            queue: match_queue.probe(),
+        });
+        threads.push({
+            let receiver = match_queue.receiver();
+            let output_queue = output_queue.clone();
+            let stopped = stopped.clone();
+            let metrics = metrics.clone();
+            let mut matcher = self.matcher;
+            let mut tracker = Tracker::default();
+            let in_order = self.drop_policy == DropPolicy::Block;
+
+            thread::spawn(move || {
+                let mut next = 0;
+                let mut pending = BTreeMap::new();
+
+                while let Some(work) = pop(&receiver, &stopped) {
+                    // with LatestWins, frames overtaken by a newer one are out of date
+                    if work.seq < next {
+                        metrics.record_drop(Stage::Match);
+                        continue;
+                    }
+
+                    // with Block, no frame is dropped, so wait for the earlier ones instead
+                    pending.insert(work.seq, work);
+                    while let Some(seq) = pending.keys().next().copied() {
+                        if in_order && seq != next {
+                            break;
+                        }
+
+                        let work = pending.remove(&seq).unwrap();
+                        next = seq + 1;
+
+                        let start = Instant::now();
+                        let (mut frame, captured_at) = recognise(work, &mut matcher, &mut tracker);
+                        metrics.record_stage(Stage::Match, start.elapsed());
+                        frame.latency = captured_at.elapsed();
+
+                        let matched = frame.faces.iter().filter(|face| face.identity.is_some());
+                        let matched = matched.count();
+                        let low_quality = frame.faces.iter().filter(|face| face.is_low_quality());
+                        let unknown = frame.faces.len() - matched - low_quality.count();
+                        metrics.record_result(frame.faces.len(), matched, unknown, frame.latency);
+
+                        if output_queue.push(frame).is_err() {
+                            return;
+                        }
+                    }
+                }
+            })
+        });
+
+        RunningPipeline {
+            output: output_queue.receiver(),
+            stopped,
+            metrics,
+            stages,
+            threads,
+        }
+    }
+}
+
+/// Match and track the faces of a frame, returning the result and when the frame was captured.
+fn recognise<M>(work: Work, matcher: &mut M, tracker: &mut Tracker) -> (RecognisedFrame, Instant)
+where
+    M: FnMut(&FaceEncoding) -> Option<Match>,
+{
+    let mut track_ids = tracker.update(&work.rects).into_iter();
+    let mut qualities = work.qualities.into_iter();
+    let mut spoofs = work.spoofs.into_iter();
+    let mut attributes = work.attributes.into_iter();
+    let faces = work
+        .rects
+        .into_iter()
+        .zip(work.landmarks)
+        .zip(work.encodings)
+        .map(|((rect, landmarks), encoding)| {
+            let quality = qualities.next();
+            let spoof = spoofs.next().flatten();
+            let matchable = quality.as_ref().is_none_or(|quality| quality.acceptable)
+                && spoof.as_ref().is_none_or(|spoof| !spoof.is_spoof);
+
+            RecognisedFace {
+                rect,
+                track_id: track_ids.next().unwrap(),
+                landmarks,
+                identity: if matchable { matcher(&encoding) } else { None },
+                encoding,
+                quality,
+                spoof,
+                attributes: attributes.next().unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    let frame = RecognisedFrame {
+        seq: work.seq,
+        captured: work.captured,
+        image: work.image,
+        faces,
+        latency: Duration::default(),
+    };
+
+    (frame, work.captured_at)
+}
+
+/// A stage, for [`RunningPipeline::stats`] to read its queue.
+struct StageProbe {
+    stage: Stage,
+    workers: usize,
+    queue: QueueProbe,
+}
+
+/// Spawn the workers of a stage, each with its own processing function from `make_worker`.
+#[allow(clippy::too_many_arguments)]
+fn spawn_stage<W, F>(
+    stage: Stage,
+    workers: usize,
+    input: &Queue<Work>,
+    output: &Queue<Work>,
+    stopped: &Arc<AtomicBool>,
+    metrics: &Arc<Metrics>,
+    threads: &mut Vec<JoinHandle<()>>,
+    make_worker: W,
+) -> StageProbe
+where
+    W: Fn() -> F,
+    F: FnMut(Work) -> Work + Send + 'static,
+{
+    for _ in 0..workers {
+        let receiver = input.receiver();
+        let output = output.clone();
+        let stopped = stopped.clone();
+        let metrics = metrics.clone();
+        let mut process = make_worker();
+
+        threads.push(thread::spawn(move || {
+            while let Some(work) = pop(&receiver, &stopped) {
+                let start = Instant::now();
+                let work = process(work);
+                metrics.record_stage(stage, start.elapsed());
+
+                if output.push(work).is_err() {
+                    break;
+                }
+            }
+        }));
+    }
+
+    StageProbe {
+        stage,
+        workers,
+        queue: input.probe(),
+    }
+}
+
+/// The handle to a started [`Pipeline`], to read its results and metrics.
+///
+/// Dropping the handle stops the pipeline and waits for its threads.
+pub struct RunningPipeline {
+    output: Receiver<RecognisedFrame>,
+    stopped: Arc<AtomicBool>,
+    metrics: Arc<Metrics>,
+    stages: Vec<StageProbe>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl RunningPipeline {
+    /// Wait for the next result. Returns `None` once the pipeline has finished.
+    pub fn recv(&self) -> Option<RecognisedFrame> {
+        self.output.recv().ok()
+    }
+
+    /// The next result, if one is ready.
+    pub fn try_recv(&self) -> Option<RecognisedFrame> {
+        self.output.try_recv().ok()
+    }
+
+    /// Iterate over the results until the pipeline has finished.
+    pub fn iter(&self) -> impl Iterator<Item = RecognisedFrame> + '_ {
+        self.output.iter()
+    }
+
+    /// The channel results arrive on, e.g. to forward them or wait on them with other channels.
+    pub fn results(&self) -> &Receiver<RecognisedFrame> {
+        &self.output
+    }
+
+    pub fn stats(&self) -> PipelineStats {
+        PipelineStats {
+            frames_in: self.metrics.frames_captured(),
+            stages: self
+                .stages
+                .iter()
+                .map(|probe| StageStats {
+                    stage: probe.stage,
+                    workers: probe.workers,
+                    queued: probe.queue.len(),
+                    dropped: self.metrics.dropped(probe.stage),
+                    latency: Latency::from(&self.metrics.stage_latency(probe.stage)),
+                })
+                .collect(),
+            output_dropped: self.metrics.output_dropped(),
+            end_to_end: Latency::from(&self.metrics.end_to_end()),
+        }
+    }
+
+    /// The metrics the pipeline records, with latency histograms and face counts.
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// Stop every stage. Frames still in the pipeline are thrown away.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for RunningPipeline {
+    fn drop(&mut self) {
+        self.stop();
+
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::face_detection::FaceLocations;
+    use crate::geometry::Point;
+    use image::Rgb;
+
+    /// Finds one face covering the image, if the image isn't black.
+    #[derive(Clone)]
+    struct FakeDetector;
+
+    impl FaceDetectorTrait for FakeDetector {
+        fn face_locations(&self, image: &ImageMatrix) -> FaceLocations {
+            if image.get_pixel(0, 0).unwrap()[0] == 0 {
+                FaceLocations::new()
+            } else {
+                vec![Rectangle::from_xywh(
+                    0,
+                    0,
+                    image.width() as i64,
+                    image.height() as i64,
+                )]
+                .into()
+            }
+        }
+    }
+
+    struct FakePredictor;
+
+    impl LandmarkPredictorTrait for FakePredictor {
+        fn face_landmarks(&self, _image: &ImageMatrix, rect: &Rectangle) -> FaceLandmarks {
+            vec![
+                Point::new(rect.left, rect.top),
+                Point::new(rect.right, rect.bottom),
+            ]
+            .into()
+        }
+    }
+
+    /// Encodes a face as the brightness of the image.
+    #[derive(Clone)]
+    struct FakeEncoder;
+
+    impl FaceEncoderTrait for FakeEncoder {
+        fn get_face_encodings(
+            &self,
+            image: &ImageMatrix,
+            landmarks: &[FaceLandmarks],
+            _num_jitters: u32,
+        ) -> crate::FaceEncodings {
+            let value = image.get_pixel(0, 0).unwrap()[0] as f64 / 255.0;
+            landmarks
+                .iter()
+                .map(|_| FaceEncoding::new_from_scalar(value))
+                .collect()
+        }
+    }
+
+    fn frame(value: u8) -> RgbImage {
+        RgbImage::from_pixel(8, 6, Rgb([value, value, value]))
+    }
+
+    #[test]
+    fn test_pipeline_in_order() {
+        let references = vec![("bright".to_string(), FaceEncoding::new_from_scalar(1.0))];
+        let (send, recv) = flume::unbounded();
+        for value in [255, 0, 255, 128].iter() {
+            send.send(frame(*value)).unwrap();
+        }
+        drop(send);
+
+        let pipeline = Pipeline::new(FakeDetector, FakePredictor, FakeEncoder, move |encoding| {
+            nearest_match(&references, encoding, 0.6)
+        })
+        .with_detect_workers(3)
+        .with_encode_workers(2)
+        .with_drop_policy(DropPolicy::Block)
+        .start(recv);
+
+        let frames: Vec<RecognisedFrame> = pipeline.iter().collect();
+        assert_eq!(
+            frames.iter().map(|frame| frame.seq).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+
+        assert_eq!(frames[0].faces.len(), 1);
+        assert_eq!(frames[0].faces[0].rect, Rectangle::from_xywh(0, 0, 8, 6));
+        assert_eq!(frames[0].faces[0].landmarks.len(), 2);
+        assert_eq!(
+            frames[0].faces[0].identity.as_ref().unwrap().identity,
+            "bright"
+        );
+        assert!(frames[1].faces.is_empty());
+        assert!(frames[3].faces[0].identity.is_none());
+
+        // the face of frame 2 isn't in the frame before it, so it starts a new track
+        assert_eq!(frames[0].faces[0].track_id, 0);
+        assert_eq!(frames[2].faces[0].track_id, 1);
+        assert_eq!(frames[3].faces[0].track_id, 1);
+
+        let stats = pipeline.stats();
+        assert_eq!(stats.frames_in, 4);
+        assert_eq!(stats.frames_out(), 4);
+        assert_eq!(stats.dropped(), 0);
+        assert_eq!(stats.stage(Stage::Detect).unwrap().workers, 3);
+        assert_eq!(stats.stage(Stage::Detect).unwrap().latency.count, 4);
+    }
+
+    #[test]
+    fn test_pipeline_quality() {
+        let references = vec![("bright".to_string(), FaceEncoding::new_from_scalar(1.0))];
+        let (send, recv) = flume::unbounded();
+        send.send(frame(255)).unwrap();
+        drop(send);
+
+        let metrics = Arc::new(Metrics::new());
+        let pipeline = Pipeline::new(FakeDetector, FakePredictor, FakeEncoder, move |encoding| {
+            nearest_match(&references, encoding, 0.6)
+        })
+        .with_quality_scorer(QualityScorer::default())
+        .with_metrics(metrics.clone())
+        .start(recv);
+
+        // the flat, tiny face would match, but isn't worth matching
+        let frames: Vec<RecognisedFrame> = pipeline.iter().collect();
+        let face = &frames[0].faces[0];
+        assert!(face.is_low_quality());
+        assert!(face.identity.is_none());
+        assert_eq!(face.quality.as_ref().unwrap().face_size, 6.0);
+        assert_eq!(metrics.faces_detected(), 1);
+        assert_eq!(metrics.faces_unknown(), 0);
+    }
+
+    #[test]
+    fn test_pipeline_latest_wins() {
+        let (send, recv) = flume::unbounded();
+        for _ in 0..50 {
+            send.send(frame(255)).unwrap();
+        }
+        drop(send);
+
+        let pipeline = Pipeline::new(
+            FakeDetector,
+            FakePredictor,
+            FakeEncoder,
+            |_: &FaceEncoding| None,
+        )
+        .with_queue_capacity(1)
+        .start(recv);
+
+        // don't read any results until the capture is done, so the queues overflow
+        while pipeline.stats().frames_in < 50 {
+            thread::sleep(Duration::from_millis(5));
+        }
+        let seqs: Vec<u64> = pipeline.iter().map(|frame| frame.seq).collect();
+
+        assert!(seqs.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(seqs.last(), Some(&49));
+
+        let stats = pipeline.stats();
+        assert_eq!(stats.frames_in, 50);
+        assert_eq!(stats.frames_out() - seqs.len() as u64, stats.output_dropped);
+        assert_eq!(
+            stats.frames_out() + stats.dropped(),
+            50 + stats.output_dropped
+        );
+    }
+}
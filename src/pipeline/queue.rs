@@ -0,0 +1,178 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use flume::{Receiver, RecvTimeoutError, SendTimeoutError, Sender, TrySendError};
+
+/// How often threads blocked on a queue check whether the pipeline was stopped.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// What happens when a frame arrives at a stage whose queue is full.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DropPolicy {
+    /// Drop the oldest queued frame to make room, so stages always work on the most recent frames.
+    /// Best for live video, where a late result is worth less than a fresh one.
+    LatestWins,
+    /// Wait for room in the queue, so every frame is processed and slow stages slow down the capture.
+    Block,
+}
+
+/// A bounded queue in front of a stage, shared by the threads pushing to it.
+///
+/// Stage workers read from a [`Queue::receiver`], so the queue disconnects once every clone is dropped.
+pub(crate) struct Queue<T> {
+    send: Sender<T>,
+    recv: Receiver<T>,
+    policy: DropPolicy,
+    stopped: Arc<AtomicBool>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// Reads the length of a queue without keeping it open.
+pub(crate) struct QueueProbe {
+    len: Box<dyn Fn() -> usize + Send + Sync>,
+}
+
+impl<T> Clone for Queue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            send: self.send.clone(),
+            recv: self.recv.clone(),
+            policy: self.policy,
+            stopped: self.stopped.clone(),
+            dropped: self.dropped.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> Queue<T> {
+    /// Create a queue counting the frames it drops with `dropped`.
+    pub(crate) fn new(
+        capacity: usize,
+        policy: DropPolicy,
+        stopped: Arc<AtomicBool>,
+        dropped: Arc<AtomicU64>,
+    ) -> Self {
+        let (send, recv) = flume::bounded(capacity.max(1));
+
+        Self {
+            send,
+            recv,
+            policy,
+            stopped,
+            dropped,
+        }
+    }
+
+    pub(crate) fn receiver(&self) -> Receiver<T> {
+        self.recv.clone()
+    }
+
+    pub(crate) fn probe(&self) -> QueueProbe {
+        let recv = self.recv.clone();
+
+        QueueProbe {
+            len: Box::new(move || recv.len()),
+        }
+    }
+
+    /// Queue an item according to the drop policy. Fails once the pipeline is stopped.
+    pub(crate) fn push(&self, mut item: T) -> Result<(), ()> {
+        loop {
+            if self.stopped.load(Ordering::Relaxed) {
+                return Err(());
+            }
+
+            match self.policy {
+                DropPolicy::LatestWins => match self.send.try_send(item) {
+                    Ok(()) => return Ok(()),
+                    Err(TrySendError::Full(back)) => {
+                        if self.recv.try_recv().is_ok() {
+                            self.dropped.fetch_add(1, Ordering::Relaxed);
+                        }
+                        item = back;
+                    }
+                    Err(TrySendError::Disconnected(_)) => return Err(()),
+                },
+                DropPolicy::Block => match self.send.send_timeout(item, POLL_INTERVAL) {
+                    Ok(()) => return Ok(()),
+                    Err(SendTimeoutError::Timeout(back)) => item = back,
+                    Err(SendTimeoutError::Disconnected(_)) => return Err(()),
+                },
+            }
+        }
+    }
+}
+
+impl QueueProbe {
+    pub(crate) fn len(&self) -> usize {
+        (self.len)()
+    }
+}
+
+/// Wait for the next item. Returns `None` once the pipeline is stopped,
+/// or once every sender is gone and the queue is empty.
+pub(crate) fn pop<T>(receiver: &Receiver<T>, stopped: &AtomicBool) -> Option<T> {
+    loop {
+        if stopped.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(item) => return Some(item),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+}
+
+#[test]
+fn test_drop_policies() {
+    let stopped = Arc::new(AtomicBool::new(false));
+
+    let dropped = Arc::new(AtomicU64::new(0));
+
+    let latest = Queue::new(2, DropPolicy::LatestWins, stopped.clone(), dropped.clone());
+    let probe = latest.probe();
+    let receiver = latest.receiver();
+    for i in 0..5 {
+        latest.push(i).unwrap();
+    }
+    assert_eq!(probe.len(), 2);
+    assert_eq!(dropped.load(Ordering::Relaxed), 3);
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![3, 4]);
+
+    let block = Queue::new(1, DropPolicy::Block, stopped.clone(), dropped.clone());
+    let receiver = block.receiver();
+    block.push(1).unwrap();
+
+    let pusher = {
+        let block = block.clone();
+        std::thread::spawn(move || block.push(2))
+    };
+    // the second push waits until the first item is taken
+    assert_eq!(pop(&receiver, &stopped), Some(1));
+    assert_eq!(pusher.join().unwrap(), Ok(()));
+    assert_eq!(pop(&receiver, &stopped), Some(2));
+    assert_eq!(dropped.load(Ordering::Relaxed), 3);
+
+    // stopping releases blocked producers and consumers
+    block.push(3).unwrap();
+    let pusher = {
+        let block = block.clone();
+        std::thread::spawn(move || block.push(4))
+    };
+    stopped.store(true, Ordering::Relaxed);
+    assert_eq!(pusher.join().unwrap(), Err(()));
+    assert_eq!(pop(&receiver, &stopped), None);
+
+    // without senders, queued items are still delivered before the queue ends
+    let stopped = AtomicBool::new(false);
+    let receiver = {
+        let queue = Queue::new(2, DropPolicy::Block, Arc::default(), Arc::default());
+        queue.push(5).unwrap();
+        queue.receiver()
+    };
+    assert_eq!(pop(&receiver, &stopped), Some(5));
+    assert_eq!(pop(&receiver, &stopped), None);
+}
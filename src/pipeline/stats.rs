@@ -0,0 +1,182 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::metrics::Histogram;
+
+/// The stages of the [`Pipeline`](super::Pipeline), in the order frames go through them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Stage {
+    Detect,
+    Landmark,
+    Encode,
+    Match,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [Stage::Detect, Stage::Landmark, Stage::Encode, Stage::Match];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Detect => "detect",
+            Stage::Landmark => "landmark",
+            Stage::Encode => "encode",
+            Stage::Match => "match",
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How long a number of frames took.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Latency {
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl Latency {
+    /// The mean latency, zero if nothing was recorded.
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::default()
+        } else {
+            self.total / self.count as u32
+        }
+    }
+}
+
+impl From<&Histogram> for Latency {
+    /// Summarise a histogram of latencies in seconds.
+    fn from(histogram: &Histogram) -> Self {
+        Self {
+            count: histogram.count(),
+            total: Duration::from_secs_f64(histogram.sum()),
+            max: Duration::from_secs_f64(histogram.max()),
+        }
+    }
+}
+
+/// A snapshot of one stage of a running pipeline.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StageStats {
+    pub stage: Stage,
+    pub workers: usize,
+    /// Frames waiting in the queue in front of the stage.
+    pub queued: usize,
+    /// Frames the stage never processed, because newer frames replaced them or they arrived out of date.
+    pub dropped: u64,
+    /// The time workers spent processing each frame.
+    pub latency: Latency,
+}
+
+/// A snapshot of a running pipeline, from [`RunningPipeline::stats`](super::RunningPipeline::stats).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PipelineStats {
+    /// Frames taken from the capture.
+    pub frames_in: u64,
+    pub stages: Vec<StageStats>,
+    /// Results dropped because they weren't read before newer ones arrived.
+    pub output_dropped: u64,
+    /// The time from taking a frame from the capture to its result being ready.
+    pub end_to_end: Latency,
+}
+
+impl PipelineStats {
+    /// Frames that went through every stage.
+    pub fn frames_out(&self) -> u64 {
+        self.end_to_end.count
+    }
+
+    /// Frames dropped anywhere in the pipeline.
+    pub fn dropped(&self) -> u64 {
+        self.stages.iter().map(|stage| stage.dropped).sum::<u64>() + self.output_dropped
+    }
+
+    pub fn stage(&self, stage: Stage) -> Option<&StageStats> {
+        self.stages.iter().find(|stats| stats.stage == stage)
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl fmt::Display for PipelineStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "in {} out {} dropped {} latency {:.1}ms (max {:.1}ms)",
+            self.frames_in,
+            self.frames_out(),
+            self.dropped(),
+            millis(self.end_to_end.mean()),
+            millis(self.end_to_end.max),
+        )?;
+
+        for stage in &self.stages {
+            write!(
+                f,
+                " | {} x{} {:.1}ms queued {} dropped {}",
+                stage.stage,
+                stage.workers,
+                millis(stage.latency.mean()),
+                stage.queued,
+                stage.dropped,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_stats() {
+    assert_eq!(Latency::default().mean(), Duration::default());
+
+    let mut histogram = Histogram::latency();
+    histogram.observe_duration(Duration::from_millis(10));
+    histogram.observe_duration(Duration::from_millis(30));
+
+    let latency = Latency::from(&histogram);
+    assert_eq!(latency.count, 2);
+    // seconds as floating point are off by a nanosecond at most
+    assert!((latency.mean().as_secs_f64() - 0.02).abs() < 1e-8);
+    assert!((latency.max.as_secs_f64() - 0.03).abs() < 1e-8);
+
+    let stats = PipelineStats {
+        frames_in: 5,
+        stages: vec![
+            StageStats {
+                stage: Stage::Detect,
+                workers: 2,
+                queued: 1,
+                dropped: 2,
+                latency,
+            },
+            StageStats {
+                stage: Stage::Match,
+                workers: 1,
+                queued: 0,
+                dropped: 1,
+                latency: Latency::default(),
+            },
+        ],
+        output_dropped: 0,
+        end_to_end: latency,
+    };
+
+    assert_eq!(stats.frames_out(), 2);
+    assert_eq!(stats.dropped(), 3);
+    assert_eq!(stats.stage(Stage::Detect).unwrap().workers, 2);
+    assert!(stats.stage(Stage::Encode).is_none());
+    assert_eq!(
+        stats.to_string(),
+        "in 5 out 2 dropped 3 latency 20.0ms (max 30.0ms) \
+         | detect x2 20.0ms queued 1 dropped 2 | match x1 0.0ms queued 0 dropped 1"
+    );
+}
@@ -0,0 +1,93 @@
+use crate::geometry::Rectangle;
+
+/// Follows faces from one frame to the next by how much their rectangles overlap.
+///
+/// Each face of a frame takes the track of the face of the previous frame it overlaps most, as long as their
+/// [IoU](Rectangle::iou) is at least the tracker's minimum. Faces that don't overlap any face of the previous
+/// frame enough start a new track. A face that isn't detected in a frame loses its track.
+#[derive(Clone, Debug)]
+pub struct Tracker {
+    min_iou: f64,
+    next_id: u64,
+    previous: Vec<(Rectangle, u64)>,
+}
+
+impl Default for Tracker {
+    /// A tracker continuing tracks of faces overlapping by an IoU of at least 0.3.
+    fn default() -> Self {
+        Self::new(0.3)
+    }
+}
+
+impl Tracker {
+    pub fn new(min_iou: f64) -> Self {
+        Self {
+            min_iou,
+            next_id: 0,
+            previous: Vec::new(),
+        }
+    }
+
+    /// Assign a track ID to each of the face rectangles of the next frame, in the same order.
+    pub fn update(&mut self, rects: &[Rectangle]) -> Vec<u64> {
+        // pair the faces greedily, the most overlapping first
+        let mut pairs = vec![];
+        for (current, rect) in rects.iter().enumerate() {
+            for (previous, (previous_rect, _)) in self.previous.iter().enumerate() {
+                let iou = rect.iou(previous_rect);
+                if iou >= self.min_iou {
+                    pairs.push((iou, current, previous));
+                }
+            }
+        }
+        pairs.sort_by(|(a, _, _), (b, _, _)| b.total_cmp(a));
+
+        let mut ids = vec![None; rects.len()];
+        let mut taken = vec![false; self.previous.len()];
+        for (_, current, previous) in pairs {
+            if ids[current].is_none() && !taken[previous] {
+                ids[current] = Some(self.previous[previous].1);
+                taken[previous] = true;
+            }
+        }
+
+        let ids: Vec<u64> = ids
+            .into_iter()
+            .map(|id| {
+                id.unwrap_or_else(|| {
+                    self.next_id += 1;
+                    self.next_id - 1
+                })
+            })
+            .collect();
+
+        self.previous = rects.iter().copied().zip(ids.iter().copied()).collect();
+        ids
+    }
+}
+
+#[test]
+fn test_tracker() {
+    let mut tracker = Tracker::default();
+    let left = Rectangle::from_xywh(0, 0, 100, 100);
+    let right = Rectangle::from_xywh(200, 0, 100, 100);
+
+    assert_eq!(tracker.update(&[left, right]), vec![0, 1]);
+
+    // both faces moved a little, and are listed the other way around
+    assert_eq!(
+        tracker.update(&[right.translate(10, 0), left.translate(0, 10)]),
+        vec![1, 0]
+    );
+
+    // a face jumping across the image is a new face, and the one that left doesn't come back
+    assert_eq!(tracker.update(&[left.translate(500, 500)]), vec![2]);
+    assert!(tracker.update(&[]).is_empty());
+    assert_eq!(tracker.update(&[left]), vec![3]);
+
+    // two faces over the same face of the previous frame, only the closest keeps its track
+    assert_eq!(
+        tracker.update(&[left.translate(40, 0), left.translate(5, 5)]),
+        vec![4, 3]
+    );
+}
@@ -0,0 +1,195 @@
+use std::ops::{Deref, DerefMut};
+
+use flume::{Receiver, Sender};
+
+use crate::face_detection::{FaceDetection, FaceDetectorTrait, FaceLocations};
+use crate::face_encoding::{FaceEncoderTrait, FaceEncodings};
+use crate::geometry::Rectangle;
+use crate::landmark_prediction::{FaceLandmarks, LandmarkPredictorTrait};
+use crate::matrix::ImageMatrix;
+
+/// A pool of model instances that can be shared between threads.
+///
+/// Models such as [`crate::FaceEncoderNetwork`] can't be used by two threads at once.
+/// The pool owns a fixed set of instances and lends each one to a single thread at a time,
+/// blocking when all of them are in use. A pool of one model serialises access to a single network.
+///
+/// The pool implements the model traits itself, so it can be used wherever a model is expected.
+pub struct ModelPool<T> {
+    send: Sender<T>,
+    recv: Receiver<T>,
+    size: usize,
+}
+
+/// A model borrowed from a [`ModelPool`], returned to the pool when dropped.
+pub struct PooledModel<'a, T> {
+    model: Option<T>,
+    pool: &'a ModelPool<T>,
+}
+
+impl<T> ModelPool<T> {
+    /// Create a pool from a set of models.
+    pub fn new<I: IntoIterator<Item = T>>(models: I) -> Self {
+        let (send, recv) = flume::unbounded();
+        let mut size = 0;
+
+        for model in models {
+            send.send(model).unwrap();
+            size += 1;
+        }
+
+        Self { send, recv, size }
+    }
+
+    /// Create a pool of `size` clones of a model.
+    pub fn from_clones(model: T, size: usize) -> Self
+    where
+        T: Clone,
+    {
+        Self::new(vec![model; size])
+    }
+
+    /// The number of models owned by the pool, whether lent or not.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Borrow a model, blocking until one is available.
+    ///
+    /// This blocks forever if the pool is empty.
+    pub fn get(&self) -> PooledModel<'_, T> {
+        // the pool holds a sender itself, so the channel can't be disconnected
+        let model = self.recv.recv().unwrap();
+
+        PooledModel {
+            model: Some(model),
+            pool: self,
+        }
+    }
+
+    /// Borrow a model if one is available right now.
+    pub fn try_get(&self) -> Option<PooledModel<'_, T>> {
+        self.recv.try_recv().ok().map(|model| PooledModel {
+            model: Some(model),
+            pool: self,
+        })
+    }
+}
+
+impl<'a, T> Deref for PooledModel<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.model.as_ref().unwrap()
+    }
+}
+
+impl<'a, T> DerefMut for PooledModel<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.model.as_mut().unwrap()
+    }
+}
+
+impl<'a, T> Drop for PooledModel<'a, T> {
+    fn drop(&mut self) {
+        if let Some(model) = self.model.take() {
+            let _ = self.pool.send.send(model);
+        }
+    }
+}
+
+impl<T: FaceDetectorTrait> FaceDetectorTrait for ModelPool<T> {
+    fn face_locations(&self, image: &ImageMatrix) -> FaceLocations {
+        self.get().face_locations(image)
+    }
+
+    fn face_detections(&self, image: &ImageMatrix) -> Vec<FaceDetection> {
+        self.get().face_detections(image)
+    }
+}
+
+impl<T: LandmarkPredictorTrait> LandmarkPredictorTrait for ModelPool<T> {
+    fn face_landmarks(&self, image: &ImageMatrix, rect: &Rectangle) -> FaceLandmarks {
+        self.get().face_landmarks(image, rect)
+    }
+}
+
+impl<T: FaceEncoderTrait> FaceEncoderTrait for ModelPool<T> {
+    fn get_face_encodings(
+        &self,
+        image: &ImageMatrix,
+        landmarks: &[FaceLandmarks],
+        num_jitters: u32,
+    ) -> FaceEncodings {
+        self.get().get_face_encodings(image, landmarks, num_jitters)
+    }
+
+    fn get_face_encodings_batch(
+        &self,
+        faces: &[(&ImageMatrix, &FaceLandmarks)],
+        num_jitters: u32,
+    ) -> FaceEncodings {
+        self.get().get_face_encodings_batch(faces, num_jitters)
+    }
+}
+
+#[test]
+fn test_thread_safety() {
+    use crate::*;
+
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+
+    // plain data
+    assert_send::<ImageMatrix>();
+    assert_sync::<ImageMatrix>();
+    assert_send::<FaceLocations>();
+    assert_sync::<FaceLocations>();
+    assert_send::<FaceLandmarks>();
+    assert_sync::<FaceLandmarks>();
+    assert_send::<FaceEncoding>();
+    assert_sync::<FaceEncoding>();
+    assert_send::<FaceEncodings>();
+    assert_sync::<FaceEncodings>();
+
+    // models that only read their state
+    assert_send::<LandmarkPredictor>();
+    assert_sync::<LandmarkPredictor>();
+
+    // models that mutate their state while running can only be moved, or shared through a pool
+    assert_send::<FaceDetector>();
+    assert_send::<FaceDetectorCnn>();
+    assert_send::<FaceEncoderNetwork>();
+    assert_sync::<ModelPool<FaceDetector>>();
+    assert_sync::<ModelPool<FaceDetectorCnn>>();
+    assert_sync::<ModelPool<FaceEncoderNetwork>>();
+}
+
+#[test]
+fn test_pool_lending() {
+    let pool = ModelPool::new(vec![1, 2]);
+    assert_eq!(pool.size(), 2);
+
+    let a = pool.get();
+    let b = pool.get();
+    assert!(pool.try_get().is_none());
+    assert_eq!(*a + *b, 3);
+
+    drop(a);
+    let mut c = pool.try_get().unwrap();
+    *c += 10;
+    drop(c);
+    drop(b);
+
+    let values: Vec<i32> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..8).map(|_| scope.spawn(|| *pool.get())).collect();
+
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    assert!(values.iter().all(|&v| v == 2 || v == 11));
+
+    // every model made it back to the pool
+    let (a, b) = (pool.get(), pool.get());
+    assert_eq!(*a + *b, 13);
+}
@@ -0,0 +1,426 @@
+//! Face image quality.
+//!
+//! Blurry, tiny, badly lit or turned away faces encode poorly: enrolled, they match strangers,
+//! and as probes they don't match anyone. A [`QualityScorer`] measures a detected face and rates it,
+//! so enrollment and live matching can leave out faces that aren't worth encoding.
+//!
+//! Every measurement is rated from 0 to 1 against its minimum, and the [`Quality::score`] of a face
+//! is its worst rating, so a face is only as good as its weakest aspect.
+
+use std::fmt;
+
+use image::Rgb;
+
+use crate::geometry::{Point, Rectangle};
+use crate::landmark_prediction::FaceLandmarks;
+use crate::matrix::ImageMatrix;
+
+/// Why a face is rated below 1.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum QualityIssue {
+    /// The face is smaller than the minimum size, in pixels.
+    TooSmall {
+        size: f64,
+        min: f64,
+    },
+    /// The variance of the Laplacian of the face is below the minimum.
+    Blurry {
+        sharpness: f64,
+        min: f64,
+    },
+    TooDark {
+        brightness: f64,
+        min: f64,
+    },
+    TooBright {
+        brightness: f64,
+        max: f64,
+    },
+    /// The standard deviation of the face's brightness is below the minimum.
+    LowContrast {
+        contrast: f64,
+        min: f64,
+    },
+    /// The face is turned away, judged by the symmetry of the landmarks around the nose.
+    NotFrontal {
+        frontalness: f64,
+        min: f64,
+    },
+}
+
+impl fmt::Display for QualityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            QualityIssue::TooSmall { size, min } => {
+                write!(f, "face is {:.0}px, at least {:.0}px is needed", size, min)
+            }
+            QualityIssue::Blurry { sharpness, min } => {
+                write!(
+                    f,
+                    "blurry (sharpness {:.0}, at least {:.0})",
+                    sharpness, min
+                )
+            }
+            QualityIssue::TooDark { brightness, min } => {
+                write!(
+                    f,
+                    "too dark (brightness {:.0}, at least {:.0})",
+                    brightness, min
+                )
+            }
+            QualityIssue::TooBright { brightness, max } => {
+                write!(
+                    f,
+                    "too bright (brightness {:.0}, at most {:.0})",
+                    brightness, max
+                )
+            }
+            QualityIssue::LowContrast { contrast, min } => {
+                write!(f, "low contrast ({:.0}, at least {:.0})", contrast, min)
+            }
+            QualityIssue::NotFrontal { frontalness, min } => write!(
+                f,
+                "not facing the camera (frontalness {:.2}, at least {:.2})",
+                frontalness, min
+            ),
+        }
+    }
+}
+
+/// The measurements and rating of a face.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quality {
+    /// The smaller side of the face rectangle, in pixels.
+    pub face_size: f64,
+    /// The variance of the Laplacian of the face's brightness. Higher is sharper.
+    pub sharpness: f64,
+    /// The mean brightness of the face, from 0 to 255.
+    pub brightness: f64,
+    /// The standard deviation of the face's brightness.
+    pub contrast: f64,
+    /// From 0, in profile, to 1, facing the camera. `None` if the landmark model isn't known.
+    pub frontalness: Option<f64>,
+    /// The worst rating of the measurements, from 0 to 1.
+    pub score: f64,
+    /// Whether the score reaches the scorer's minimum.
+    pub acceptable: bool,
+    /// The measurements rated below 1.
+    pub issues: Vec<QualityIssue>,
+}
+
+impl Quality {
+    /// The issues, separated by commas.
+    pub fn reasons(&self) -> String {
+        self.issues
+            .iter()
+            .map(|issue| issue.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Rates faces against minimum measurements.
+///
+/// The defaults suit the dlib models: faces are encoded as 150x150 chips, so faces much smaller than that
+/// are upscaled blur.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct QualityScorer {
+    pub min_face_size: f64,
+    pub min_sharpness: f64,
+    pub min_brightness: f64,
+    pub max_brightness: f64,
+    pub min_contrast: f64,
+    pub min_frontalness: f64,
+    /// Faces scoring below this aren't acceptable.
+    pub min_score: f64,
+}
+
+impl Default for QualityScorer {
+    fn default() -> Self {
+        Self {
+            min_face_size: 80.0,
+            min_sharpness: 100.0,
+            min_brightness: 50.0,
+            max_brightness: 205.0,
+            min_contrast: 25.0,
+            min_frontalness: 0.6,
+            min_score: 0.8,
+        }
+    }
+}
+
+impl QualityScorer {
+    pub fn with_min_face_size(mut self, size: f64) -> Self {
+        self.min_face_size = size;
+        self
+    }
+
+    pub fn with_min_score(mut self, score: f64) -> Self {
+        self.min_score = score;
+        self
+    }
+
+    /// Measure and rate the face in `rect` of `image`.
+    pub fn assess(
+        &self,
+        image: &ImageMatrix,
+        rect: &Rectangle,
+        landmarks: &FaceLandmarks,
+    ) -> Quality {
+        let clipped = rect.clamp_to_image(image.width(), image.height());
+        if clipped.is_empty() {
+            // the face is entirely outside of the image, so there's nothing to measure
+            return self.rate(0.0, 0.0, 0.0, 0.0, frontalness(landmarks));
+        }
+        let (width, height) = (clipped.width() as usize, clipped.height() as usize);
+
+        let mut gray = Vec::with_capacity(width * height);
+        for y in clipped.top as usize..=clipped.bottom as usize {
+            let row = &image.row(y)[clipped.left as usize..=clipped.right as usize];
+            gray.extend(row.iter().map(luma));
+        }
+
+        let (brightness, contrast) = mean_and_std_dev(&gray);
+
+        self.rate(
+            rect.width().min(rect.height()) as f64,
+            laplacian_variance(&gray, width, height),
+            brightness,
+            contrast,
+            frontalness(landmarks),
+        )
+    }
+
+    /// Rate measurements taken elsewhere.
+    pub fn rate(
+        &self,
+        face_size: f64,
+        sharpness: f64,
+        brightness: f64,
+        contrast: f64,
+        frontalness: Option<f64>,
+    ) -> Quality {
+        let mut ratings = vec![];
+        let mut issues = vec![];
+        let mut rate = |rating: f64, issue: QualityIssue| {
+            let rating = rating.clamp(0.0, 1.0);
+            if rating < 1.0 {
+                issues.push(issue);
+            }
+            ratings.push(rating);
+        };
+
+        rate(
+            face_size / self.min_face_size,
+            QualityIssue::TooSmall {
+                size: face_size,
+                min: self.min_face_size,
+            },
+        );
+        rate(
+            sharpness / self.min_sharpness,
+            QualityIssue::Blurry {
+                sharpness,
+                min: self.min_sharpness,
+            },
+        );
+        if brightness < self.min_brightness {
+            rate(
+                brightness / self.min_brightness,
+                QualityIssue::TooDark {
+                    brightness,
+                    min: self.min_brightness,
+                },
+            );
+        } else {
+            rate(
+                (255.0 - brightness) / (255.0 - self.max_brightness),
+                QualityIssue::TooBright {
+                    brightness,
+                    max: self.max_brightness,
+                },
+            );
+        }
+        rate(
+            contrast / self.min_contrast,
+            QualityIssue::LowContrast {
+                contrast,
+                min: self.min_contrast,
+            },
+        );
+        if let Some(frontalness) = frontalness {
+            rate(
+                frontalness / self.min_frontalness,
+                QualityIssue::NotFrontal {
+                    frontalness,
+                    min: self.min_frontalness,
+                },
+            );
+        }
+
+        let score = ratings.into_iter().fold(1.0, f64::min);
+
+        Quality {
+            face_size,
+            sharpness,
+            brightness,
+            contrast,
+            frontalness,
+            score,
+            acceptable: score >= self.min_score,
+            issues,
+        }
+    }
+}
+
+/// The brightness of a pixel from 0 to 255, weighing the channels like BT.601 luma.
+pub fn luma(pixel: &Rgb<u8>) -> f64 {
+    let [r, g, b] = pixel.0;
+    0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64
+}
+
+/// The mean and standard deviation of `values`, 0 for no values.
+fn mean_and_std_dev(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    let len = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / len;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / len;
+
+    (mean, variance.sqrt())
+}
+
+/// The variance of the 4-neighbour Laplacian of a `width` by `height` gray image, over the pixels that have
+/// all their neighbours. Edges make it high, blur makes it low.
+pub fn laplacian_variance(gray: &[f64], width: usize, height: usize) -> f64 {
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+
+    let mut laplacian = Vec::with_capacity((width - 2) * (height - 2));
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let at = |x: usize, y: usize| gray[y * width + x];
+
+            laplacian
+                .push(at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4.0 * at(x, y));
+        }
+    }
+
+    let (_, std_dev) = mean_and_std_dev(&laplacian);
+    std_dev * std_dev
+}
+
+/// How squarely a face looks at the camera, from the distances of the nose to the outer eye corners.
+///
+/// Turning the head brings the nose closer to one eye in the image, so the ratio of the shorter
+/// to the longer distance drops from 1. Supports the 68 and 5 point landmark models.
+pub fn frontalness(parts: &[Point]) -> Option<f64> {
+    let (left_eye, right_eye, nose) = match parts.len() {
+        68 => (parts[36], parts[45], parts[30]),
+        5 => (parts[2], parts[0], parts[4]),
+        _ => return None,
+    };
+
+    let left = nose.distance(&left_eye);
+    let right = nose.distance(&right_eye);
+    let longer = left.max(right);
+
+    if longer == 0.0 {
+        return None;
+    }
+
+    Some(left.min(right) / longer)
+}
+
+#[test]
+fn test_quality_measurements() {
+    let (width, height) = (16, 16);
+    let flat = vec![128.0; width * height];
+    let checkerboard: Vec<f64> = (0..width * height)
+        .map(|i| {
+            if (i % width + i / width) % 2 == 0 {
+                0.0
+            } else {
+                255.0
+            }
+        })
+        .collect();
+
+    assert_eq!(laplacian_variance(&flat, width, height), 0.0);
+    assert!(laplacian_variance(&checkerboard, width, height) > 1000.0);
+    assert_eq!(laplacian_variance(&[1.0, 2.0], 2, 1), 0.0);
+
+    let (mean, std_dev) = mean_and_std_dev(&checkerboard);
+    assert!((mean - 127.5).abs() < 1e-9);
+    assert!((std_dev - 127.5).abs() < 1e-9);
+
+    let mut parts = vec![Point::default(); 68];
+    parts[36] = Point::new(30, 40);
+    parts[45] = Point::new(70, 40);
+    parts[30] = Point::new(50, 60);
+    assert_eq!(frontalness(&parts), Some(1.0));
+
+    // turned to the side, the nose is next to one eye
+    parts[30] = Point::new(66, 60);
+    assert!(frontalness(&parts).unwrap() < 0.5);
+
+    let five = [
+        Point::new(70, 40),
+        Point::new(58, 40),
+        Point::new(30, 40),
+        Point::new(42, 40),
+        Point::new(50, 60),
+    ];
+    assert_eq!(frontalness(&five), Some(1.0));
+    assert_eq!(frontalness(&parts[..10]), None);
+}
+
+#[test]
+fn test_quality_rating() {
+    let scorer = QualityScorer::default();
+
+    let good = scorer.rate(150.0, 400.0, 120.0, 50.0, Some(0.9));
+    assert_eq!(good.score, 1.0);
+    assert!(good.acceptable);
+    assert!(good.issues.is_empty());
+
+    let tiny = scorer.rate(40.0, 400.0, 120.0, 50.0, None);
+    assert_eq!(tiny.score, 0.5);
+    assert!(!tiny.acceptable);
+    assert_eq!(tiny.reasons(), "face is 40px, at least 80px is needed");
+
+    let dark_and_blurry = scorer.rate(150.0, 90.0, 10.0, 50.0, Some(0.9));
+    assert_eq!(dark_and_blurry.score, 0.2);
+    assert_eq!(dark_and_blurry.issues.len(), 2);
+    assert!(matches!(
+        dark_and_blurry.issues[1],
+        QualityIssue::TooDark { .. }
+    ));
+
+    let washed_out = scorer.rate(150.0, 400.0, 250.0, 50.0, None);
+    assert!(matches!(
+        washed_out.issues[0],
+        QualityIssue::TooBright { .. }
+    ));
+    assert!(!washed_out.acceptable);
+
+    // slightly below a minimum is reported, but still acceptable
+    let profile = scorer
+        .with_min_score(0.9)
+        .rate(150.0, 400.0, 120.0, 50.0, Some(0.57));
+    assert_eq!(profile.issues.len(), 1);
+    assert!(profile.acceptable);
+}
+
+#[test]
+fn test_face_outside_image() {
+    let image = ImageMatrix::from_image(&image::RgbImage::new(100, 100));
+    let outside = Rectangle::from_xywh(200, 200, 50, 50);
+
+    let quality = QualityScorer::default().assess(&image, &outside, &FaceLandmarks::default());
+    assert_eq!(quality.score, 0.0);
+    assert!(!quality.acceptable);
+}
@@ -0,0 +1,401 @@
+//! Face recognition as a local HTTP/JSON service, for tools that can't link dlib.
+//!
+//! A [`RecognitionServer`] loads the models once and answers requests with a [`RecognitionService`].
+//! Images are posted as the raw bytes of a JPEG or PNG file, results are returned as JSON:
+//!
+//! | Request | Response |
+//! |---|---|
+//! | `POST /detect` with an image | `{"faces": [{"rect": {..}, "score": 1.0}]}` |
+//! | `POST /landmarks` with an image | `{"faces": [{"rect": {..}, "parts": [[x, y], ..]}]}` |
+//! | `POST /encode` with an image | `{"faces": [{"rect": {..}, "encoding": [..]}]}` |
+//! | `POST /identify` with an image | `{"faces": [{"rect": {..}, "identity": "alice", "distance": 0.41}]}` |
+//! | `POST /compare` with `{"known": [[..], ..], "candidate": [..]}` | `{"distances": [..], "matches": [..]}` |
+//! | `GET /health` | `{"status": "ok", "references": 2}` |
+//!
+//! Errors are returned as `{"error": ".."}` with a 4xx status.
+
+mod server;
+
+use std::convert::TryInto;
+use std::io::Cursor;
+
+use image::io::Reader;
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+use tiny_http::Method;
+
+pub use self::server::RecognitionServer;
+
+use crate::face_detection::FaceDetectorTrait;
+use crate::face_encoding::{FaceEncoderTrait, FaceEncoding};
+use crate::geometry::Rectangle;
+use crate::landmark_prediction::{FaceLandmarks, LandmarkPredictorTrait};
+use crate::matrix::ImageMatrix;
+use crate::pipeline::nearest_match;
+
+/// Uploads larger than this are refused.
+pub const MAX_BODY_BYTES: usize = 32 * 1024 * 1024;
+
+/// Images with more pixels than this, about 8000 x 5000, are refused before they're decoded,
+/// as a small upload can declare an image that takes gigabytes to decode.
+pub const MAX_IMAGE_PIXELS: u64 = 40_000_000;
+
+/// A status code and a JSON body.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reply {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Reply {
+    fn json<T: Serialize>(value: &T) -> Self {
+        Self {
+            status: 200,
+            body: serde_json::to_string(value).unwrap(),
+        }
+    }
+
+    fn error(status: u16, error: impl Into<String>) -> Self {
+        #[derive(Serialize)]
+        struct Error {
+            error: String,
+        }
+
+        Self {
+            status,
+            body: serde_json::to_string(&Error {
+                error: error.into(),
+            })
+            .unwrap(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Faces<T> {
+    faces: Vec<T>,
+}
+
+#[derive(Serialize)]
+struct EncodedFace {
+    rect: Rectangle,
+    encoding: FaceEncoding,
+}
+
+#[derive(Serialize)]
+struct IdentifiedFace {
+    rect: Rectangle,
+    /// `None` if the face didn't match any reference.
+    identity: Option<String>,
+    distance: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct CompareRequest {
+    known: Vec<FaceEncoding>,
+    candidate: FaceEncoding,
+    threshold: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct CompareResponse {
+    distances: Vec<f64>,
+    /// Whether each known encoding is at most the threshold away from the candidate.
+    matches: Vec<bool>,
+}
+
+#[derive(Serialize)]
+struct Health {
+    status: &'static str,
+    references: usize,
+}
+
+/// The width and height declared in the header of an image file, without decoding it.
+fn image_dimensions(body: &[u8]) -> Result<(u32, u32), String> {
+    // the PNG decoder allocates a row of the declared width to read the header, so read it from the IHDR chunk
+    if body.get(12..16) == Some(b"IHDR") && body.len() >= 24 {
+        let number = |start: usize| u32::from_be_bytes(body[start..start + 4].try_into().unwrap());
+        return Ok((number(16), number(20)));
+    }
+
+    Reader::new(Cursor::new(body))
+        .with_guessed_format()
+        .map_err(|why| why.to_string())?
+        .into_dimensions()
+        .map_err(|why| why.to_string())
+}
+
+/// Decode an uploaded JPEG or PNG file, of at most [`MAX_IMAGE_PIXELS`].
+fn decode_image(body: &[u8]) -> Result<ImageMatrix, Reply> {
+    if body.is_empty() {
+        return Err(Reply::error(
+            400,
+            "expected a JPEG or PNG image as the body",
+        ));
+    }
+
+    match image::guess_format(body) {
+        Ok(ImageFormat::Jpeg) | Ok(ImageFormat::Png) => {}
+        _ => return Err(Reply::error(415, "only JPEG and PNG images are supported")),
+    }
+
+    let (width, height) = image_dimensions(body)
+        .map_err(|why| Reply::error(400, format!("can't decode image: {}", why)))?;
+    if width as u64 * height as u64 > MAX_IMAGE_PIXELS {
+        return Err(Reply::error(
+            413,
+            format!(
+                "images are limited to {} pixels, got {}x{}",
+                MAX_IMAGE_PIXELS, width, height
+            ),
+        ));
+    }
+
+    image::load_from_memory(body)
+        .map(|image| ImageMatrix::from_dynamic_image(&image))
+        .map_err(|why| Reply::error(400, format!("can't decode image: {}", why)))
+}
+
+/// Answers recognition requests with a set of models and a gallery of known faces.
+pub struct RecognitionService<D, P, E> {
+    detector: D,
+    predictor: P,
+    encoder: E,
+    references: Vec<(String, FaceEncoding)>,
+    threshold: f64,
+    num_jitters: u32,
+}
+
+impl<D, P, E> RecognitionService<D, P, E>
+where
+    D: FaceDetectorTrait,
+    P: LandmarkPredictorTrait,
+    E: FaceEncoderTrait,
+{
+    /// A service with an empty gallery, a match threshold of 0.6 and no jitter.
+    pub fn new(detector: D, predictor: P, encoder: E) -> Self {
+        Self {
+            detector,
+            predictor,
+            encoder,
+            references: Vec::new(),
+            threshold: 0.6,
+            num_jitters: 0,
+        }
+    }
+
+    /// The known faces `/identify` matches against.
+    pub fn with_references(mut self, references: Vec<(String, FaceEncoding)>) -> Self {
+        self.references = references;
+        self
+    }
+
+    /// Faces at most `threshold` away from a reference are identified as that reference.
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_num_jitters(mut self, num_jitters: u32) -> Self {
+        self.num_jitters = num_jitters;
+        self
+    }
+
+    pub fn references(&self) -> &[(String, FaceEncoding)] {
+        &self.references
+    }
+
+    /// Answer a request for `path` with `body`.
+    pub fn handle(&self, method: &Method, path: &str, body: &[u8]) -> Reply {
+        let expected = match path {
+            "/health" => Method::Get,
+            "/detect" | "/landmarks" | "/encode" | "/identify" | "/compare" => Method::Post,
+            _ => return Reply::error(404, format!("no endpoint at {}", path)),
+        };
+        if *method != expected {
+            return Reply::error(405, format!("{} expects {}", path, expected));
+        }
+
+        let result = match path {
+            "/health" => Ok(Reply::json(&Health {
+                status: "ok",
+                references: self.references.len(),
+            })),
+            "/detect" => self.detect(body),
+            "/landmarks" => self.landmarks(body),
+            "/encode" => self.encode(body),
+            "/identify" => self.identify(body),
+            _ => self.compare(body),
+        };
+
+        result.unwrap_or_else(|reply| reply)
+    }
+
+    fn detect(&self, body: &[u8]) -> Result<Reply, Reply> {
+        let image = decode_image(body)?;
+        let faces = self.detector.face_detections(&image);
+
+        Ok(Reply::json(&Faces { faces }))
+    }
+
+    fn find_landmarks(&self, image: &ImageMatrix) -> Vec<FaceLandmarks> {
+        self.detector
+            .face_locations(image)
+            .iter()
+            .map(|rect| self.predictor.face_landmarks(image, rect))
+            .collect()
+    }
+
+    fn landmarks(&self, body: &[u8]) -> Result<Reply, Reply> {
+        let image = decode_image(body)?;
+        let faces = self.find_landmarks(&image);
+
+        Ok(Reply::json(&Faces { faces }))
+    }
+
+    fn encode_faces(&self, image: &ImageMatrix) -> Vec<EncodedFace> {
+        let landmarks = self.find_landmarks(image);
+        let encodings = self
+            .encoder
+            .get_face_encodings(image, &landmarks, self.num_jitters);
+
+        landmarks
+            .iter()
+            .zip(encodings.iter())
+            .map(|(landmarks, encoding)| EncodedFace {
+                rect: landmarks.rect(),
+                encoding: encoding.clone(),
+            })
+            .collect()
+    }
+
+    fn encode(&self, body: &[u8]) -> Result<Reply, Reply> {
+        let image = decode_image(body)?;
+        let faces = self.encode_faces(&image);
+
+        Ok(Reply::json(&Faces { faces }))
+    }
+
+    fn identify(&self, body: &[u8]) -> Result<Reply, Reply> {
+        let image = decode_image(body)?;
+        let faces = self
+            .encode_faces(&image)
+            .into_iter()
+            .map(|face| {
+                let found = nearest_match(&self.references, &face.encoding, self.threshold);
+
+                IdentifiedFace {
+                    rect: face.rect,
+                    distance: found.as_ref().map(|m| m.distance),
+                    identity: found.map(|m| m.identity),
+                }
+            })
+            .collect();
+
+        Ok(Reply::json(&Faces { faces }))
+    }
+
+    fn compare(&self, body: &[u8]) -> Result<Reply, Reply> {
+        let request: CompareRequest = serde_json::from_slice(body)
+            .map_err(|why| Reply::error(400, format!("invalid compare request: {}", why)))?;
+        let threshold = request.threshold.unwrap_or(self.threshold);
+
+        let distances: Vec<f64> = request
+            .known
+            .iter()
+            .map(|known| known.distance(&request.candidate))
+            .collect();
+        let matches = distances
+            .iter()
+            .map(|distance| *distance <= threshold)
+            .collect();
+
+        Ok(Reply::json(&CompareResponse { distances, matches }))
+    }
+}
+
+/// Stands in for every model in tests that never get as far as running one.
+#[cfg(test)]
+pub(crate) struct NoModel;
+
+#[cfg(test)]
+impl FaceDetectorTrait for NoModel {
+    fn face_locations(&self, _: &ImageMatrix) -> crate::face_detection::FaceLocations {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+impl LandmarkPredictorTrait for NoModel {
+    fn face_landmarks(&self, _: &ImageMatrix, _: &Rectangle) -> FaceLandmarks {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+impl FaceEncoderTrait for NoModel {
+    fn get_face_encodings(
+        &self,
+        _: &ImageMatrix,
+        _: &[FaceLandmarks],
+        _: u32,
+    ) -> crate::face_encoding::FaceEncodings {
+        unreachable!()
+    }
+}
+
+#[test]
+fn test_service_errors() {
+    let service = RecognitionService::new(NoModel, NoModel, NoModel);
+    let error = |reply: Reply| -> (u16, serde_json::Value) {
+        let body: serde_json::Value = serde_json::from_str(&reply.body).unwrap();
+        assert!(body["error"].is_string(), "{}", reply.body);
+        (reply.status, body)
+    };
+
+    let reply = service.handle(&Method::Get, "/health", b"");
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.body, r#"{"status":"ok","references":0}"#);
+
+    assert_eq!(error(service.handle(&Method::Get, "/nope", b"")).0, 404);
+    assert_eq!(error(service.handle(&Method::Get, "/detect", b"")).0, 405);
+    assert_eq!(error(service.handle(&Method::Post, "/health", b"")).0, 405);
+
+    // images are checked before they reach the models
+    assert_eq!(error(service.handle(&Method::Post, "/detect", b"")).0, 400);
+    assert_eq!(
+        error(service.handle(&Method::Post, "/identify", b"GIF89a....")).0,
+        415
+    );
+    let (status, body) =
+        error(service.handle(&Method::Post, "/encode", b"\x89PNG\r\n\x1a\n broken"));
+    assert_eq!(status, 400);
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("can't decode image"));
+
+    // the size of an image is checked before it's decoded
+    let mut huge = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+    huge.extend_from_slice(&100_000u32.to_be_bytes());
+    huge.extend_from_slice(&100_000u32.to_be_bytes());
+    huge.extend_from_slice(&[8, 2, 0, 0, 0]);
+    let (status, body) = error(service.handle(&Method::Post, "/detect", &huge));
+    assert_eq!(status, 413);
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .ends_with("got 100000x100000"));
+
+    let (status, body) = error(service.handle(&Method::Post, "/compare", b"{\"known\": 1}"));
+    assert_eq!(status, 400);
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("invalid compare request"));
+
+    let short = br#"{"known": [[0.1, 0.2, 0.3]], "candidate": [0.1, 0.2, 0.3]}"#;
+    let (status, body) = error(service.handle(&Method::Post, "/compare", short));
+    assert_eq!(status, 400);
+    assert!(body["error"].as_str().unwrap().contains("got 3"));
+}
@@ -0,0 +1,111 @@
+use std::io::Read;
+use std::net::SocketAddr;
+
+use tiny_http::{Header, Response};
+
+use super::{RecognitionService, Reply, MAX_BODY_BYTES};
+use crate::face_detection::FaceDetectorTrait;
+use crate::face_encoding::FaceEncoderTrait;
+use crate::http::{self, HttpServer};
+use crate::landmark_prediction::LandmarkPredictorTrait;
+
+/// Serves a [`RecognitionService`] over HTTP on a background thread.
+///
+/// Requests are answered one at a time, as the detectors and the encoder can't be shared between threads.
+/// The server stops when dropped.
+pub struct RecognitionServer {
+    server: HttpServer,
+}
+
+fn read_body(reader: &mut dyn Read) -> Result<Vec<u8>, Reply> {
+    let mut body = Vec::new();
+    reader
+        .take(MAX_BODY_BYTES as u64 + 1)
+        .read_to_end(&mut body)
+        .map_err(|why| Reply::error(400, format!("can't read request: {}", why)))?;
+
+    if body.len() > MAX_BODY_BYTES {
+        return Err(Reply::error(
+            413,
+            format!("requests are limited to {} bytes", MAX_BODY_BYTES),
+        ));
+    }
+
+    Ok(body)
+}
+
+impl RecognitionServer {
+    /// Listen on `addr`, e.g. `127.0.0.1:9899`. Port 0 picks a free port, see [`RecognitionServer::addr`].
+    pub fn start<D, P, E>(addr: &str, service: RecognitionService<D, P, E>) -> Result<Self, String>
+    where
+        D: FaceDetectorTrait + Send + 'static,
+        P: LandmarkPredictorTrait + Send + 'static,
+        E: FaceEncoderTrait + Send + 'static,
+    {
+        let server = HttpServer::start(addr, move |request| {
+            let path = http::path(request).to_string();
+            let method = request.method().clone();
+
+            let reply = match read_body(request.as_reader()) {
+                Ok(body) => service.handle(&method, &path, &body),
+                Err(reply) => reply,
+            };
+
+            let header = Header::from_bytes(&b"Content-Type"[..], "application/json").unwrap();
+            Response::from_string(reply.body)
+                .with_status_code(reply.status)
+                .with_header(header)
+        })
+        .map_err(|why| format!("Can't serve recognition on {}: {}", addr, why))?;
+
+        Ok(Self { server })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.server.addr()
+    }
+
+    /// Serve until the process is killed.
+    pub fn join(self) {
+        self.server.join();
+    }
+}
+
+#[test]
+fn test_recognition_server() {
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    use super::NoModel;
+
+    fn request(addr: SocketAddr, method: &str, path: &str, body: &[u8]) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.0\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n",
+            method,
+            path,
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(body).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    let service = RecognitionService::new(NoModel, NoModel, NoModel);
+    let server = RecognitionServer::start("127.0.0.1:0", service).unwrap();
+
+    let response = request(server.addr(), "GET", "/health?verbose=1", b"");
+    assert!(response.starts_with("HTTP/1.0 200"));
+    assert!(response.contains("Content-Type: application/json"));
+    assert!(response.ends_with(r#"{"status":"ok","references":0}"#));
+
+    let response = request(server.addr(), "POST", "/detect", b"not an image");
+    assert!(response.starts_with("HTTP/1.0 415"));
+    assert!(response.ends_with(r#"{"error":"only JPEG and PNG images are supported"}"#));
+
+    assert!(request(server.addr(), "GET", "/", b"").starts_with("HTTP/1.0 404"));
+}
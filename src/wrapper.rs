@@ -47,18 +47,172 @@ cpp! {{
 
     using face_detection_cnn = dlib::loss_mmod<dlib::con<1,9,9,1,1,rcon5<rcon5<rcon5<downsampler<dlib::input_rgb_image_pyramid<dlib::pyramid_down<6>>>>>>>>;
 
+    // pixel format conversion
+
+    // values of `PixelFormat` in src/matrix.rs
+    enum pixel_format : uint32_t {
+        PIXEL_FORMAT_RGB = 0,
+        PIXEL_FORMAT_RGBA = 1,
+        PIXEL_FORMAT_BGR = 2,
+        PIXEL_FORMAT_BGRA = 3,
+        PIXEL_FORMAT_LUMA = 4,
+        PIXEL_FORMAT_YUYV = 5,
+        PIXEL_FORMAT_NV12 = 6,
+    };
+
+    // BT.601 limited range, as produced by webcams
+    inline dlib::rgb_pixel yuv_to_rgb(const int y, const int u, const int v) {
+        const int c = y - 16;
+        const int d = u - 128;
+        const int e = v - 128;
+
+        auto clamp = [](const int value) -> unsigned char {
+            return value < 0 ? 0 : (value > 255 ? 255 : value);
+        };
+
+        return dlib::rgb_pixel(
+            clamp((298 * c + 409 * e + 128) >> 8),
+            clamp((298 * c - 100 * d - 208 * e + 128) >> 8),
+            clamp((298 * c + 516 * d + 128) >> 8)
+        );
+    }
+
+    // copy a raw buffer into a new rgb matrix, the buffer size is checked on the rust side
+    dlib::matrix<dlib::rgb_pixel> matrix_from_raw(const uint8_t* data, const size_t width, const size_t height, const size_t stride, const uint32_t format) {
+        dlib::matrix<dlib::rgb_pixel> image(height, width);
+
+        for (size_t y = 0; y < height; y++) {
+            const uint8_t* row = data + y * stride;
+
+            switch (format) {
+                case PIXEL_FORMAT_RGB:
+                    for (size_t x = 0; x < width; x++) {
+                        image(y, x) = dlib::rgb_pixel(row[x * 3], row[x * 3 + 1], row[x * 3 + 2]);
+                    }
+                    break;
+                case PIXEL_FORMAT_RGBA:
+                    for (size_t x = 0; x < width; x++) {
+                        image(y, x) = dlib::rgb_pixel(row[x * 4], row[x * 4 + 1], row[x * 4 + 2]);
+                    }
+                    break;
+                case PIXEL_FORMAT_BGR:
+                    for (size_t x = 0; x < width; x++) {
+                        image(y, x) = dlib::rgb_pixel(row[x * 3 + 2], row[x * 3 + 1], row[x * 3]);
+                    }
+                    break;
+                case PIXEL_FORMAT_BGRA:
+                    for (size_t x = 0; x < width; x++) {
+                        image(y, x) = dlib::rgb_pixel(row[x * 4 + 2], row[x * 4 + 1], row[x * 4]);
+                    }
+                    break;
+                case PIXEL_FORMAT_LUMA:
+                    for (size_t x = 0; x < width; x++) {
+                        image(y, x) = dlib::rgb_pixel(row[x], row[x], row[x]);
+                    }
+                    break;
+                case PIXEL_FORMAT_YUYV:
+                    // Y0 U Y1 V, two pixels share their chroma
+                    for (size_t x = 0; x < width; x++) {
+                        const uint8_t* pair = row + (x / 2) * 4;
+                        image(y, x) = yuv_to_rgb(pair[(x % 2) * 2], pair[1], pair[3]);
+                    }
+                    break;
+                case PIXEL_FORMAT_NV12: {
+                    // a full resolution Y plane followed by a half resolution interleaved UV plane
+                    const uint8_t* uv = data + height * stride + (y / 2) * stride;
+                    for (size_t x = 0; x < width; x++) {
+                        image(y, x) = yuv_to_rgb(row[x], uv[(x / 2) * 2], uv[(x / 2) * 2 + 1]);
+                    }
+                    break;
+                }
+            }
+        }
+
+        return image;
+    }
+
     // misc
 
-    // TODO: I am unsure if having rnd as a global here is thread safe.
+    // mirrors `FaceDetection` in src/face_detection/detection.rs
+    struct face_detection {
+        dlib::rectangle rect;
+        double score;
+    };
+
+    // mirrors `JitterOptions` in src/face_encoding/jitter.rs
+    struct jitter_options {
+        uint64_t seed;
+        double max_rotation_degrees;
+        double min_object_height;
+        double max_object_height;
+        double translate_amount;
+        bool randomly_flip;
+    };
+
+    // dlib::jitter_image with its constants taken from `options`
+    // https://github.com/davisking/dlib/blob/master/dlib/image_transforms/interpolation.h
+    dlib::matrix<dlib::rgb_pixel> jitter_crop(const dlib::matrix<dlib::rgb_pixel>& img, dlib::rand& rnd, const jitter_options& options) {
+        const auto rect = dlib::shrink_rect(dlib::get_rect(img), 3);
+
+        // perturb the location of the crop by a small fraction of the object's size
+        const dlib::point rand_translate = dlib::dpoint(
+            rnd.get_double_in_range(-options.translate_amount, options.translate_amount) * rect.width(),
+            rnd.get_double_in_range(-options.translate_amount, options.translate_amount) * rect.height()
+        );
 
-    dlib::rand rnd;
+        // perturb the scale of the crop by a fraction of the object's size
+        const double rand_scale_perturb = rnd.get_double_in_range(options.min_object_height, options.max_object_height);
+
+        const long box_size = rect.height() / rand_scale_perturb;
+        const auto crop_rect = dlib::centered_rect(dlib::center(rect) + rand_translate, box_size, box_size);
+        const double angle = rnd.get_double_in_range(-options.max_rotation_degrees, options.max_rotation_degrees) * dlib::pi / 180;
+
+        dlib::matrix<dlib::rgb_pixel> crop;
+        dlib::extract_image_chip(img, dlib::chip_details(crop_rect, dlib::chip_dims(img.nr(), img.nc()), angle), crop);
+
+        if (options.randomly_flip && rnd.get_random_double() > 0.5) {
+            dlib::flip_image_left_right(crop);
+        }
+
+        return crop;
+    }
 
     // https://github.com/davisking/dlib/blob/master/tools/python/src/face_recognition.cpp#L131
-    std::vector<dlib::matrix<dlib::rgb_pixel>> jitter_image(const dlib::matrix<dlib::rgb_pixel>& img, const int num_jitters) {
+    std::vector<dlib::matrix<dlib::rgb_pixel>> jitter_image(const dlib::matrix<dlib::rgb_pixel>& img, const int num_jitters, dlib::rand& rnd, const jitter_options& options) {
         std::vector<dlib::matrix<dlib::rgb_pixel>> crops;
         for (int i = 0; i < num_jitters; ++i) {
-            crops.push_back(dlib::jitter_image(img, rnd));
+            crops.push_back(jitter_crop(img, rnd, options));
         }
         return crops;
     }
+
+    // run the encoding network over aligned face chips, averaging over jittered copies if requested.
+    // the jitter rng is local to the call and seeded from `options.seed`, so results don't depend on previous calls or other threads
+    std::vector<dlib::matrix<double,0,1>> encode_face_chips(face_encoding_nn& net, const dlib::array<dlib::matrix<dlib::rgb_pixel>>& face_chips, const unsigned int num_jitters, const jitter_options& options) {
+        std::vector<dlib::matrix<double,0,1>> encodings;
+        encodings.reserve(face_chips.size());
+
+        // extract descriptors and convert from float vectors to double vectors
+
+        if (num_jitters <= 1) {
+            auto network_output = net(face_chips, 16);
+            for (auto& float_encoding : network_output) {
+                encodings.push_back((dlib::matrix_cast<double>(float_encoding)));
+            }
+        } else {
+            dlib::rand rnd;
+
+            for (auto& chip : face_chips) {
+                // reseed for every face so its encoding doesn't depend on the other faces in the batch
+                rnd.set_seed(dlib::cast_to_string(options.seed));
+
+                auto network_output = net(jitter_image(chip, num_jitters, rnd, options), 16);
+                dlib::matrix<float,0,1> float_encoding = dlib::mean(dlib::mat(network_output));
+
+                encodings.push_back(dlib::matrix_cast<double>(float_encoding));
+            }
+        }
+
+        return encodings;
+    }
 }}
@@ -19,10 +19,11 @@ fn load_image(filename: &str) -> RgbImage {
 
 #[cfg(feature = "embed-all")]
 lazy_static! {
-    static ref DETECTOR: FaceDetector = FaceDetector::default();
-    static ref DETECTOR_CNN: FaceDetectorCnn = FaceDetectorCnn::default();
+    // detectors and the encoder aren't `Sync`, so tests share them through pools
+    static ref DETECTOR: ModelPool<FaceDetector> = ModelPool::from_clones(FaceDetector::default(), 2);
+    static ref DETECTOR_CNN: ModelPool<FaceDetectorCnn> = ModelPool::from_clones(FaceDetectorCnn::default(), 1);
     static ref PREDICTOR: LandmarkPredictor = LandmarkPredictor::default();
-    static ref MODEL: FaceEncoderNetwork = FaceEncoderNetwork::default();
+    static ref MODEL: ModelPool<FaceEncoderNetwork> = ModelPool::from_clones(FaceEncoderNetwork::default(), 1);
 //
     static ref OBAMA_1: RgbImage = load_image("obama_1.jpg");
     static ref OBAMA_2: RgbImage = load_image("obama_2.jpg");
@@ -65,6 +66,21 @@ fn test_face_detection() {
     assert_eq!(DETECTOR.face_locations(&OBAMA_1_MATRIX).len(), 1);
 }
 
+#[cfg(feature = "embed-all")]
+#[test]
+fn test_ensemble_detection() {
+    initialize();
+
+    // the same face found at two scales is only reported once
+    let ensemble = EnsembleDetector::new()
+        .with_detector(FaceDetector::default())
+        .with_detector(ScaledDetector::new(FaceDetector::default(), 0.5).unwrap());
+
+    let detections = ensemble.face_detections(&OBAMA_1_MATRIX);
+    assert_eq!(detections.len(), 1);
+    assert_eq!(ensemble.face_locations(&OBAMA_1_MATRIX).len(), 1);
+}
+
 // This benchmark is super slow to run, so turn it off by default
 /*
 #[test]
@@ -115,3 +131,179 @@ fn encoding_distances() {
     let distance = a_encoding.distance(b_encoding);
     assert!(distance > 0.0 && distance < 0.6);
 }
+
+#[cfg(feature = "embed-all")]
+#[test]
+fn test_batched_enrollment() {
+    use fofscreen::enrollment::Enroller;
+
+    initialize();
+
+    let assets = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets");
+    let images = vec![
+        ("obama".to_string(), assets.join("obama.jpg")),
+        ("missing".to_string(), assets.join("missing.jpg")),
+        ("frag".to_string(), assets.join("frag.jpeg")),
+    ];
+
+    let mut calls = 0;
+    let detector = FaceDetector::default();
+    let report = Enroller::new(&detector, &*PREDICTOR, &*MODEL)
+        .with_workers(2)
+        .enroll(images, |progress| {
+            calls += 1;
+            assert_eq!(progress.total, 3);
+        });
+
+    assert_eq!(calls, 3);
+    assert_eq!(report.enrolled.len(), 2);
+    assert_eq!(report.enrolled[0].identity, "obama");
+    assert_eq!(report.enrolled[1].identity, "frag");
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.errors[0].0, assets.join("missing.jpg"));
+
+    // batching must not change the encodings
+    let obama = load_image("obama.jpg");
+    let matrix = ImageMatrix::from_image(&obama);
+    let rect = report.enrolled[0].rect;
+    let landmarks = PREDICTOR.face_landmarks(&matrix, &rect);
+    let encoding = &MODEL.get_face_encodings(&matrix, &[landmarks], 0)[0];
+
+    assert!(encoding.distance(&report.enrolled[0].encoding) < 1e-6);
+}
+
+#[cfg(feature = "embed-all")]
+#[test]
+fn test_jitter_seed_is_reproducible() {
+    initialize();
+
+    let rect = DETECTOR.face_locations(&OBAMA_1_MATRIX)[0];
+    let landmarks = [PREDICTOR.face_landmarks(&OBAMA_1_MATRIX, &rect)];
+
+    let mut model = MODEL.get().clone();
+    model.set_jitter_seed(42);
+    assert_eq!(model.jitter_seed(), 42);
+    assert_eq!(model.jitter_options().seed, 42);
+
+    let a = &model.get_face_encodings(&OBAMA_1_MATRIX, &landmarks, 5)[0];
+    let b = &model.get_face_encodings(&OBAMA_1_MATRIX, &landmarks, 5)[0];
+    assert_eq!(a, b);
+
+    model.set_jitter_seed(7);
+    let c = &model.get_face_encodings(&OBAMA_1_MATRIX, &landmarks, 5)[0];
+    assert_ne!(a, c);
+    assert!(a.distance(c) < 0.1);
+
+    // without any randomness left, every crop is the same regardless of the seed
+    let fixed = JitterOptions {
+        seed: 1,
+        max_rotation_degrees: 0.0,
+        min_object_height: 1.0,
+        max_object_height: 1.0,
+        translate_amount: 0.0,
+        randomly_flip: false,
+    };
+    let d = &model
+        .get_face_encodings_with_jitter(&OBAMA_1_MATRIX, &landmarks, 5, &fixed)
+        .unwrap()[0];
+    let e = &model
+        .get_face_encodings_with_jitter(
+            &OBAMA_1_MATRIX,
+            &landmarks,
+            5,
+            &JitterOptions { seed: 2, ..fixed },
+        )
+        .unwrap()[0];
+    assert_eq!(d, e);
+}
+
+#[cfg(feature = "embed-all")]
+#[test]
+fn test_pipeline_recognition() {
+    use fofscreen::pipeline::{nearest_match, DropPolicy, Pipeline};
+
+    initialize();
+
+    let rect = DETECTOR.face_locations(&OBAMA_1_MATRIX)[0];
+    let landmarks = PREDICTOR.face_landmarks(&OBAMA_1_MATRIX, &rect);
+    let obama = MODEL.get_face_encodings(&OBAMA_1_MATRIX, &[landmarks], 0)[0].clone();
+    let references = vec![("obama".to_string(), obama)];
+
+    let (send, recv) = flume::unbounded();
+    send.send(OBAMA_2.clone()).unwrap();
+    send.send(OBAMA_1.clone()).unwrap();
+    drop(send);
+
+    let pipeline = Pipeline::new(
+        FaceDetector::default(),
+        PREDICTOR.clone(),
+        MODEL.get().clone(),
+        move |encoding| nearest_match(&references, encoding, 0.6),
+    )
+    .with_detect_workers(2)
+    .with_drop_policy(DropPolicy::Block)
+    .start(recv);
+
+    let frames: Vec<_> = pipeline.iter().collect();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].image.dimensions(), OBAMA_2.dimensions());
+
+    for frame in &frames {
+        assert_eq!(frame.faces.len(), 1);
+        assert_eq!(frame.faces[0].identity.as_ref().unwrap().identity, "obama");
+    }
+    assert!(frames[1].faces[0].identity.as_ref().unwrap().distance < 1e-6);
+    assert_eq!(frames[1].faces[0].rect, rect);
+    assert_eq!(pipeline.stats().frames_out(), 2);
+}
+
+#[cfg(feature = "embed-all")]
+#[test]
+fn test_recognition_service() {
+    use fofscreen::service::RecognitionService;
+    use tiny_http::Method;
+
+    initialize();
+
+    let rect = DETECTOR.face_locations(&OBAMA_1_MATRIX)[0];
+    let landmarks = PREDICTOR.face_landmarks(&OBAMA_1_MATRIX, &rect);
+    let obama = MODEL.get_face_encodings(&OBAMA_1_MATRIX, &[landmarks], 0)[0].clone();
+
+    let service = RecognitionService::new(
+        FaceDetector::default(),
+        PREDICTOR.clone(),
+        MODEL.get().clone(),
+    )
+    .with_references(vec![("obama".to_string(), obama.clone())]);
+
+    let jpeg = std::fs::read(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("assets")
+            .join("obama_2.jpg"),
+    )
+    .unwrap();
+    let json = |body: &str| -> serde_json::Value { serde_json::from_str(body).unwrap() };
+
+    let reply = service.handle(&Method::Post, "/landmarks", &jpeg);
+    assert_eq!(reply.status, 200);
+    assert_eq!(
+        json(&reply.body)["faces"][0]["parts"]
+            .as_array()
+            .unwrap()
+            .len(),
+        68
+    );
+
+    let reply = service.handle(&Method::Post, "/identify", &jpeg);
+    assert_eq!(reply.status, 200);
+    let faces = json(&reply.body)["faces"].clone();
+    assert_eq!(faces.as_array().unwrap().len(), 1);
+    assert_eq!(faces[0]["identity"], "obama");
+
+    let reply = service.handle(&Method::Post, "/encode", &jpeg);
+    let encoding = json(&reply.body)["faces"][0]["encoding"].clone();
+    let compare = serde_json::json!({ "known": [obama], "candidate": encoding });
+    let reply = service.handle(&Method::Post, "/compare", compare.to_string().as_bytes());
+    assert_eq!(reply.status, 200);
+    assert_eq!(json(&reply.body)["matches"], serde_json::json!([true]));
+}